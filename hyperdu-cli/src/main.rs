@@ -145,6 +145,16 @@ enum TimeKindArg {
     Ctime,
 }
 
+impl From<TimeKindArg> for hyperdu_core::age::TimeKind {
+    fn from(t: TimeKindArg) -> Self {
+        match t {
+            TimeKindArg::Mtime => Self::Mtime,
+            TimeKindArg::Atime => Self::Atime,
+            TimeKindArg::Ctime => Self::Ctime,
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum PerfArg {
     Turbo,
@@ -207,17 +217,77 @@ struct Args {
     #[arg(
         long = "exclude-from",
         long_help = "1行に1パターンを記載した除外パターンファイルを読み込みます。\n\
-    行頭接頭辞で種別を指定: 're:' は正規表現、'glob:' はglob、それ以外は部分一致として扱います。\n\
-    例: re:^\\.cache$, glob:**/build/**"
+    行頭接頭辞で種別を指定: 're:' は正規表現、'glob:' はglob、'expr:' は --filter と同じ述語式（一致するファイルを除外）、それ以外は部分一致として扱います。\n\
+    例: re:^\\.cache$, glob:**/build/**, expr:size<4K and name~*.tmp"
     )]
     exclude_from: Vec<PathBuf>,
+    /// Comma-separated globs; count only files whose path matches (e.g. *.log)
+    #[arg(
+        long = "include",
+        value_name = "GLOBS",
+        long_help = "カンマ区切りのglob。パスが一致するファイル（とシンボリックリンク）だけを集計します。ディレクトリは引き続き走査されるため、サブディレクトリ内の一致ファイルも数えられます。--include-regex と併用した場合はどちらかに一致すれば集計します。\n\
+    例: --include '*.log,*.log.gz'"
+    )]
+    include: Option<String>,
+    /// Regex on the full path; count only matching files (repeatable)
+    #[arg(
+        long = "include-regex",
+        value_name = "REGEX",
+        long_help = "フルパスに対する正規表現。一致するファイルだけを集計します（複数指定可）。\n\
+    例: --include-regex '/logs?/.*\\.txt$'"
+    )]
+    include_regex: Vec<String>,
+    /// find-like predicate; count only matching files (repeatable, all must hold)
+    #[arg(
+        long = "filter",
+        value_name = "EXPR",
+        long_help = "find 風の述語式。式を満たすファイルだけを集計します（複数指定時はすべてを満たすもの）。ディレクトリは引き続き走査されます。\n\
+    述語は 項目 演算子 値 を空白なしで書き、and/&&・or/||・not/!・括弧で組み合わせます（並べただけの場合は and）。\n\
+    size（論理サイズ。K/M/G/T/P は1024単位、KB/MB.. は1000単位）, mtime/atime/ctime（経過時間。s/m/h/d/w/y、単位なしは日）: = != < <= > >=\n\
+    type（f=ファイル, l=シンボリックリンク, d=ディレクトリ, o=その他）, owner/group（名前またはID）: = !=\n\
+    name（ファイル名）, path（フルパス）: = != と ~ !~（glob）\n\
+    例: --filter 'size>100M and mtime>90d'  --filter 'name~*.iso or (owner=build and not type=l)'"
+    )]
+    filter: Vec<String>,
+    /// Skip files matched by .gitignore/.ignore/.duignore in each directory
+    #[arg(
+        long = "ignore-files",
+        long_help = "各ディレクトリの .gitignore / .ignore / .duignore を読み、一致するファイル・ディレクトリを集計から外します。\n\
+    深い階層のファイルが浅い階層を上書きし（!pattern で再包含）、同じディレクトリでは .duignore > .ignore > .gitignore の順に優先されます。\n\
+    スキャンルートがリポジトリ内にある場合は、リポジトリのルート（.git のあるディレクトリ）までの上位の規則も適用します。.git ディレクトリは常に無視扱いです。\n\
+    --exclude を指定しない場合、既定の除外（.git,node_modules,target）は無効になります。"
+    )]
+    ignore_files: bool,
+    /// Like --ignore-files, but walk ignored paths and report their bytes separately
+    #[arg(
+        long = "count-ignored",
+        long_help = "--ignore-files と同じ判定を行いますが、無視対象も走査し、そのサイズを通常の集計とは別に Ignored として表示します（JSONでは \"ignored\"）。\n\
+    例: モノレポで追跡対象の容量とビルド成果物などの容量を分けて確認する"
+    )]
+    count_ignored: bool,
+    /// Walk excluded paths and report bytes/files removed by each exclude pattern
+    #[arg(
+        long = "count-excluded",
+        long_help = "除外パターン（--exclude / --exclude-from の部分一致・正規表現・glob）に一致したパスも走査し、パターンごとに除外されたファイル数とサイズを表示します。合計値は除外時と同じです。\n\
+    除外ディレクトリ以下はすべてそのディレクトリに一致したパターンに計上されます。除外対象も走査するため、その分スキャンは遅くなります。\n\
+    例: --exclude node_modules,target --count-excluded"
+    )]
+    count_excluded: bool,
+    /// Write per-pattern exclusion JSON report to path (implies --count-excluded)
+    #[arg(
+        long = "exclude-report",
+        value_name = "PATH",
+        long_help = "除外パターンごとの種別・ファイル数・ディレクトリ数・論理/物理サイズをJSONへ出力します（--count-excluded を含意）。"
+    )]
+    exclude_report: Option<PathBuf>,
 
     /// Maximum depth (0 = unlimited)
     #[arg(
         long = "max-depth",
         default_value_t = 0,
         long_help = "走査の最大深さ。0は無制限。\n\
-    1はルート直下のみ、2はその子まで…といった指定になります。"
+    1はルート直下のみ、2はその子まで…といった指定になります。\n\
+    du互換出力ではGNU duと同様にすべて走査し、この深さまでの行のみを出力します。"
     )]
     max_depth: u32,
 
@@ -286,22 +356,29 @@ struct Args {
     /// Write CSV to path
     #[arg(
         long,
-        long_help = "CSVを指定パスに出力します（HyperDU標準出力時）。列: path, logical, physical, files"
+        long_help = "CSVを指定パスに出力します（HyperDU標準出力時）。列: path, logical, physical, files, dirs, symlinks, max_mtime, min_atime, exclusive（--age 指定時は older_<N>d 列が続きます）"
     )]
     csv: Option<PathBuf>,
 
     /// Write JSON to path
     #[arg(
         long,
-        long_help = "JSONを指定パスに出力します（HyperDU標準出力時）。配列要素に各エントリの統計を出力。"
+        long_help = "JSONを指定パスに出力します（HyperDU標準出力時）。配列要素に各エントリの統計を出力（読み取れなかったディレクトリには error を付与）。"
     )]
     json: Option<PathBuf>,
 
+    /// Write the JSON report as {entries, errors} instead of a bare array
+    #[arg(
+        long = "json-errors",
+        long_help = "--json の出力を配列ではなく {\"entries\": [...], \"errors\": [...]} 形式にします。errors には読み取れなかった全パス（path, kind, errno, syscall）が入ります（一覧に出ないパスも含む）。"
+    )]
+    json_errors: bool,
+
     /// Classify files by type: basic or deep
     #[arg(
         long = "classify",
         value_name = "MODE",
-        long_help = "ファイル種別の分類を実施します: basic|deep。deepは先頭バイトによるMIME推定を行い、若干低速です。"
+        long_help = "ファイル種別の分類をスキャン中に実施します: basic|deep。basicは拡張子で分類します。deepは先頭バイトによるMIME推定を別スレッドで行い、若干低速です。"
     )]
     classify: Option<String>,
 
@@ -321,6 +398,120 @@ struct Args {
     )]
     class_report_csv: Option<PathBuf>,
 
+    /// Break usage down by owning user and group
+    #[arg(
+        long = "by-owner",
+        action = ArgAction::SetTrue,
+        long_help = "所有ユーザー/グループ（uid/gid）別の使用量を集計して表示します。名前はpasswd/groupデータベースで解決します（Windowsでは未対応）。"
+    )]
+    by_owner: bool,
+
+    /// Write per-owner JSON report to path
+    #[arg(
+        long = "owner-report",
+        value_name = "PATH",
+        long_help = "所有者別の集計（全体と各ディレクトリ）をJSONへ出力します（--by-owner 指定時）。"
+    )]
+    owner_report: Option<PathBuf>,
+
+    /// Write per-owner CSV report to path
+    #[arg(
+        long = "owner-report-csv",
+        value_name = "PATH",
+        long_help = "所有者別の集計をCSVへ出力します（--by-owner 指定時）。列: path, kind, id, name, logical, physical, files"
+    )]
+    owner_report_csv: Option<PathBuf>,
+
+    /// Collect a log2 file-size histogram per directory
+    #[arg(
+        long = "histogram",
+        action = ArgAction::SetTrue,
+        long_help = "ディレクトリごとにファイルサイズのlog2ヒストグラムを集計し、ルートの分布を表示します。--json 出力にも size_histogram として含めます。"
+    )]
+    histogram: bool,
+
+    /// Bucket file usage by age (30/90/365 days by default)
+    #[arg(
+        long = "age",
+        action = ArgAction::SetTrue,
+        long_help = "ディレクトリごとにファイルの経過日数（既定 30/90/365日）で使用量を集計し、ルートの内訳を表示します。基準の時刻は --time-kind（mtime/atime/ctime、既定mtime）で選びます。CSV には older_<N>d 列、--json には age として含めます。"
+    )]
+    age: bool,
+    /// Comma-separated age thresholds in days for --age (implies --age)
+    #[arg(
+        long = "age-days",
+        value_name = "DAYS",
+        long_help = "--age の区切り日数をカンマ区切りで指定します（例: 7,30,180）。指定すると --age も有効になります。"
+    )]
+    age_days: Option<String>,
+
+    /// Report sparse files and allocation slack per directory
+    #[arg(
+        long = "sparse",
+        action = ArgAction::SetTrue,
+        long_help = "スパースファイル（割り当てが論理サイズの半分未満）の上位と、ディレクトリごとの割り当てスラック（物理サイズ−論理サイズ）を表示します。物理サイズの計算が必要です（--logical-only や --perf turbo では空になります）。"
+    )]
+    sparse: bool,
+    /// Write sparse/slack JSON report to path
+    #[arg(
+        long = "sparse-report",
+        value_name = "PATH",
+        long_help = "スパース/スラックの集計をJSONへ出力します（--sparse 指定時）。"
+    )]
+    sparse_report: Option<PathBuf>,
+
+    /// Report hardlink groups (inodes with several links)
+    #[arg(
+        long = "hardlinks",
+        action = ArgAction::SetTrue,
+        long_help = "複数のリンクを持つファイルを (dev, ino) ごとにまとめ、パス・リンク数・サイズを表示します（Windowsでは未対応）。"
+    )]
+    hardlinks: bool,
+    /// Split hardlinked bytes evenly across the directories that link them
+    #[arg(
+        long = "hardlink-split",
+        action = ArgAction::SetTrue,
+        long_help = "ハードリンクされたファイルのサイズを、リンクを持つディレクトリ間で均等に按分して集計します。既定では最初に走査したディレクトリに全量が計上されます（重複排除なしのモードでは各リンクに全量）。ファイル数は変わりません。"
+    )]
+    hardlink_split: bool,
+    /// Write hardlink group JSON report to path
+    #[arg(
+        long = "hardlink-report",
+        value_name = "PATH",
+        long_help = "ハードリンクのグループをJSONへ出力します（--hardlinks 指定時）。"
+    )]
+    hardlink_report: Option<PathBuf>,
+
+    /// Report shared and exclusive extent bytes via FIEMAP (Linux)
+    #[arg(
+        long = "extents",
+        action = ArgAction::SetTrue,
+        long_help = "各ファイルのエクステントを FIEMAP で調べ、他のファイルと共有されているバイト（reflink/重複排除/スナップショット）と専有バイトをディレクトリごとに集計します。unique は共有エクステントを一度だけ数えた量で、専有と合わせて削除で実際に解放される量の目安です（走査範囲外からの参照は判別できません）。btrfs でも物理サイズの計算を維持します。FIEMAP 非対応の環境では物理サイズを専有として扱います。各ファイルを開くため低速です。"
+    )]
+    extents: bool,
+    /// Write extent sharing JSON report to path
+    #[arg(
+        long = "extent-report",
+        value_name = "PATH",
+        long_help = "共有/専有エクステントの集計をJSONへ出力します（--extents 指定時）。"
+    )]
+    extent_report: Option<PathBuf>,
+
+    /// Break usage down per mounted filesystem and list skipped mounts
+    #[arg(
+        long = "mounts",
+        action = ArgAction::SetTrue,
+        long_help = "/proc/self/mountinfo を基に、各ディレクトリのファイルをそれが載っているファイルシステム（デバイス）に割り当て、マウントごとの合計を表示します。ルート以下にあるのに走査しなかったマウント（--one-file-system で除外されたものなど）も一覧します。Linux以外では空になります。"
+    )]
+    mounts: bool,
+    /// Write per-filesystem JSON report to path
+    #[arg(
+        long = "mount-report",
+        value_name = "PATH",
+        long_help = "マウントごとの合計、走査しなかったマウント、ディレクトリごとのデバイスをJSONへ出力します（--mounts 指定時）。"
+    )]
+    mount_report: Option<PathBuf>,
+
     /// Incremental snapshot DB path (sled)
     #[arg(
         long = "incremental-db",
@@ -329,6 +520,14 @@ struct Args {
     )]
     incr_db: Option<PathBuf>,
 
+    /// Visit the largest directories of a previous JSON report first
+    #[arg(
+        long = "prev-report",
+        value_name = "PATH",
+        long_help = "以前の --json レポートのディレクトリ別ファイル数/サイズを使い、前回大きかったサブツリーから先に走査します（負荷分散と途中結果の改善）。パスは前回と同じ表記のルートで一致させます。--incremental-db にスナップショットがある場合は指定がなくてもそれを使います。"
+    )]
+    prev_report: Option<PathBuf>,
+
     /// Compute delta against snapshot DB
     #[arg(
         long = "compute-delta",
@@ -353,11 +552,49 @@ struct Args {
     )]
     watch: bool,
 
+    /// Periodically save scan state to PATH
+    #[arg(
+        long = "checkpoint",
+        value_name = "PATH",
+        long_help = "未処理ディレクトリのキューと途中までの集計を定期的に PATH へ保存します（--checkpoint-every 秒ごと、Ctrl-C による中断時にも保存）。正常に完了すると削除されます。ルートは1つのみ指定できます。"
+    )]
+    checkpoint: Option<PathBuf>,
+    /// Checkpoint interval in seconds (default 60)
+    #[arg(
+        long = "checkpoint-every",
+        value_name = "SECS",
+        default_value_t = 60,
+        long_help = "チェックポイントの保存間隔（秒）。既定は60。保存中はワーカーを一時停止します。"
+    )]
+    checkpoint_every: u64,
+    /// Resume a scan from a checkpoint file
+    #[arg(
+        long = "resume",
+        value_name = "CHECKPOINT",
+        long_help = "--checkpoint で保存した状態からスキャンを再開します。ルートはチェックポイントから取得します（指定する場合は同じルートのみ）。保存時と同じオプションで実行してください。--checkpoint を省略すると同じファイルへ引き続き保存します。ファイル単位の集計（-a, --classify, --by-owner など）とは併用できません。"
+    )]
+    resume: Option<PathBuf>,
+
+    /// Stop after SECS seconds and estimate the rest
+    #[arg(
+        long = "budget-secs",
+        value_name = "SECS",
+        long_help = "指定秒数でスキャンを打ち切り、読み終えたディレクトリの階層ごとの平均（ファイル数・サイズ・サブディレクトリ数）から未走査部分の合計を推定します。正確な途中集計と並べて推定値と95%信頼区間を表示します（JSONではルート要素の estimate）。"
+    )]
+    budget_secs: Option<f64>,
+    /// Stop after N files and estimate the rest
+    #[arg(
+        long = "budget-files",
+        value_name = "N",
+        long_help = "指定ファイル数を数えた時点でスキャンを打ち切り、残りを推定します（--budget-secs と併用可、先に達した方で停止）。"
+    )]
+    budget_files: Option<u64>,
+
     /// Print intermittent progress to stderr
     #[arg(
         long,
         action = ArgAction::SetTrue,
-        long_help = "処理件数や一部のサンプルパスを定期的にstderrへ表示します。"
+        long_help = "処理件数や一部のサンプルパスを定期的にstderrへ表示します。スキャン途中の上位ディレクトリと未完了ディレクトリ数も HYPERDU_PROGRESS_LIVE_SECS 秒ごと（既定5秒）に表示します。"
     )]
     progress: bool,
     /// Progress emission frequency (files). Default 8192
@@ -407,6 +644,30 @@ struct Args {
     )]
     uring_depth: Option<usize>,
 
+    /// Limit directory reads and stat calls to N per second
+    #[arg(
+        long = "max-iops",
+        value_name = "N",
+        long_help = "ディレクトリ読み取り（getdents等）とstat系呼び出しの合計を毎秒N回までに制限します（全ワーカー共通）。本番サーバでディスクを占有しないための設定です。"
+    )]
+    max_iops: Option<u64>,
+
+    /// Cap on in-flight io_uring STATX requests per worker (Linux only)
+    #[arg(
+        long = "uring-max-inflight",
+        value_name = "N",
+        long_help = "ワーカーごとに同時に発行するio_uring STATX要求数の上限（Linuxのみ）。--uring-batch/--uring-depth とライブチューナはこの値を超えません。"
+    )]
+    uring_max_inflight: Option<usize>,
+
+    /// Run scan workers in the idle I/O priority class (Linux only)
+    #[arg(
+        long = "ioprio-idle",
+        action = ArgAction::SetTrue,
+        long_help = "スキャンのワーカースレッドをアイドルI/O優先度クラスで実行します（Linuxのみ、ionice -c3 相当）。他のI/Oが待っていない時だけディスクを使います。効果はBFQ/CFQスケジューラ使用時のみです。"
+    )]
+    ioprio_idle: bool,
+
     /// Disable io_uring backend (Linux) even if available
     #[arg(
         long = "no-uring",
@@ -557,6 +818,37 @@ struct Args {
     du互換出力ではブロック数計算に論理サイズを用い、物理サイズの取得を省略します。"
     )]
     apparent_size: bool,
+    /// List inode usage instead of block usage
+    #[arg(
+        long = "inodes",
+        action = ArgAction::SetTrue,
+        long_help = "ブロック数の代わりにinode数を出力します（du互換出力）。ディレクトリ自身・ファイル・シンボリックリンク・特殊ファイルを1つずつ数え、ハードリンクは --count-links がない限り1回だけ数えます。"
+    )]
+    inodes: bool,
+    /// Display only a total for each root
+    #[arg(
+        short = 's',
+        long = "summarize",
+        action = ArgAction::SetTrue,
+        long_help = "各ルートの合計のみを出力します（du互換出力、--max-depth=0 相当）。"
+    )]
+    summarize: bool,
+    /// Print a grand total of all roots
+    #[arg(
+        short = 'c',
+        long = "total",
+        action = ArgAction::SetTrue,
+        long_help = "全ルートの総計を最後に「total」行として出力します（du互換）。複数ルートは1つのワーカープールでまとめて走査し、ルート間のハードリンクや入れ子のルートは1回だけ数えます。"
+    )]
+    total: bool,
+    /// Write counts for all files, not just directories
+    #[arg(
+        short = 'a',
+        long = "all",
+        action = ArgAction::SetTrue,
+        long_help = "ディレクトリだけでなくファイルの行も出力します（du互換出力）。"
+    )]
+    all: bool,
     /// Use SI units (K=1000, M=1000^2, G=1000^3) for -k/-m/-g and --block-size suffixes
     #[arg(
         long = "si",
@@ -604,7 +896,7 @@ struct Args {
     #[arg(
         long = "time-kind",
         value_enum,
-        long_help = "--time で出力する時刻の種類: mtime, atime, ctime。"
+        long_help = "--time で出力する時刻、および --age の基準とする時刻の種類: mtime, atime, ctime。"
     )]
     time_kind: Option<TimeKindArg>,
     /// Time style: iso, long-iso, full-iso (default: iso)
@@ -713,13 +1005,18 @@ fn main() -> Result<()> {
     let mut exclude_contains: Vec<String> = args
         .exclude
         .as_deref()
-        .unwrap_or(".git,node_modules,target")
+        .unwrap_or(if args.ignore_files || args.count_ignored {
+            ""
+        } else {
+            ".git,node_modules,target"
+        })
         .split(',')
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty())
         .collect();
     let mut exclude_regex: Vec<String> = Vec::new();
     let mut exclude_glob: Vec<String> = Vec::new();
+    let mut filter_expr = args.filter.clone();
     for f in &args.exclude_from {
         if let Ok(text) = std::fs::read_to_string(f) {
             for line in text.lines() {
@@ -731,6 +1028,8 @@ fn main() -> Result<()> {
                     exclude_regex.push(rest.trim().to_string());
                 } else if let Some(rest) = s.strip_prefix("glob:") {
                     exclude_glob.push(rest.trim().to_string());
+                } else if let Some(rest) = s.strip_prefix("expr:") {
+                    filter_expr.push(format!("not ({})", rest.trim()));
                 } else {
                     exclude_contains.push(s.to_string());
                 }
@@ -738,6 +1037,15 @@ fn main() -> Result<()> {
         }
     }
 
+    let include_glob: Vec<String> = args
+        .include
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
     let threads = args.threads.unwrap_or_else(|| {
         std::thread::available_parallelism()
             .map(|n| n.get())
@@ -745,9 +1053,12 @@ fn main() -> Result<()> {
     });
 
     let mut opt = hyperdu_core::OptionsBuilder::new()
-        .with_exclude_contains(exclude_contains)
+        .with_exclude_contains(exclude_contains.clone())
         .with_exclude_regex(exclude_regex)
         .with_exclude_glob(exclude_glob)
+        .with_include_glob(include_glob)
+        .with_include_regex(args.include_regex.clone())
+        .with_filter_expr(filter_expr)
         .max_depth(args.max_depth)
         .min_file_size(args.min_file_size)
         .follow_links(args.follow_links)
@@ -775,7 +1086,9 @@ fn main() -> Result<()> {
         let _ = ctrlc::set_handler(move || {
             cancel.store(true, std::sync::atomic::Ordering::Relaxed);
             if !notified2.swap(true, std::sync::atomic::Ordering::Relaxed) {
-                eprintln!("signal: cancelling… 現在までの集計を出力します");
+                eprintln!(
+                    "signal: cancelling… 現在までの集計を出力します（未完了の値には ~ が付きます）"
+                );
             }
         });
     }
@@ -898,7 +1211,19 @@ fn main() -> Result<()> {
     if args.approximate {
         opt.approximate_sizes = true;
     }
+    opt.extent_accounting = args.extents;
     opt.one_file_system = args.one_file_system;
+    opt.ignore_files = args.ignore_files || args.count_ignored;
+    opt.ignore_report = args.count_ignored;
+    if args.count_excluded || args.exclude_report.is_some() {
+        opt.exclude_stats = Some(std::sync::Arc::new(
+            hyperdu_core::exclusions::ExcludeStats::new(),
+        ));
+    }
+    if opt.ignore_files && args.exclude.is_none() {
+        // The builder keeps the default excludes for an empty list
+        opt.exclude_contains = exclude_contains;
+    }
     if args.follow_links && !matches!(opt.compat_mode, hyperdu_core::CompatMode::HyperDU) {
         opt.visited_bloom = Some(std::sync::Arc::new(hyperdu_core::Bloom::with_bits(1 << 20)));
         opt.visited_dirs = Some(std::sync::Arc::new(dashmap::DashMap::with_capacity(1024)));
@@ -911,6 +1236,18 @@ fn main() -> Result<()> {
         opt.uring_sq_depth
             .store(d.max(1), std::sync::atomic::Ordering::Relaxed);
     }
+    opt.io_ops_per_sec = args.max_iops.unwrap_or(0);
+    opt.ioprio_idle = args.ioprio_idle;
+    if let Some(cap) = args.uring_max_inflight {
+        opt.uring_max_inflight = cap;
+        for a in [&opt.uring_batch, &opt.uring_sq_depth] {
+            let v = a.load(std::sync::atomic::Ordering::Relaxed);
+            a.store(
+                hyperdu_core::throttle::cap_inflight(cap, v),
+                std::sync::atomic::Ordering::Relaxed,
+            );
+        }
+    }
 
     // Tuning-only mode: probe several candidates quickly and exit
     if args.tune_only {
@@ -1170,7 +1507,7 @@ fn main() -> Result<()> {
     }
     if let Some(n) = args.dir_yield_every {
         opt.dir_yield_every
-            .store(n.max(0), std::sync::atomic::Ordering::Relaxed);
+            .store(n, std::sync::atomic::Ordering::Relaxed);
     }
     opt.progress_every = args.progress_every.unwrap_or(8192);
     let print_progress = args.progress;
@@ -1196,6 +1533,7 @@ fn main() -> Result<()> {
     let depth_candidates_fast: [u32; 4] = [256, 512, 1024, 2048];
     let depth_candidates_slow: [u32; 4] = [128, 256, 512, 1024];
     let d_atomic = opt.uring_sq_depth.clone();
+    let inflight_cap = opt.uring_max_inflight;
     // metrics snapshot
     let m_prev = std::sync::Arc::new(std::sync::Mutex::new((0u64, 0u64, 0u64, 0u64))); // fail, wait_ns, enq, cqe
     let m_fail = opt.uring_sqe_fail.clone();
@@ -1318,9 +1656,10 @@ fn main() -> Result<()> {
                     as usize;
                 if new_bidx != *bidx {
                     *bidx = new_bidx;
-                    let new_b = batch_candidates[*bidx];
-                    b_atomic.store(new_b, std::sync::atomic::Ordering::Relaxed);
-                    if print_tune {
+                    let new_b =
+                        hyperdu_core::throttle::cap_inflight(inflight_cap, batch_candidates[*bidx]);
+                    let old_b = b_atomic.swap(new_b, std::sync::atomic::Ordering::Relaxed);
+                    if print_tune && old_b != new_b {
                         eprintln!("[live-tune] uring_batch -> {new_b}");
                     }
                 }
@@ -1337,9 +1676,12 @@ fn main() -> Result<()> {
                     (*didx as isize + 1).clamp(0, (depth_candidates.len() - 1) as isize) as usize;
                 if new_didx != *didx {
                     *didx = new_didx;
-                    let new_d = depth_candidates[*didx] as usize;
-                    d_atomic.store(new_d, std::sync::atomic::Ordering::Relaxed);
-                    if print_tune {
+                    let new_d = hyperdu_core::throttle::cap_inflight(
+                        inflight_cap,
+                        depth_candidates[*didx] as usize,
+                    );
+                    let old_d = d_atomic.swap(new_d, std::sync::atomic::Ordering::Relaxed);
+                    if print_tune && old_d != new_d {
                         eprintln!("[live-tune] uring_depth -> {new_d}");
                     }
                 }
@@ -1353,9 +1695,12 @@ fn main() -> Result<()> {
                         as usize;
                     if new_didx != *didx {
                         *didx = new_didx;
-                        let new_d = depth_candidates[*didx] as usize;
-                        d_atomic.store(new_d, std::sync::atomic::Ordering::Relaxed);
-                        if print_tune {
+                        let new_d = hyperdu_core::throttle::cap_inflight(
+                            inflight_cap,
+                            depth_candidates[*didx] as usize,
+                        );
+                        let old_d = d_atomic.swap(new_d, std::sync::atomic::Ordering::Relaxed);
+                        if print_tune && old_d != new_d {
                             eprintln!("[live-tune] uring_depth -> {new_d}");
                         }
                     }
@@ -1383,6 +1728,58 @@ fn main() -> Result<()> {
     } else {
         args.roots.clone()
     };
+    // Checkpoint / resume: a single root, taken from the checkpoint when resuming
+    let roots = match &args.resume {
+        Some(path) => {
+            let cp = hyperdu_core::checkpoint::Checkpoint::load(path)?;
+            if !args.roots.is_empty() && args.roots != [cp.root()] {
+                anyhow::bail!(
+                    "checkpoint {} is for {}; omit ROOTS or pass the same root",
+                    path.display(),
+                    cp.root().display()
+                );
+            }
+            eprintln!(
+                "resume: {} directories pending under {}",
+                cp.pending_dirs(),
+                cp.root().display()
+            );
+            let root = cp.root().to_path_buf();
+            opt.resume_from = Some(Arc::new(cp));
+            opt.checkpoint_path = Some(args.checkpoint.clone().unwrap_or_else(|| path.clone()));
+            vec![root]
+        }
+        None => {
+            if args.checkpoint.is_some() && roots.len() > 1 {
+                anyhow::bail!("--checkpoint takes a single root");
+            }
+            opt.checkpoint_path = args.checkpoint.clone();
+            roots
+        }
+    };
+    opt.checkpoint_every_secs = args.checkpoint_every;
+    opt.budget_ms = args.budget_secs.map_or(0, |s| (s * 1000.0).max(1.0) as u64);
+    opt.budget_files = args.budget_files.unwrap_or(0);
+    // Largest-first order from an earlier report, else from the snapshot DB
+    let size_hints = match (&args.prev_report, &args.incr_db) {
+        (Some(report), _) => Some(hyperdu_core::hints::SizeHints::from_report(report)?),
+        (None, Some(dbp)) if dbp.exists() => {
+            let db = hyperdu_core::incremental::open_db(dbp)?;
+            let mut hints = hyperdu_core::hints::SizeHints::default();
+            for root in &roots {
+                hints.extend(hyperdu_core::incremental::size_hints(&db, root)?);
+            }
+            Some(hints)
+        }
+        _ => None,
+    };
+    if let Some(hints) = size_hints.filter(|h| !h.is_empty()) {
+        eprintln!(
+            "order: largest-first from {} known directories",
+            hints.len()
+        );
+        opt.size_hints = Some(Arc::new(hints));
+    }
 
     // Quick Win: Minimal FS detection to improve defaults on DrvFS/Network FS
     #[cfg(target_os = "linux")]
@@ -1423,7 +1820,13 @@ fn main() -> Result<()> {
                     if !rep.changes.is_empty() {
                         meta.push(format!("changes=[{}]", rep.changes.join(",")));
                     }
-                    println!("fs-auto: {} for '{}'", meta.join(" "), root0.display());
+                    // Keep du-compatible stdout clean
+                    let line = format!("fs-auto: {} for '{}'", meta.join(" "), root0.display());
+                    if matches!(opt.compat_mode, hyperdu_core::CompatMode::HyperDU) {
+                        println!("{line}");
+                    } else {
+                        eprintln!("{line}");
+                    }
                 }
             }
         }
@@ -1432,10 +1835,15 @@ fn main() -> Result<()> {
     {
         if std::env::var("HYPERDU_FS_AUTO").ok().as_deref() != Some("0") {
             if let Some(root0) = roots.first() {
-                println!(
+                let line = format!(
                     "fs-auto: fs='unknown' strategy='generic' reason='platform=non-linux' for '{}'",
                     root0.display()
                 );
+                if matches!(opt.compat_mode, hyperdu_core::CompatMode::HyperDU) {
+                    println!("{line}");
+                } else {
+                    eprintln!("{line}");
+                }
             }
         }
     }
@@ -1448,9 +1856,118 @@ fn main() -> Result<()> {
         }
         let root = roots.first().expect("at least one root");
         let t0 = std::time::Instant::now();
-        let map = hyperdu_core::scan_directory(root, &opt)?;
+        let owners = args
+            .by_owner
+            .then(|| Arc::new(hyperdu_core::owner::OwnerVisitor::new()));
+        let hist = args
+            .histogram
+            .then(|| Arc::new(hyperdu_core::histogram::HistogramVisitor::new()));
+        let ages = if args.age || args.age_days.is_some() {
+            let days = match args.age_days.as_deref() {
+                Some(list) => parse_age_days(list)?,
+                None => hyperdu_core::age::DEFAULT_AGE_DAYS.to_vec(),
+            };
+            let kind = args.time_kind.unwrap_or(TimeKindArg::Mtime);
+            Some(Arc::new(hyperdu_core::age::AgeVisitor::new(
+                kind.into(),
+                &days,
+            )))
+        } else {
+            None
+        };
+        // Classification runs inside the scan (no second walk)
+        let classifier = args.classify.as_deref().map(|mode| {
+            let cmode = match mode {
+                "deep" => hyperdu_core::classify::ClassifyMode::Deep,
+                _ => hyperdu_core::classify::ClassifyMode::Basic,
+            };
+            Arc::new(hyperdu_core::classify::ClassifyVisitor::with_threads(
+                cmode,
+                opt.threads,
+            ))
+        });
+        let sparse = args
+            .sparse
+            .then(|| Arc::new(hyperdu_core::sparse::SparseVisitor::new(args.top)));
+        let links = (args.hardlinks || args.hardlink_split)
+            .then(|| Arc::new(hyperdu_core::hardlink::HardlinkVisitor::new()));
+        let extents = args
+            .extents
+            .then(|| Arc::new(hyperdu_core::extents::ExtentVisitor::new(args.top)));
+        let mounts = args
+            .mounts
+            .then(|| Arc::new(hyperdu_core::mounts::MountVisitor::new()));
+        let mut visitors: Vec<Arc<dyn hyperdu_core::FileVisitor>> = Vec::new();
+        if let Some(m) = &mounts {
+            visitors.push(m.clone());
+        }
+        if let Some(e) = &extents {
+            visitors.push(e.clone());
+        }
+        if let Some(l) = &links {
+            visitors.push(l.clone());
+        }
+        if let Some(s) = &sparse {
+            visitors.push(s.clone());
+        }
+        if let Some(c) = &classifier {
+            visitors.push(c.clone());
+        }
+        if let Some(o) = &owners {
+            visitors.push(o.clone());
+        }
+        if let Some(h) = &hist {
+            visitors.push(h.clone());
+        }
+        if let Some(a) = &ages {
+            visitors.push(a.clone());
+        }
+        let visitor = (!visitors.is_empty()).then(|| {
+            Arc::new(hyperdu_core::VisitorSet(visitors)) as Arc<dyn hyperdu_core::FileVisitor>
+        });
+        if visitor.is_some() && opt.resume_from.is_some() {
+            anyhow::bail!("--resume cannot be combined with per-file reports");
+        }
+        let mut map = if print_progress {
+            // Run in the background and print the biggest directories found so far
+            let live_secs: u64 = std::env::var("HYPERDU_PROGRESS_LIVE_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5)
+                .max(1);
+            let handle = match &visitor {
+                Some(v) => hyperdu_core::start_scan_with_visitor(
+                    root,
+                    &opt,
+                    Arc::new(hyperdu_core::platform_scanner()),
+                    v.clone(),
+                )?,
+                None => hyperdu_core::start_scan(root, &opt)?,
+            };
+            let mut last_live = std::time::Instant::now();
+            while !handle.is_finished() {
+                thread::sleep(Duration::from_millis(100));
+                if last_live.elapsed() >= Duration::from_secs(live_secs) {
+                    last_live = std::time::Instant::now();
+                    print_live_snapshot(&handle.snapshot(), 5);
+                }
+            }
+            handle.join()?
+        } else if let Some(v) = &visitor {
+            hyperdu_core::scan_directory_with_visitor(
+                root,
+                &opt,
+                Arc::new(hyperdu_core::platform_scanner()),
+                v.clone(),
+            )?
+        } else {
+            hyperdu_core::scan_directory(root, &opt)?
+        };
         let dt = t0.elapsed();
         total_dt += dt;
+        if let (Some(l), true) = (&links, args.hardlink_split) {
+            l.apply_split(root, &mut map);
+        }
         let total_stat = *map.get(root).unwrap_or(&hyperdu_core::Stat::default());
         // Emit a final progress line if progress enabled and threshold未達で未出力の場合
         if print_progress {
@@ -1483,14 +2000,36 @@ fn main() -> Result<()> {
         println!("Top {} under {} (physical desc):", args.top, root.display());
         for (i, (p, s)) in v.iter().take(args.top).enumerate() {
             println!(
-                "{:>3}. {:<} | phys={} | log={} | files={}",
+                "{:>3}. {:<} | phys={}{} | excl={} | log={} | files={}",
                 i + 1,
                 p.display(),
+                partial_mark(s),
                 format_size(s.physical, BINARY),
+                format_size(s.exclusive, BINARY),
                 format_size(s.logical, BINARY),
                 s.files
             );
         }
+        let hist_map = hist.as_ref().map(|h| h.report(root));
+        if let Some(h) = hist_map.as_ref().and_then(|m| m.get(root)) {
+            println!();
+            println!(
+                "Size histogram under {} (files per log2 bucket):",
+                root.display()
+            );
+            print_histogram(h);
+        }
+        let age_map = ages.as_ref().map(|a| a.report(root));
+        if let Some(a) = &ages {
+            let h = age_map
+                .as_ref()
+                .and_then(|m| m.get(root))
+                .cloned()
+                .unwrap_or_default();
+            println!();
+            println!("Age ({:?}) under {}:", a.kind(), root.display());
+            print_age(&h, a.days());
+        }
         println!();
         println!("Summary:");
         println!("  Root: {}", root.display());
@@ -1529,12 +2068,79 @@ fn main() -> Result<()> {
             println!("  Uring-metrics: n/a");
         }
         println!(
-            "  Total: files={} | phys={} | log={} | dirs={}",
+            "  Total: files={} | phys={}{} | log={} | dirs={}",
             total_stat.files,
+            partial_mark(&total_stat),
             format_size(total_stat.physical, BINARY),
             format_size(total_stat.logical, BINARY),
             dirs_scanned
         );
+        if opt.ignore_report {
+            println!(
+                "  Ignored: phys={} (not included above)",
+                format_size(total_stat.ignored, BINARY)
+            );
+        }
+        if let Some(stats) = &opt.exclude_stats {
+            let usage = stats.report();
+            println!("  Excluded (not included above):");
+            for u in &usage {
+                println!(
+                    "    {} ({:?}) | files={} | phys={} | log={} | dirs={}",
+                    u.pattern,
+                    u.kind,
+                    u.files,
+                    format_size(u.physical, BINARY),
+                    format_size(u.logical, BINARY),
+                    u.dirs
+                );
+            }
+            let auto_xjson = args.verbose.then(|| PathBuf::from("exclude-report.json"));
+            if let Some(p) = args.exclude_report.as_ref().or(auto_xjson.as_ref()) {
+                let mut file = File::create(p)?;
+                let json = serde_json::to_string_pretty(&serde_json::json!({
+                    "patterns": usage,
+                    "total": {
+                        "files": usage.iter().map(|u| u.files).sum::<u64>(),
+                        "logical": usage.iter().map(|u| u.logical).sum::<u64>(),
+                        "physical": usage.iter().map(|u| u.physical).sum::<u64>(),
+                    }
+                }))?;
+                file.write_all(json.as_bytes())?;
+                println!("wrote exclude-report: {}", p.display());
+            }
+        }
+        if !total_stat.is_complete() {
+            println!(
+                "  Incomplete: {} directories not fully read; sizes marked ~ are lower bounds",
+                total_stat.incomplete
+            );
+        }
+        let estimate = opt.last_estimate.lock().ok().and_then(|e| e.clone());
+        if let Some(est) = &estimate {
+            println!(
+                "  Estimate: budget spent; {} directories unexplored, {} sampled",
+                est.unexplored_dirs, est.sampled_dirs
+            );
+            let size = |n: u64| format_size(n, BINARY);
+            println!("    phys:  {}", estimate_line(&est.physical, size));
+            println!("    log:   {}", estimate_line(&est.logical, size));
+            println!(
+                "    files: {}",
+                estimate_line(&est.files, |n| n.to_string())
+            );
+        }
+        let scan_errors = opt.errors.lock().map(|e| e.clone()).unwrap_or_default();
+        if !scan_errors.is_empty() {
+            // One record per directory that failed to open or to list completely
+            println!(
+                "  Errors: {} directories could not be read (fully)",
+                scan_errors.len()
+            );
+            for e in scan_errors.iter().take(args.top) {
+                println!("    {} ({})", e.path.display(), scan_error_detail(e));
+            }
+        }
 
         // Disk/Volume usage (best-effort)
         if let Some((vol_total, vol_free)) = fs_total_free(root) {
@@ -1558,31 +2164,347 @@ fn main() -> Result<()> {
         let auto_csv = args.verbose.then(|| PathBuf::from("hyperdu-report.csv"));
         if let Some(csv_path) = args.csv.as_ref().or(auto_csv.as_ref()) {
             let mut wtr = csv::Writer::from_path(csv_path)?;
-            wtr.write_record(["path", "logical", "physical", "files"])?;
+            let age_days = ages.as_ref().map(|a| a.days()).unwrap_or_default();
+            let mut header: Vec<String> = [
+                "path",
+                "logical",
+                "physical",
+                "files",
+                "dirs",
+                "symlinks",
+                "max_mtime",
+                "min_atime",
+                "exclusive",
+                "incomplete",
+            ]
+            .map(String::from)
+            .to_vec();
+            header.extend(age_days.iter().map(|d| format!("older_{d}d")));
+            wtr.write_record(&header)?;
             for (p, s) in &v {
-                wtr.write_record([
-                    p.to_string_lossy().as_ref(),
-                    &s.logical.to_string(),
-                    &s.physical.to_string(),
-                    &s.files.to_string(),
-                ])?;
+                let mut rec = vec![
+                    p.to_string_lossy().into_owned(),
+                    s.logical.to_string(),
+                    s.physical.to_string(),
+                    s.files.to_string(),
+                    s.dirs.to_string(),
+                    s.symlinks.to_string(),
+                    s.max_mtime.to_string(),
+                    s.min_atime.to_string(),
+                    s.exclusive.to_string(),
+                    (!s.is_complete()).to_string(),
+                ];
+                let h = age_map.as_ref().and_then(|m| m.get(p));
+                rec.extend(
+                    (0..age_days.len()).map(|i| h.map_or(0, |h| h.older_than(i).1).to_string()),
+                );
+                wtr.write_record(&rec)?;
             }
             wtr.flush()?;
             println!("wrote CSV: {}", csv_path.display());
         }
         if let Some(json_path) = args.json.as_ref().or(auto_json.as_ref()) {
             let mut file = File::create(json_path)?;
-            let json = serde_json::to_string_pretty(&v.iter().map(|(p, s)| serde_json::json!({"path": p, "logical": s.logical, "physical": s.physical, "files": s.files})).collect::<Vec<_>>())?;
+            let errors_by_path: std::collections::HashMap<&Path, &hyperdu_core::ScanErrorRecord> =
+                scan_errors.iter().map(|e| (e.path.as_path(), e)).collect();
+            let entries = v.iter().map(|(p, s)| {
+                let mut e = serde_json::json!({"path": p, "logical": s.logical, "physical": s.physical, "files": s.files, "dirs": s.dirs, "symlinks": s.symlinks, "max_mtime": s.max_mtime, "min_atime": s.min_atime, "exclusive": s.exclusive, "incomplete": !s.is_complete()});
+                // Directory that could not be read: {"kind", "errno", "syscall"}
+                if let Some(err) = errors_by_path.get(p.as_path()) {
+                    e["error"] = serde_json::json!({"kind": err.kind, "errno": err.errno, "syscall": err.syscall});
+                }
+                if opt.ignore_report {
+                    e["ignored"] = serde_json::json!(s.ignored);
+                }
+                if let (Some(est), true) = (&estimate, p == root) {
+                    e["estimate"] = serde_json::json!(est);
+                }
+                if let Some(h) = hist_map.as_ref().and_then(|m| m.get(p)) {
+                    e["size_histogram"] = h.nonzero().map(|(i, files, bytes)| {
+                        let (min, max) = hyperdu_core::histogram::SizeHistogram::bucket_range(i);
+                        serde_json::json!({"min": min, "max": max, "files": files, "bytes": bytes})
+                    }).collect::<Vec<_>>().into();
+                }
+                if let Some(a) = &ages {
+                    let h = age_map.as_ref().and_then(|m| m.get(p));
+                    e["age"] = a.days().iter().enumerate().map(|(i, d)| {
+                        let (files, bytes) = h.map_or((0, 0), |h| h.older_than(i));
+                        serde_json::json!({"older_than_days": d, "files": files, "bytes": bytes})
+                    }).collect::<Vec<_>>().into();
+                }
+                e
+            }).collect::<Vec<_>>();
+            // --json-errors: every unreadable path, not only the ones among the listed entries
+            let json = if args.json_errors {
+                serde_json::to_string_pretty(
+                    &serde_json::json!({"entries": entries, "errors": scan_errors}),
+                )?
+            } else {
+                serde_json::to_string_pretty(&entries)?
+            };
             file.write_all(json.as_bytes())?;
             println!("wrote JSON: {}", json_path.display());
         }
-        // Optional classification after scan
-        if let Some(mode) = &args.classify {
-            let cmode = match mode.as_str() {
-                "deep" => hyperdu_core::classify::ClassifyMode::Deep,
-                _ => hyperdu_core::classify::ClassifyMode::Basic,
-            };
-            let class_stats = hyperdu_core::classify::classify_directory(root, &opt, cmode);
+        // Optional per-owner breakdown (collected during the scan)
+        if let Some(ov) = &owners {
+            let report = ov.report(root);
+            let mut names = hyperdu_core::owner::OwnerNames::default();
+            println!("By owner (physical desc):");
+            for (uid, s) in report.total.users_by_size().iter().take(args.top) {
+                println!(
+                    "  user  {:<16} | phys={} | log={} | files={}",
+                    names.user(*uid),
+                    format_size(s.physical, BINARY),
+                    format_size(s.logical, BINARY),
+                    s.files
+                );
+            }
+            for (gid, s) in report.total.groups_by_size().iter().take(args.top) {
+                println!(
+                    "  group {:<16} | phys={} | log={} | files={}",
+                    names.group(*gid),
+                    format_size(s.physical, BINARY),
+                    format_size(s.logical, BINARY),
+                    s.files
+                );
+            }
+            let auto_ojson = args.verbose.then(|| PathBuf::from("owner-report.json"));
+            let auto_ocsv = args.verbose.then(|| PathBuf::from("owner-report.csv"));
+            let mut dirs: Vec<_> = report.per_dir.iter().collect();
+            dirs.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            if let Some(p) = args.owner_report.as_ref().or(auto_ojson.as_ref()) {
+                let mut file = File::create(p)?;
+                let json = serde_json::to_string_pretty(&serde_json::json!({
+                    "root": root,
+                    "total": owner_json(&report.total, &mut names),
+                    "dirs": dirs.iter().map(|(d, o)| {
+                        let mut v = owner_json(o, &mut names);
+                        v["path"] = serde_json::json!(d);
+                        v
+                    }).collect::<Vec<_>>()
+                }))?;
+                file.write_all(json.as_bytes())?;
+                println!("wrote owner-report: {}", p.display());
+            }
+            if let Some(p) = args.owner_report_csv.as_ref().or(auto_ocsv.as_ref()) {
+                let mut wtr = csv::Writer::from_path(p)?;
+                wtr.write_record(["path", "kind", "id", "name", "logical", "physical", "files"])?;
+                for (d, o) in &dirs {
+                    let path = d.to_string_lossy();
+                    for (uid, s) in o.users_by_size() {
+                        let name = names.user(uid).to_string();
+                        wtr.write_record([
+                            path.as_ref(),
+                            "user",
+                            &uid.to_string(),
+                            &name,
+                            &s.logical.to_string(),
+                            &s.physical.to_string(),
+                            &s.files.to_string(),
+                        ])?;
+                    }
+                    for (gid, s) in o.groups_by_size() {
+                        let name = names.group(gid).to_string();
+                        wtr.write_record([
+                            path.as_ref(),
+                            "group",
+                            &gid.to_string(),
+                            &name,
+                            &s.logical.to_string(),
+                            &s.physical.to_string(),
+                            &s.files.to_string(),
+                        ])?;
+                    }
+                }
+                wtr.flush()?;
+                println!("wrote owner-report-csv: {}", p.display());
+            }
+        }
+        // Optional hardlink groups (collected during the scan)
+        if let (Some(l), true) = (&links, args.hardlinks) {
+            let report = l.report();
+            println!(
+                "Hardlink groups: {} | shared={} (physical desc):",
+                report.groups.len(),
+                format_size(report.shared_bytes(), BINARY)
+            );
+            for g in report.groups.iter().take(args.top) {
+                println!(
+                    "  dev={} ino={} | links={}/{} | phys={} | log={}",
+                    g.dev,
+                    g.ino,
+                    g.paths.len(),
+                    g.nlink,
+                    format_size(g.physical, BINARY),
+                    format_size(g.logical, BINARY)
+                );
+                for p in &g.paths {
+                    println!("    {}", p.display());
+                }
+            }
+            let auto_hjson = args.verbose.then(|| PathBuf::from("hardlink-report.json"));
+            if let Some(p) = args.hardlink_report.as_ref().or(auto_hjson.as_ref()) {
+                let mut file = File::create(p)?;
+                let json = serde_json::to_string_pretty(&report.groups)?;
+                file.write_all(json.as_bytes())?;
+                println!("wrote hardlink-report: {}", p.display());
+            }
+        }
+        // Optional sparse/slack report (collected during the scan)
+        if let Some(sv) = &sparse {
+            let report = sv.report(root);
+            let total = report.per_dir.get(root).copied().unwrap_or_default();
+            println!("Sparse files (unallocated desc):");
+            for f in &report.top_sparse {
+                println!(
+                    "  {} | log={} | phys={} | unallocated={}",
+                    f.path.display(),
+                    format_size(f.logical, BINARY),
+                    format_size(f.physical, BINARY),
+                    format_size(f.unallocated, BINARY)
+                );
+            }
+            let mut dirs: Vec<_> = report
+                .per_dir
+                .iter()
+                .filter(|(p, s)| p.as_path() != root.as_path() && s.slack_bytes > 0)
+                .collect();
+            dirs.sort_unstable_by(|a, b| b.1.slack_bytes.cmp(&a.1.slack_bytes).then(a.0.cmp(b.0)));
+            println!(
+                "Slack (total): slack={} | wasteful_files={} | sparse_files={} | sparse_bytes={}",
+                format_size(total.slack_bytes, BINARY),
+                total.wasteful_files,
+                total.sparse_files,
+                format_size(total.sparse_bytes, BINARY)
+            );
+            for (p, s) in dirs.iter().take(args.top) {
+                println!(
+                    "  {} | slack={} | wasteful_files={} | sparse_files={}",
+                    p.display(),
+                    format_size(s.slack_bytes, BINARY),
+                    s.wasteful_files,
+                    s.sparse_files
+                );
+            }
+            let auto_sjson = args.verbose.then(|| PathBuf::from("sparse-report.json"));
+            if let Some(p) = args.sparse_report.as_ref().or(auto_sjson.as_ref()) {
+                let mut file = File::create(p)?;
+                let json = serde_json::to_string_pretty(&serde_json::json!({
+                    "total": total,
+                    "top_sparse": report.top_sparse,
+                    "per_dir": dirs.iter().map(|(p, s)| {
+                        let mut e = serde_json::to_value(s).unwrap_or_default();
+                        e["path"] = serde_json::json!(p);
+                        e
+                    }).collect::<Vec<_>>()
+                }))?;
+                file.write_all(json.as_bytes())?;
+                println!("wrote sparse-report: {}", p.display());
+            }
+        }
+        // Optional extent sharing report (collected during the scan)
+        if let Some(ev) = &extents {
+            let report = ev.report(root);
+            let total = report.per_dir.get(root).copied().unwrap_or_default();
+            println!("Shared extents (shared desc):");
+            for f in &report.top_shared {
+                println!(
+                    "  {} | shared={} | exclusive={}",
+                    f.path.display(),
+                    format_size(f.shared, BINARY),
+                    format_size(f.exclusive, BINARY)
+                );
+            }
+            let mut dirs: Vec<_> = report
+                .per_dir
+                .iter()
+                .filter(|(p, s)| p.as_path() != root.as_path() && s.shared > 0)
+                .collect();
+            dirs.sort_unstable_by(|a, b| b.1.shared.cmp(&a.1.shared).then(a.0.cmp(b.0)));
+            println!(
+                "Extents (total): exclusive={} | shared={} | unique={}",
+                format_size(total.exclusive, BINARY),
+                format_size(total.shared, BINARY),
+                format_size(total.shared_unique, BINARY)
+            );
+            for (p, s) in dirs.iter().take(args.top) {
+                println!(
+                    "  {} | exclusive={} | shared={} | unique={}",
+                    p.display(),
+                    format_size(s.exclusive, BINARY),
+                    format_size(s.shared, BINARY),
+                    format_size(s.shared_unique, BINARY)
+                );
+            }
+            let auto_ejson = args.verbose.then(|| PathBuf::from("extent-report.json"));
+            if let Some(p) = args.extent_report.as_ref().or(auto_ejson.as_ref()) {
+                let mut file = File::create(p)?;
+                let json = serde_json::to_string_pretty(&serde_json::json!({
+                    "total": total,
+                    "top_shared": report.top_shared,
+                    "per_dir": dirs.iter().map(|(p, s)| {
+                        let mut e = serde_json::to_value(s).unwrap_or_default();
+                        e["path"] = serde_json::json!(p);
+                        e
+                    }).collect::<Vec<_>>()
+                }))?;
+                file.write_all(json.as_bytes())?;
+                println!("wrote extent-report: {}", p.display());
+            }
+        }
+        // Optional per-filesystem breakdown (collected during the scan)
+        if let Some(mv) = &mounts {
+            let report = mv.report(root, &hyperdu_core::mounts::MountTable::read());
+            println!("Filesystems (physical desc):");
+            for u in &report.mounts {
+                println!(
+                    "  {} | {} {} dev={} | phys={} | log={} | files={} | dirs={}",
+                    u.mount.mount_point.display(),
+                    u.mount.fs_type,
+                    u.mount.source,
+                    u.mount.dev,
+                    format_size(u.stat.physical, BINARY),
+                    format_size(u.stat.logical, BINARY),
+                    u.stat.files,
+                    u.stat.dirs
+                );
+            }
+            if !report.skipped.is_empty() {
+                println!("Skipped mounts: {}", report.skipped.len());
+                for m in &report.skipped {
+                    println!(
+                        "  {} | {} {} dev={}",
+                        m.mount_point.display(),
+                        m.fs_type,
+                        m.source,
+                        m.dev
+                    );
+                }
+            }
+            let auto_mjson = args.verbose.then(|| PathBuf::from("mount-report.json"));
+            if let Some(p) = args.mount_report.as_ref().or(auto_mjson.as_ref()) {
+                let mut dirs: Vec<_> = report.per_dir.iter().collect();
+                dirs.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                let mut file = File::create(p)?;
+                let json = serde_json::to_string_pretty(&serde_json::json!({
+                    "mounts": report.mounts,
+                    "skipped": report.skipped,
+                    "per_dir": dirs.iter().map(|(p, (i, s))| serde_json::json!({
+                        "path": p,
+                        "dev": report.mounts[*i].mount.dev,
+                        "mount_point": report.mounts[*i].mount.mount_point,
+                        "files": s.files,
+                        "logical": s.logical,
+                        "physical": s.physical,
+                    })).collect::<Vec<_>>()
+                }))?;
+                file.write_all(json.as_bytes())?;
+                println!("wrote mount-report: {}", p.display());
+            }
+        }
+        // Optional classification (collected during the scan)
+        if let Some(c) = &classifier {
+            let class_stats = c.finish();
             println!(
                 "classify: categories={} extensions={} top_entries={}",
                 class_stats.by_category.len(),
@@ -1686,20 +2608,47 @@ fn main() -> Result<()> {
             "inner" => hyperdu_core::HeuristicsMode::InnerOnly,
             _ => hyperdu_core::HeuristicsMode::Auto,
         };
+        if args.summarize && args.all {
+            anyhow::bail!("cannot both summarize and show all entries");
+        }
+        if args.all && opt.resume_from.is_some() {
+            anyhow::bail!("--resume cannot be combined with per-file reports");
+        }
+        // Like GNU du, --max-depth only limits the rows printed; totals cover the whole tree
+        opt.max_depth = 0;
+        opt.count_special_files = args.inodes;
+        let du = DuFormat {
+            block_size: bs,
+            apparent: args.apparent_size,
+            inodes: args.inodes,
+            max_depth: if args.summarize {
+                Some(0)
+            } else {
+                (args.max_depth > 0).then_some(args.max_depth as usize)
+            },
+            time: print_time.then_some((time_kind, time_style)),
+        };
 
         #[cfg(feature = "rayon-par")]
         {
-            if cfg.auto_parallel {
+            // Per-file rows (-a), checkpoints and budgets need the sequential path;
+            // several roots or -c go through scan_roots for the shared dedupe
+            let sequential = args.all
+                || roots.len() > 1
+                || args.total
+                || opt.checkpoint_path.is_some()
+                || opt.budget_ms > 0
+                || opt.budget_files > 0;
+            if cfg.auto_parallel && !sequential {
                 let t0 = std::time::Instant::now();
                 let merged = hyperdu_core::auto_parallel_scan(roots.clone(), &opt)?;
                 total_dt += t0.elapsed();
                 for root in roots {
-                    let mut entries: Vec<(PathBuf, hyperdu_core::Stat)> = merged
+                    let entries: Vec<(PathBuf, hyperdu_core::Stat)> = merged
                         .iter()
                         .filter(|(p, _)| p.starts_with(&root))
                         .map(|(p, s)| (p.clone(), *s))
                         .collect();
-                    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
                     if print_progress {
                         let total_files: u64 = entries.iter().map(|(_, s)| s.files).sum();
                         let now = std::time::Instant::now();
@@ -1716,43 +2665,24 @@ fn main() -> Result<()> {
                             *last.lock().unwrap() = (total_files, now);
                         }
                     }
-                    for (p, s) in entries {
-                        if p.as_os_str().is_empty() {
-                            continue;
-                        }
-                        let bytes = if args.apparent_size {
-                            s.logical
-                        } else {
-                            s.physical
-                        };
-                        let blocks = div_ceil(bytes, bs as u64);
-                        if print_time {
-                            println!(
-                                "{}\t{}\t{}",
-                                blocks,
-                                format_time(&p, time_kind, time_style),
-                                p.display()
-                            );
-                        } else {
-                            println!("{}\t{}", blocks, p.display());
-                        }
-                    }
+                    let rows = entries.into_iter().map(|(p, s)| (p, s, true)).collect();
+                    print_du_rows(&root, rows, &du);
                 }
                 return Ok(());
             }
             if cfg.auto_parallel
+                && !sequential
                 && matches!(opt.heuristics_mode, hyperdu_core::HeuristicsMode::OuterOnly)
             {
                 let t0 = std::time::Instant::now();
                 let merged = hyperdu_core::parallel_scan(roots.clone(), &opt)?;
                 total_dt += t0.elapsed();
                 for root in roots {
-                    let mut entries: Vec<(PathBuf, hyperdu_core::Stat)> = merged
+                    let entries: Vec<(PathBuf, hyperdu_core::Stat)> = merged
                         .iter()
                         .filter(|(p, _)| p.starts_with(&root))
                         .map(|(p, s)| (p.clone(), *s))
                         .collect();
-                    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
                     if print_progress {
                         let total_files: u64 = entries.iter().map(|(_, s)| s.files).sum();
                         let now = std::time::Instant::now();
@@ -1769,27 +2699,8 @@ fn main() -> Result<()> {
                             *last.lock().unwrap() = (total_files, now);
                         }
                     }
-                    for (p, s) in entries {
-                        if p.as_os_str().is_empty() {
-                            continue;
-                        }
-                        let bytes = if args.apparent_size {
-                            s.logical
-                        } else {
-                            s.physical
-                        };
-                        let blocks = div_ceil(bytes, bs as u64);
-                        if print_time {
-                            println!(
-                                "{}\t{}\t{}",
-                                blocks,
-                                format_time(&p, time_kind, time_style),
-                                p.display()
-                            );
-                        } else {
-                            println!("{}\t{}", blocks, p.display());
-                        }
-                    }
+                    let rows = entries.into_iter().map(|(p, s)| (p, s, true)).collect();
+                    print_du_rows(&root, rows, &du);
                 }
                 return Ok(());
             }
@@ -1803,12 +2714,65 @@ fn main() -> Result<()> {
             }
         }
 
+        let multi = (roots.len() > 1 || args.total) && !args.all;
+        let roots = if !multi {
+            roots
+        } else {
+            let t0 = std::time::Instant::now();
+            let (existing, missing): (Vec<PathBuf>, Vec<PathBuf>) =
+                roots.into_iter().partition(|r| r.exists());
+            for root in &missing {
+                eprintln!("{}: No such file or directory", root.display());
+                exit_code = 1;
+            }
+            if !existing.is_empty() {
+                let scan = hyperdu_core::scan_roots(&existing, &opt)?;
+                for tree in scan.trees() {
+                    let root = tree.root_path();
+                    let rows = tree
+                        .to_stat_map()
+                        .into_iter()
+                        .map(|(p, s)| (p, s, true))
+                        .collect();
+                    print_du_rows(root, rows, &du);
+                    if let Some(est) = tree.estimate() {
+                        print_du_estimate(root, est, &du);
+                    }
+                }
+                if args.total {
+                    // total().dirs already counts the roots themselves
+                    print_du_line(&existing[0], &scan.total(), false, &"total", &du);
+                }
+            }
+            total_dt += t0.elapsed();
+            Vec::new()
+        };
+        // -a -c: grand total of the separately scanned roots
+        let mut grand = hyperdu_core::Stat::default();
+        let total_at = roots.first().cloned();
         for root in roots {
             let t0 = std::time::Instant::now();
-            match hyperdu_core::scan_directory(&root, &opt) {
+            // -a: collect the counted non-directory entries during the scan
+            let files = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let res = if args.all {
+                let sink = files.clone();
+                let visitor = move |e: &hyperdu_core::FileEntry<'_>| {
+                    if e.kind != hyperdu_core::EntryKind::Dir {
+                        sink.lock().unwrap().push(du_entry_row(e));
+                    }
+                };
+                hyperdu_core::scan_directory_with_visitor(
+                    &root,
+                    &opt,
+                    Arc::new(hyperdu_core::platform_scanner()),
+                    Arc::new(visitor),
+                )
+            } else {
+                hyperdu_core::scan_directory(&root, &opt)
+            };
+            match res {
                 Ok(map) => {
-                    let mut entries: Vec<(PathBuf, hyperdu_core::Stat)> = map.into_iter().collect();
-                    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                    let entries: Vec<(PathBuf, hyperdu_core::Stat)> = map.into_iter().collect();
                     if print_progress {
                         let total_files = entries
                             .iter()
@@ -1829,26 +2793,15 @@ fn main() -> Result<()> {
                             *last.lock().unwrap() = (total_files, now);
                         }
                     }
-                    for (p, s) in entries {
-                        if p.as_os_str().is_empty() {
-                            continue;
-                        }
-                        let bytes = if args.apparent_size {
-                            s.logical
-                        } else {
-                            s.physical
-                        };
-                        let blocks = div_ceil(bytes, bs as u64);
-                        if print_time {
-                            println!(
-                                "{}\t{}\t{}",
-                                blocks,
-                                format_time(&p, time_kind, time_style),
-                                p.display()
-                            );
-                        } else {
-                            println!("{}\t{}", blocks, p.display());
-                        }
+                    if let Some((_, s)) = entries.iter().find(|(p, _)| p == &root) {
+                        grand.merge(s);
+                        grand.dirs += 1;
+                    }
+                    let mut rows: Vec<_> = entries.into_iter().map(|(p, s)| (p, s, true)).collect();
+                    rows.append(&mut files.lock().unwrap());
+                    print_du_rows(&root, rows, &du);
+                    if let Some(est) = opt.last_estimate.lock().ok().and_then(|e| e.clone()) {
+                        print_du_estimate(&root, &est, &du);
                     }
                 }
                 Err(e) => {
@@ -1858,6 +2811,9 @@ fn main() -> Result<()> {
             }
             total_dt += t0.elapsed();
         }
+        if let (true, Some(at)) = (args.total, total_at) {
+            print_du_line(&at, &grand, false, &"total", &du);
+        }
         let errn = opt.error_count.load(std::sync::atomic::Ordering::Relaxed);
         if errn > 0 || exit_code != 0 {
             std::process::exit(1);
@@ -1866,6 +2822,220 @@ fn main() -> Result<()> {
     }
 }
 
+/// `~` in front of values that only cover part of the subtree.
+fn partial_mark(s: &hyperdu_core::Stat) -> &'static str {
+    if s.is_complete() {
+        ""
+    } else {
+        "~"
+    }
+}
+
+/// Estimate of a budget-bounded du scan, on stderr to keep stdout du-compatible.
+fn print_du_estimate(root: &Path, est: &hyperdu_core::estimate::SizeEstimate, f: &DuFormat) {
+    let t = if f.apparent {
+        est.logical
+    } else {
+        est.physical
+    };
+    eprintln!(
+        "estimate: {}: {} | files {} ({} directories unexplored)",
+        root.display(),
+        estimate_line(&t, |n| format_size(n, BINARY)),
+        estimate_line(&est.files, |n| n.to_string()),
+        est.unexplored_dirs
+    );
+}
+
+/// `counted X -> ~Y (95% CI lo - hi)` for a budget-bounded scan.
+fn estimate_line(
+    t: &hyperdu_core::estimate::EstimatedTotal,
+    fmt: impl Fn(u64) -> String,
+) -> String {
+    format!(
+        "counted {} -> ~{} (95% CI {} - {})",
+        fmt(t.counted),
+        fmt(t.total),
+        fmt(t.low),
+        fmt(t.high)
+    )
+}
+
+/// `kind, syscall errno=N` for a scan error line.
+fn scan_error_detail(e: &hyperdu_core::ScanErrorRecord) -> String {
+    let mut out = e.kind.to_string();
+    if let Some(call) = e.syscall {
+        out.push_str(&format!(", {call}"));
+    }
+    if let Some(errno) = e.errno {
+        out.push_str(&format!(" errno={errno}"));
+    }
+    out
+}
+
+/// du-style output settings shared by the compat code paths.
+struct DuFormat<'a> {
+    block_size: u64,
+    apparent: bool,
+    inodes: bool,
+    /// Deepest row printed, relative to the root (`None` = all).
+    max_depth: Option<usize>,
+    time: Option<(TimeKindArg, &'a str)>,
+}
+
+/// Row for a file, symlink or special file listed with `-a`.
+fn du_entry_row(e: &hyperdu_core::FileEntry<'_>) -> (PathBuf, hyperdu_core::Stat, bool) {
+    let mut s = hyperdu_core::Stat {
+        logical: e.logical,
+        physical: e.physical,
+        ..Default::default()
+    };
+    if e.kind == hyperdu_core::EntryKind::Symlink {
+        s.symlinks = 1;
+    } else {
+        s.files = 1;
+    }
+    s.note_times(e.mtime, e.atime);
+    (e.path(), s, false)
+}
+
+/// Print `(path, stat, is_dir)` rows below `root` as `value<TAB>path`, sorted by path.
+/// The value is blocks of `block_size`, or inodes with `--inodes` (a directory
+/// counts itself plus everything below it). Values of directories that were
+/// not fully read get a `~` prefix.
+fn print_du_rows(root: &Path, mut rows: Vec<(PathBuf, hyperdu_core::Stat, bool)>, f: &DuFormat) {
+    rows.retain(|(p, _, _)| match p.strip_prefix(root) {
+        Ok(rel) => f.max_depth.map_or(true, |d| rel.components().count() <= d),
+        Err(_) => false,
+    });
+    rows.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    for (p, s, is_dir) in rows {
+        if p.as_os_str().is_empty() {
+            continue;
+        }
+        print_du_line(&p, &s, is_dir, &p.display(), f);
+    }
+}
+
+/// One `VALUE[\tTIME]\tLABEL` line; `p` is where `--time` looks up the timestamp.
+fn print_du_line(
+    p: &Path,
+    s: &hyperdu_core::Stat,
+    is_dir: bool,
+    label: &dyn std::fmt::Display,
+    f: &DuFormat,
+) {
+    let value = if f.inodes {
+        s.inodes() + u64::from(is_dir)
+    } else {
+        let bytes = if f.apparent { s.logical } else { s.physical };
+        div_ceil(bytes, f.block_size)
+    };
+    let mark = partial_mark(s);
+    match f.time {
+        Some((kind, style)) => println!(
+            "{mark}{}\t{}\t{}",
+            value,
+            format_time(p, s, kind, style),
+            label
+        ),
+        None => println!("{mark}{}\t{}", value, label),
+    }
+}
+
+/// Text bar chart of the non-empty histogram buckets.
+fn print_histogram(h: &hyperdu_core::histogram::SizeHistogram) {
+    let max = h.files.iter().copied().max().unwrap_or(0).max(1);
+    for (i, files, bytes) in h.nonzero() {
+        let (lo, hi) = hyperdu_core::histogram::SizeHistogram::bucket_range(i);
+        let range = match hi {
+            Some(hi) => format!("[{}, {})", format_size(lo, BINARY), format_size(hi, BINARY)),
+            None => format!(">= {}", format_size(lo, BINARY)),
+        };
+        let bar = "#".repeat(((files * 40).div_ceil(max)) as usize);
+        println!(
+            "  {:<22} files={:<10} phys={:<11} {}",
+            range,
+            files,
+            format_size(bytes, BINARY),
+            bar
+        );
+    }
+}
+
+/// Usage at least N days old per threshold, as a share of the aged total.
+fn print_age(h: &hyperdu_core::age::AgeHistogram, days: &[u32]) {
+    let total = h.total_bytes().max(1);
+    for (i, d) in days.iter().enumerate() {
+        let (files, bytes) = h.older_than(i);
+        println!(
+            "  >= {:<5} files={:<10} phys={:<11} {:>5.1}%",
+            format!("{d}d"),
+            files,
+            format_size(bytes, BINARY),
+            (bytes as f64) * 100.0 / (total as f64)
+        );
+    }
+}
+
+/// Parse the comma-separated `--age-days` list.
+fn parse_age_days(list: &str) -> Result<Vec<u32>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| {
+            d.parse::<u32>()
+                .map_err(|_| anyhow::anyhow!("invalid --age-days value: {d}"))
+        })
+        .collect()
+}
+
+/// JSON object with the users and groups of one owner breakdown, largest first.
+fn owner_json(
+    o: &hyperdu_core::owner::OwnerStats,
+    names: &mut hyperdu_core::owner::OwnerNames,
+) -> serde_json::Value {
+    let users: Vec<_> = o
+        .users_by_size()
+        .into_iter()
+        .map(|(id, s)| {
+            serde_json::json!({"id": id, "name": names.user(id), "logical": s.logical, "physical": s.physical, "files": s.files})
+        })
+        .collect();
+    let groups: Vec<_> = o
+        .groups_by_size()
+        .into_iter()
+        .map(|(id, s)| {
+            serde_json::json!({"id": id, "name": names.group(id), "logical": s.logical, "physical": s.physical, "files": s.files})
+        })
+        .collect();
+    serde_json::json!({"users": users, "groups": groups})
+}
+
+/// Print the largest directories of a running scan (below the root) and the pending count.
+fn print_live_snapshot(snap: &hyperdu_core::ScanSnapshot, top: usize) {
+    let tree = &snap.tree;
+    let total = tree.stat(hyperdu_core::ScanTree::ROOT);
+    println!(
+        "live: {} files | phys={} | pending dirs={}",
+        total.files,
+        format_size(total.physical, BINARY),
+        snap.pending.len()
+    );
+    let mut ids: Vec<hyperdu_core::NodeId> = tree.ids().skip(1).collect();
+    ids.sort_unstable_by_key(|&id| std::cmp::Reverse(tree.stat(id).physical));
+    for (i, &id) in ids.iter().take(top).enumerate() {
+        let s = tree.stat(id);
+        println!(
+            "  {:>2}. {} | phys={} | files={}",
+            i + 1,
+            tree.path(id).display(),
+            format_size(s.physical, BINARY),
+            s.files
+        );
+    }
+}
+
 #[allow(dead_code)]
 fn parse_block_size(s: &str) -> Option<u64> {
     let sl = s.trim().to_ascii_lowercase();
@@ -1910,15 +3080,22 @@ fn parse_block_size_with_si(s: &str, si: bool) -> Option<u64> {
     num.parse::<u64>().ok().map(|n| n.saturating_mul(mul))
 }
 
+/// Time column for du `--time`. Like GNU du, mtime is the newest of the
+/// directory itself and anything below it (taken from the scan's `max_mtime`).
 #[cfg(feature = "time-format")]
-fn format_time(p: &std::path::Path, when: TimeKindArg, style: &str) -> String {
+fn format_time(
+    p: &std::path::Path,
+    stat: &hyperdu_core::Stat,
+    when: TimeKindArg,
+    style: &str,
+) -> String {
     // Only called when user explicitly requested --time; keep it minimal
     #[cfg(unix)]
     {
         use std::os::unix::fs::MetadataExt;
         if let Ok(md) = std::fs::symlink_metadata(p) {
             let (secs, _nsec) = match when {
-                TimeKindArg::Mtime => (md.mtime(), md.mtime_nsec()),
+                TimeKindArg::Mtime => (md.mtime().max(stat.max_mtime), md.mtime_nsec()),
                 TimeKindArg::Atime => (md.atime(), md.atime_nsec()),
                 TimeKindArg::Ctime => (md.ctime(), md.ctime_nsec()),
             };
@@ -1947,7 +3124,10 @@ fn format_time(p: &std::path::Path, when: TimeKindArg, style: &str) -> String {
                 TimeKindArg::Ctime => md.creation_time(),
             };
             // FILETIME epoch (1601) to Unix epoch (1970)
-            let secs = ((t100 / 10_000_000) as i64) - 11644473600i64;
+            let mut secs = ((t100 / 10_000_000) as i64) - 11644473600i64;
+            if matches!(when, TimeKindArg::Mtime) {
+                secs = secs.max(stat.max_mtime);
+            }
             let dt = chrono::DateTime::<chrono::Utc>::from_timestamp(secs, 0)
                 .map(|d| d.naive_utc())
                 .unwrap_or_else(|| {
@@ -1967,7 +3147,12 @@ fn format_time(p: &std::path::Path, when: TimeKindArg, style: &str) -> String {
 }
 
 #[cfg(not(feature = "time-format"))]
-fn format_time(_p: &std::path::Path, _when: TimeKindArg, _style: &str) -> String {
+fn format_time(
+    _p: &std::path::Path,
+    _stat: &hyperdu_core::Stat,
+    _when: TimeKindArg,
+    _style: &str,
+) -> String {
     String::from("-")
 }
 
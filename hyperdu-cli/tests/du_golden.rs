@@ -45,3 +45,99 @@ fn du_tab_sorted_blocks() {
         }
     }
 }
+
+#[test]
+fn du_inodes_counts() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("r");
+    std::fs::create_dir_all(root.join("d/e")).unwrap();
+    std::fs::write(root.join("a"), b"abc").unwrap();
+    std::fs::write(root.join("d/e/x"), b"x").unwrap();
+    #[cfg(unix)]
+    {
+        std::fs::hard_link(root.join("a"), root.join("d/b")).unwrap();
+        std::os::unix::fs::symlink("a", root.join("l")).unwrap();
+    }
+
+    let exe = bin_path();
+    if std::fs::metadata(&exe).is_err() {
+        eprintln!("skip: test binary not found at {exe}");
+        return;
+    }
+    let run = |extra: &[&str]| {
+        let out = Command::new(&exe)
+            .arg(&root)
+            .args(["--compat", "gnu", "--inodes"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "cli failed: status={:?}", out.status);
+        String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|l| {
+                let (n, p) = l.split_once('\t').unwrap();
+                let rel = std::path::Path::new(p).strip_prefix(&root).unwrap();
+                (
+                    rel.to_string_lossy().into_owned(),
+                    n.parse::<u64>().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    // r, a, l, d, d/b (ハードリンクは1回), d/e, d/e/x
+    let total = if cfg!(unix) { 6 } else { 5 };
+    assert_eq!(run(&["-s"]), vec![(String::new(), total)]);
+    // --max-depth は表示行のみを制限し、合計は全体
+    assert_eq!(
+        run(&["--max-depth", "1"]),
+        vec![(String::new(), total), ("d".to_string(), total - 3)]
+    );
+    let all = run(&["-a"]);
+    assert!(all.contains(&("d/e/x".to_string(), 1)));
+    assert!(all.contains(&("d/e".to_string(), 2)));
+}
+
+#[test]
+fn json_report_lists_entries_and_errors() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("r");
+    std::fs::create_dir_all(root.join("d")).unwrap();
+    std::fs::write(root.join("d/a"), vec![0u8; 4096]).unwrap();
+    let json = tmp.path().join("report.json");
+
+    let exe = bin_path();
+    if std::fs::metadata(&exe).is_err() {
+        eprintln!("skip: test binary not found at {exe}");
+        return;
+    }
+    let out = Command::new(&exe)
+        .arg(&root)
+        .arg("--json")
+        .arg(&json)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "cli failed: status={:?}", out.status);
+    let v: serde_json::Value = serde_json::from_slice(&std::fs::read(&json).unwrap()).unwrap();
+    assert!(v.as_array().is_some_and(|e| !e.is_empty()));
+
+    let out = Command::new(&exe)
+        .arg(&root)
+        .arg("--json")
+        .arg(&json)
+        .arg("--json-errors")
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "cli failed: status={:?}", out.status);
+    let v: serde_json::Value = serde_json::from_slice(&std::fs::read(&json).unwrap()).unwrap();
+    assert!(v["entries"].as_array().is_some_and(|e| !e.is_empty()));
+    assert_eq!(v["errors"], serde_json::json!([]));
+
+    // The report is still accepted as scheduling hints
+    let out = Command::new(&exe)
+        .arg(&root)
+        .arg("--prev-report")
+        .arg(&json)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "cli failed: status={:?}", out.status);
+}
@@ -0,0 +1,159 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ahash::AHashMap as HashMap;
+use dashmap::DashMap;
+
+use crate::{EntryKind, FileEntry, FileVisitor};
+
+/// Default age thresholds in days.
+pub const DEFAULT_AGE_DAYS: [u32; 3] = [30, 90, 365];
+
+/// Which timestamp ages are measured from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeKind {
+    #[default]
+    Mtime,
+    Atime,
+    Ctime,
+}
+
+impl TimeKind {
+    #[inline]
+    pub(crate) fn of(self, e: &FileEntry<'_>) -> i64 {
+        match self {
+            TimeKind::Mtime => e.mtime,
+            TimeKind::Atime => e.atime,
+            TimeKind::Ctime => e.ctime,
+        }
+    }
+}
+
+/// Files and physical bytes per age bucket. With thresholds `[d0, d1, ..]`,
+/// bucket 0 is younger than `d0` days, bucket `i` is `[d(i-1), d(i))` and the
+/// last bucket is at least the largest threshold.
+#[derive(Clone, Debug, Default)]
+pub struct AgeHistogram {
+    pub files: Vec<u64>,
+    pub bytes: Vec<u64>,
+}
+
+impl AgeHistogram {
+    fn with_buckets(n: usize) -> Self {
+        Self {
+            files: vec![0; n],
+            bytes: vec![0; n],
+        }
+    }
+
+    pub fn merge(&mut self, other: &AgeHistogram) {
+        if self.files.len() < other.files.len() {
+            self.files.resize(other.files.len(), 0);
+            self.bytes.resize(other.bytes.len(), 0);
+        }
+        for (i, (f, b)) in other.files.iter().zip(&other.bytes).enumerate() {
+            self.files[i] += f;
+            self.bytes[i] += b;
+        }
+    }
+
+    /// Files and bytes at least `days[i]` old, i.e. the cumulative tail from
+    /// bucket `i + 1` (`i` indexes the thresholds).
+    pub fn older_than(&self, i: usize) -> (u64, u64) {
+        let files = self.files.iter().skip(i + 1).sum();
+        let bytes = self.bytes.iter().skip(i + 1).sum();
+        (files, bytes)
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes.iter().sum()
+    }
+}
+
+/// Visitor that buckets file usage by age per directory.
+///
+/// Files without the chosen timestamp (approximate sizes) are skipped.
+/// [`AgeVisitor::report`] rolls the buckets up to the scan root.
+pub struct AgeVisitor {
+    kind: TimeKind,
+    days: Vec<u32>,
+    // Bucket boundaries as absolute timestamps, newest first
+    cutoffs: Vec<i64>,
+    dirs: DashMap<PathBuf, AgeHistogram>,
+}
+
+impl AgeVisitor {
+    /// Ages are measured from now; `days` is sorted and deduplicated.
+    pub fn new(kind: TimeKind, days: &[u32]) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        Self::with_now(kind, days, now)
+    }
+
+    pub fn with_now(kind: TimeKind, days: &[u32], now: i64) -> Self {
+        let mut days = days.to_vec();
+        days.sort_unstable();
+        days.dedup();
+        let cutoffs = days.iter().map(|d| now - i64::from(*d) * 86_400).collect();
+        Self {
+            kind,
+            days,
+            cutoffs,
+            dirs: DashMap::new(),
+        }
+    }
+
+    pub fn kind(&self) -> TimeKind {
+        self.kind
+    }
+
+    /// Thresholds in days, ascending.
+    pub fn days(&self) -> &[u32] {
+        &self.days
+    }
+
+    #[inline]
+    fn bucket_of(&self, t: i64) -> usize {
+        self.cutoffs.iter().take_while(|&&c| t <= c).count()
+    }
+
+    pub fn report(&self, root: &Path) -> HashMap<PathBuf, AgeHistogram> {
+        let mut per_dir: HashMap<PathBuf, AgeHistogram> = self
+            .dirs
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect();
+        crate::rollup::rollup_to_root(&mut per_dir, root, AgeHistogram::merge);
+        per_dir
+    }
+}
+
+impl FileVisitor for AgeVisitor {
+    fn visit(&self, entry: &FileEntry<'_>) {
+        if entry.kind != EntryKind::File {
+            return;
+        }
+        let t = self.kind.of(entry);
+        if t == 0 {
+            return;
+        }
+        let b = self.bucket_of(t);
+        let add = |h: &mut AgeHistogram| {
+            h.files[b] += 1;
+            h.bytes[b] += entry.physical;
+        };
+        if let Some(mut h) = self.dirs.get_mut(entry.parent) {
+            add(&mut h);
+            return;
+        }
+        let n = self.days.len() + 1;
+        add(&mut self
+            .dirs
+            .entry(entry.parent.to_path_buf())
+            .or_insert_with(|| AgeHistogram::with_buckets(n)));
+    }
+}
@@ -0,0 +1,516 @@
+//! On-disk checkpoints of a running scan.
+//!
+//! With `Options::checkpoint_path` set, a scan periodically pauses its
+//! workers and writes the pending job queues (directory, depth, resume offset)
+//! together with the per-directory stats counted so far. Setting
+//! `Options::resume_from` to a loaded [`Checkpoint`] continues from there
+//! instead of starting at the root. Resume with the same options the
+//! checkpoint was written with; filters are not recorded.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    hardlink::{LinkTable, SharedInode},
+    scan_handle::LiveState,
+    Job, Options, Stat, StatMap,
+};
+
+const MAGIC: &[u8; 8] = b"HDUCKPT\x02";
+
+/// Scan state saved by a checkpointing scan.
+#[derive(Clone, Debug, Default)]
+pub struct Checkpoint {
+    root: PathBuf,
+    pub(crate) jobs: Vec<Job>,
+    /// Per-directory stats (not rolled up).
+    pub(crate) stats: StatMap,
+    /// Keys of `Options::inode_cache`.
+    pub(crate) inodes: Vec<(u64, u64)>,
+    /// Keys of `Options::visited_dirs`.
+    pub(crate) visited: Vec<(u64, u64)>,
+    pub(crate) links: Vec<((u64, u64), SharedInode)>,
+}
+
+impl Checkpoint {
+    /// Read a checkpoint written by an earlier scan.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| format!("cannot read checkpoint {}", path.display()))?;
+        Self::decode(&data).with_context(|| format!("invalid checkpoint {}", path.display()))
+    }
+
+    /// Root directory of the checkpointed scan.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of directories still (fully or partly) unread.
+    pub fn pending_dirs(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Write atomically: to `<path>.tmp` first, then rename over `path`.
+    pub(crate) fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let mut f = std::fs::File::create(&tmp)
+            .with_context(|| format!("cannot create {}", tmp.display()))?;
+        f.write_all(&self.encode())?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("cannot replace checkpoint {}", path.display()))?;
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut w = Vec::with_capacity(64 + self.stats.len() * 96);
+        w.extend_from_slice(MAGIC);
+        put_path(&mut w, &self.root);
+        put_u64(&mut w, self.jobs.len() as u64);
+        for job in &self.jobs {
+            put_path(&mut w, &job.dir);
+            put_u64(&mut w, job.depth as u64);
+            match job.resume {
+                Some(off) => {
+                    w.push(1);
+                    put_u64(&mut w, off);
+                }
+                None => w.push(0),
+            }
+        }
+        put_u64(&mut w, self.stats.len() as u64);
+        for (dir, s) in &self.stats {
+            put_path(&mut w, dir);
+            for v in [
+                s.logical,
+                s.physical,
+                s.files,
+                s.dirs,
+                s.symlinks,
+                s.max_mtime as u64,
+                s.min_atime as u64,
+                s.exclusive,
+                s.incomplete,
+                s.ignored,
+            ] {
+                put_u64(&mut w, v);
+            }
+        }
+        for keys in [&self.inodes, &self.visited] {
+            put_u64(&mut w, keys.len() as u64);
+            for &(dev, ino) in keys {
+                put_u64(&mut w, dev);
+                put_u64(&mut w, ino);
+            }
+        }
+        put_u64(&mut w, self.links.len() as u64);
+        for ((dev, ino), s) in &self.links {
+            put_u64(&mut w, *dev);
+            put_u64(&mut w, *ino);
+            put_u64(&mut w, s.nlink);
+            put_u64(&mut w, s.physical);
+            put_u64(&mut w, s.dirs.len() as u64);
+            for d in &s.dirs {
+                put_path(&mut w, d);
+            }
+        }
+        w
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut r = Reader { data, pos: 0 };
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(anyhow!("not a hyperdu checkpoint"));
+        }
+        let mut cp = Checkpoint {
+            root: r.path()?,
+            ..Checkpoint::default()
+        };
+        for _ in 0..r.u64()? {
+            let dir = r.path()?;
+            let depth = r.u64()? as u32;
+            let resume = match r.take(1)?[0] {
+                0 => None,
+                _ => Some(r.u64()?),
+            };
+            cp.jobs.push(Job { dir, depth, resume });
+        }
+        for _ in 0..r.u64()? {
+            let dir = r.path()?;
+            let mut v = [0u64; 10];
+            for x in &mut v {
+                *x = r.u64()?;
+            }
+            let s = Stat {
+                logical: v[0],
+                physical: v[1],
+                files: v[2],
+                dirs: v[3],
+                symlinks: v[4],
+                max_mtime: v[5] as i64,
+                min_atime: v[6] as i64,
+                exclusive: v[7],
+                incomplete: v[8],
+                ignored: v[9],
+            };
+            cp.stats.insert(dir, s);
+        }
+        for keys in [&mut cp.inodes, &mut cp.visited] {
+            for _ in 0..r.u64()? {
+                keys.push((r.u64()?, r.u64()?));
+            }
+        }
+        for _ in 0..r.u64()? {
+            let key = (r.u64()?, r.u64()?);
+            let mut s = SharedInode {
+                nlink: r.u64()?,
+                physical: r.u64()?,
+                dirs: Vec::new(),
+            };
+            for _ in 0..r.u64()? {
+                s.dirs.push(r.path()?);
+            }
+            cp.links.push((key, s));
+        }
+        if r.pos != data.len() {
+            return Err(anyhow!("trailing data"));
+        }
+        Ok(cp)
+    }
+}
+
+fn put_u64(w: &mut Vec<u8>, v: u64) {
+    w.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_path(w: &mut Vec<u8>, p: &Path) {
+    let b = p.as_os_str().as_encoded_bytes();
+    put_u64(w, b.len() as u64);
+    w.extend_from_slice(b);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&e| e <= self.data.len())
+            .ok_or_else(|| anyhow!("truncated"))?;
+        let b = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(b)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let b = self.take(8)?;
+        Ok(u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn path(&mut self) -> Result<PathBuf> {
+        let n = self.u64()? as usize;
+        let b = self.take(n)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            Ok(PathBuf::from(std::ffi::OsStr::from_bytes(b)))
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(PathBuf::from(String::from_utf8_lossy(b).into_owned()))
+        }
+    }
+}
+
+/// Lets the checkpoint writer stop all workers at a job boundary, so the
+/// published live state is consistent while it is saved.
+pub(crate) struct PauseGate {
+    requested: AtomicBool,
+    state: Mutex<GateState>,
+    cond: Condvar,
+    workers: usize,
+}
+
+#[derive(Default)]
+struct GateState {
+    parked: usize,
+    exited: usize,
+    generation: u64,
+}
+
+impl PauseGate {
+    pub(crate) fn new(workers: usize) -> Self {
+        Self {
+            requested: AtomicBool::new(false),
+            state: Mutex::default(),
+            cond: Condvar::new(),
+            workers,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn requested(&self) -> bool {
+        self.requested.load(Ordering::Acquire)
+    }
+
+    /// Block the calling worker until the current pause is over. The worker
+    /// must have published its results first.
+    pub(crate) fn park(&self) {
+        let mut st = self.state.lock().unwrap();
+        if !self.requested() {
+            return;
+        }
+        st.parked += 1;
+        let gen = st.generation;
+        self.cond.notify_all();
+        while st.generation == gen {
+            st = self.cond.wait(st).unwrap();
+        }
+    }
+
+    /// Called once by each worker when it leaves the job loop.
+    pub(crate) fn exit(&self) {
+        self.state.lock().unwrap().exited += 1;
+        self.cond.notify_all();
+    }
+
+    /// Run `f` while every worker is parked or has exited.
+    fn quiesce(&self, f: impl FnOnce()) {
+        self.requested.store(true, Ordering::Release);
+        let mut st = self.state.lock().unwrap();
+        while st.parked + st.exited < self.workers {
+            st = self.cond.wait(st).unwrap();
+        }
+        f();
+        self.requested.store(false, Ordering::Release);
+        st.parked = 0;
+        st.generation += 1;
+        self.cond.notify_all();
+    }
+}
+
+/// Snapshot the current scan state. Workers must be parked or done.
+pub(crate) fn capture(
+    root: &Path,
+    live: &LiveState,
+    opt: &Options,
+    links: &LinkTable,
+) -> Checkpoint {
+    let (stats, jobs) = live.checkpoint_state();
+    let keys = |m: &Option<Arc<dashmap::DashMap<(u64, u64), ()>>>| {
+        m.as_ref()
+            .map(|m| m.iter().map(|e| *e.key()).collect())
+            .unwrap_or_default()
+    };
+    Checkpoint {
+        root: root.to_path_buf(),
+        jobs,
+        stats,
+        inodes: keys(&opt.inode_cache),
+        visited: keys(&opt.visited_dirs),
+        links: links.entries(),
+    }
+}
+
+/// Put the dedupe state of `cp` back into `opt` and `links`.
+pub(crate) fn restore(cp: &Checkpoint, opt: &Options, links: &LinkTable) {
+    if let Some(cache) = &opt.inode_cache {
+        for &key in &cp.inodes {
+            cache.insert(key, ());
+        }
+    }
+    if let Some(vset) = &opt.visited_dirs {
+        for &(dev, ino) in &cp.visited {
+            if let Some(bf) = &opt.visited_bloom {
+                bf.test_and_set(dev, ino);
+            }
+            vset.insert((dev, ino), ());
+        }
+    }
+    links.restore(&cp.links);
+}
+
+/// Report a failed save through `Options::error_report`; the scan goes on.
+pub(crate) fn save_or_report(cp: &Checkpoint, path: &Path, opt: &Options) {
+    if let Err(e) = cp.save(path) {
+        if let Some(cb) = &opt.error_report {
+            cb(&format!("checkpoint: {e:#}"));
+        }
+    }
+}
+
+/// Stops and joins the checkpoint writer when dropped.
+pub(crate) struct WriterGuard {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for WriterGuard {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+/// Start a thread that saves a checkpoint to `path` every `opt.checkpoint_every_secs`.
+pub(crate) fn start_writer(
+    root: PathBuf,
+    path: PathBuf,
+    opt: Arc<Options>,
+    gate: Arc<PauseGate>,
+    live: Arc<LiveState>,
+    links: Arc<LinkTable>,
+) -> WriterGuard {
+    let running = Arc::new(AtomicBool::new(true));
+    let running_c = running.clone();
+    let every = Duration::from_secs(opt.checkpoint_every_secs.max(1));
+    let handle = std::thread::Builder::new()
+        .name("hyperdu-checkpoint".into())
+        .spawn(move || {
+            let mut last = Instant::now();
+            while running_c.load(Ordering::Relaxed) && !opt.cancel.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(100));
+                if last.elapsed() < every {
+                    continue;
+                }
+                let mut cp = None;
+                gate.quiesce(|| cp = Some(capture(&root, &live, &opt, &links)));
+                if let Some(cp) = cp {
+                    save_or_report(&cp, &path, &opt);
+                }
+                last = Instant::now();
+            }
+        })
+        .ok();
+    WriterGuard { running, handle }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::field_reassign_with_default)]
+    use super::*;
+
+    fn sample(root: &Path) -> Checkpoint {
+        let mut stats = StatMap::default();
+        stats.insert(
+            root.to_path_buf(),
+            Stat {
+                logical: 3,
+                physical: 3,
+                files: 1,
+                ..Stat::default()
+            },
+        );
+        Checkpoint {
+            root: root.to_path_buf(),
+            jobs: vec![
+                Job {
+                    dir: root.join("gone"),
+                    depth: 1,
+                    resume: None,
+                },
+                Job {
+                    dir: root.join("half"),
+                    depth: 1,
+                    resume: Some(42),
+                },
+            ],
+            stats,
+            inodes: vec![(1, 2)],
+            visited: Vec::new(),
+            links: vec![(
+                (1, 7),
+                SharedInode {
+                    nlink: 2,
+                    physical: 4096,
+                    dirs: vec![root.to_path_buf()],
+                },
+            )],
+        }
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("scan.ckpt");
+        let cp = sample(tmp.path());
+        cp.save(&path).unwrap();
+        let back = Checkpoint::load(&path).unwrap();
+        assert_eq!(back.root(), tmp.path());
+        assert_eq!(back.jobs, cp.jobs);
+        assert_eq!(back.stats[tmp.path()].logical, 3);
+        assert_eq!((back.inodes, back.links), (cp.inodes, cp.links));
+    }
+
+    #[test]
+    fn corrupt_files_are_rejected() {
+        let data = sample(Path::new("/r")).encode();
+        // Every truncation fails instead of yielding a partial checkpoint
+        for n in 0..data.len() {
+            assert!(Checkpoint::decode(&data[..n]).is_err(), "prefix {n}");
+        }
+        let mut long = data.clone();
+        long.push(0);
+        assert!(format!("{:#}", Checkpoint::decode(&long).unwrap_err()).contains("trailing"));
+        // Older format versions and foreign files
+        let mut old = data.clone();
+        old[7] = 1;
+        assert!(format!("{:#}", Checkpoint::decode(&old).unwrap_err()).contains("not a hyperdu"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("scan.ckpt");
+        std::fs::write(&path, b"garbage").unwrap();
+        let err = format!("{:#}", Checkpoint::load(&path).unwrap_err());
+        assert!(err.contains("invalid checkpoint"), "{err}");
+        assert!(Checkpoint::load(tmp.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn stale_checkpoints_resume_or_refuse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(root.join("half")).unwrap();
+        std::fs::write(root.join("half/x"), b"xy").unwrap();
+        let scanner = || Arc::new(crate::platform_scanner());
+
+        // A checkpoint of another root is refused
+        let mut opt = Options::default();
+        opt.resume_from = Some(Arc::new(sample(tmp.path())));
+        let err = crate::scan_directory_with(&root, &opt, scanner()).unwrap_err();
+        assert!(err.to_string().contains("checkpoint is for"), "{err}");
+
+        // Directories that vanished since the checkpoint are reported and the
+        // rest of the scan continues
+        let mut cp = sample(&root);
+        cp.jobs[1].resume = None;
+        let mut opt = Options::default();
+        opt.threads = 1;
+        opt.resume_from = Some(Arc::new(cp));
+        let map = crate::scan_directory_with(&root, &opt, scanner()).unwrap();
+        let errors = opt.errors.lock().unwrap().clone();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, root.join("gone"));
+        let r = map[&root];
+        assert_eq!((r.files, r.logical), (2, 5));
+        assert!(!r.is_complete());
+    }
+}
@@ -1,13 +1,27 @@
 use std::{
+    cell::Cell,
     collections::{BTreeMap, HashMap},
     fs,
     io::Read,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
 };
 
+use crossbeam_channel::{Receiver, Sender};
 use serde::Serialize;
 
-use crate::{filters::path_excluded, Options};
+use crate::{EntryKind, FileEntry, FileVisitor, Options};
+
+// Keep the largest files in `top_consumers`: trim back to this many size buckets
+// once twice as many have accumulated.
+const TOP_KEEP: usize = 1024;
+
+// Files queued per sniffing thread before the scan workers wait for them.
+const DEEP_QUEUE_PER_THREAD: usize = 256;
 
 #[derive(Clone, Copy, Debug)]
 pub enum ClassifyMode {
@@ -30,27 +44,64 @@ pub struct TypeStatistics {
 
 impl TypeStatistics {
     pub fn add(&mut self, path: &Path, ext: &str, cat: &str, size: u64) {
-        let e = self.by_extension.entry(ext.to_string()).or_default();
-        e.files += 1;
-        e.bytes += size;
-        let c = self.by_category.entry(cat.to_string()).or_default();
-        c.files += 1;
-        c.bytes += size;
-        self.top_consumers
-            .entry(size)
-            .or_default()
-            .push(path.to_path_buf());
-        if self.top_consumers.len() > 2048 {
+        self.count(ext, cat, size);
+        self.note_top(size, || path.to_path_buf());
+    }
+
+    fn count(&mut self, ext: &str, cat: &str, size: u64) {
+        bump(&mut self.by_extension, ext, size);
+        bump(&mut self.by_category, cat, size);
+    }
+
+    /// Record `path` as a top consumer unless it is too small to be kept anyway.
+    fn note_top(&mut self, size: u64, path: impl FnOnce() -> PathBuf) {
+        if self.top_consumers.len() >= TOP_KEEP
+            && self.top_consumers.keys().next().is_some_and(|&k| size < k)
+        {
+            return;
+        }
+        self.top_consumers.entry(size).or_default().push(path());
+        self.trim_top();
+    }
+
+    fn trim_top(&mut self) {
+        if self.top_consumers.len() > 2 * TOP_KEEP {
             // cap memory by trimming smallest buckets
-            while self.top_consumers.len() > 1024 {
-                if let Some(k) = self.top_consumers.keys().next().cloned() {
-                    self.top_consumers.remove(&k);
-                } else {
-                    break;
-                }
+            while self.top_consumers.len() > TOP_KEEP {
+                self.top_consumers.pop_first();
             }
         }
     }
+
+    pub fn merge(&mut self, other: TypeStatistics) {
+        for (k, v) in other.by_extension {
+            let e = self.by_extension.entry(k).or_default();
+            e.files += v.files;
+            e.bytes += v.bytes;
+        }
+        for (k, v) in other.by_category {
+            let c = self.by_category.entry(k).or_default();
+            c.files += v.files;
+            c.bytes += v.bytes;
+        }
+        for (size, mut paths) in other.top_consumers {
+            self.top_consumers
+                .entry(size)
+                .or_default()
+                .append(&mut paths);
+        }
+        self.trim_top();
+    }
+}
+
+#[inline]
+fn bump(map: &mut HashMap<String, CategoryStats>, key: &str, size: u64) {
+    let s = match map.get_mut(key) {
+        Some(s) => s,
+        None => map.entry(key.to_string()).or_default(),
+    };
+    s.files += 1;
+    s.bytes += size;
 }
 
 fn basic_category_from_ext(ext: &str) -> &'static str {
@@ -114,47 +165,174 @@ fn deep_category_from_bytes(buf: &[u8]) -> &'static str {
     "other"
 }
 
-pub fn classify_directory(root: &Path, opt: &Options, mode: ClassifyMode) -> TypeStatistics {
-    let mut stats = TypeStatistics::default();
-    fn walk(dir: &Path, depth: u32, opt: &Options, mode: ClassifyMode, stats: &mut TypeStatistics) {
-        if opt.max_depth > 0 && depth > opt.max_depth {
-            return;
+fn deep_category_of(path: &Path) -> Option<&'static str> {
+    let mut f = fs::File::open(path).ok()?;
+    let mut buf = [0u8; 8192];
+    let n: usize = f.read(&mut buf).unwrap_or_default();
+    Some(deep_category_from_bytes(&buf[..n]))
+}
+
+thread_local! {
+    // Shard slot of the current thread, assigned on first use
+    static SHARD: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+#[inline]
+fn shard_index(n: usize) -> usize {
+    SHARD.with(|s| {
+        if s.get() == usize::MAX {
+            s.set(NEXT_SHARD.fetch_add(1, Ordering::Relaxed));
         }
-        let rd = match fs::read_dir(dir) {
-            Ok(r) => r,
-            Err(_) => return,
+        s.get() % n
+    })
+}
+
+struct DeepJob {
+    path: PathBuf,
+    ext: String,
+    size: u64,
+}
+
+/// MIME sniffing stage: worker threads drain a bounded queue of files fed by
+/// the scan, so a fast scan waits for the readers instead of piling up jobs.
+struct DeepStage {
+    jobs: Sender<DeepJob>,
+    // Dropped by `join`: the workers drain what is queued and exit
+    stop: Mutex<Option<Sender<()>>>,
+    workers: Mutex<Vec<JoinHandle<TypeStatistics>>>,
+}
+
+impl DeepStage {
+    fn spawn(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (jobs, rx) = crossbeam_channel::bounded::<DeepJob>(threads * DEEP_QUEUE_PER_THREAD);
+        let (stop, stopped) = crossbeam_channel::bounded::<()>(0);
+        let workers = (0..threads)
+            .map(|_| {
+                let rx = rx.clone();
+                let stopped = stopped.clone();
+                std::thread::spawn(move || Self::work(&rx, &stopped))
+            })
+            .collect();
+        Self {
+            jobs,
+            stop: Mutex::new(Some(stop)),
+            workers: Mutex::new(workers),
+        }
+    }
+
+    fn work(rx: &Receiver<DeepJob>, stopped: &Receiver<()>) -> TypeStatistics {
+        let mut stats = TypeStatistics::default();
+        let mut sniff = |job: DeepJob| {
+            // Unreadable files keep their extension category
+            let cat =
+                deep_category_of(&job.path).unwrap_or_else(|| basic_category_from_ext(&job.ext));
+            stats.count(&job.ext, cat, job.size);
+            stats.note_top(job.size, || job.path);
         };
-        for ent in rd {
-            let Ok(ent) = ent else { continue };
-            let path = ent.path();
-            if path_excluded(&path, opt) {
-                continue;
-            }
-            let Ok(md) = ent.metadata() else { continue };
-            if md.is_dir() {
-                walk(&path, depth + 1, opt, mode, stats);
-            } else if md.is_file() {
-                let size = md.len();
-                if size < opt.min_file_size {
-                    continue;
-                }
-                let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-                let mut cat = basic_category_from_ext(ext);
-                if let ClassifyMode::Deep = mode {
-                    if size > 0 {
-                        let mut f = match fs::File::open(&path) {
-                            Ok(f) => f,
-                            Err(_) => continue,
-                        };
-                        let mut buf = [0u8; 8192];
-                        let n: usize = f.read(&mut buf).unwrap_or_default();
-                        cat = deep_category_from_bytes(&buf[..n]);
-                    }
+        loop {
+            crossbeam_channel::select! {
+                recv(rx) -> job => match job {
+                    Ok(job) => sniff(job),
+                    Err(_) => break,
+                },
+                recv(stopped) -> _ => {
+                    // The scan is over, so nothing new arrives
+                    rx.try_iter().for_each(&mut sniff);
+                    break;
                 }
-                stats.add(&path, ext, cat, size);
             }
         }
+        stats
+    }
+
+    fn join(&self) -> TypeStatistics {
+        self.stop.lock().unwrap().take();
+        let mut stats = TypeStatistics::default();
+        for w in self.workers.lock().unwrap().drain(..) {
+            if let Ok(s) = w.join() {
+                stats.merge(s);
+            }
+        }
+        stats
+    }
+}
+
+/// Visitor that classifies files while the scan runs.
+///
+/// Basic mode categorizes by extension into per-thread [`TypeStatistics`] shards.
+/// Deep mode hands non-empty files to a pool of MIME sniffing threads instead,
+/// so the scan workers never read file contents. Sizes are logical and follow
+/// the scan's own filters (exclude, min size, depth). Call
+/// [`ClassifyVisitor::finish`] once after the scan to merge the results.
+pub struct ClassifyVisitor {
+    shards: Box<[Mutex<TypeStatistics>]>,
+    deep: Option<DeepStage>,
+}
+
+impl ClassifyVisitor {
+    pub fn new(mode: ClassifyMode) -> Self {
+        let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+        Self::with_threads(mode, threads)
     }
-    walk(root, 0, opt, mode, &mut stats);
-    stats
+
+    /// `threads` sizes the shard count and, in deep mode, the sniffing pool.
+    pub fn with_threads(mode: ClassifyMode, threads: usize) -> Self {
+        let threads = threads.max(1);
+        Self {
+            shards: (0..threads)
+                .map(|_| Mutex::new(TypeStatistics::default()))
+                .collect(),
+            deep: matches!(mode, ClassifyMode::Deep).then(|| DeepStage::spawn(threads)),
+        }
+    }
+
+    /// Wait for the deep stage to drain and merge all shards.
+    pub fn finish(&self) -> TypeStatistics {
+        let mut stats = self.deep.as_ref().map(DeepStage::join).unwrap_or_default();
+        for shard in self.shards.iter() {
+            stats.merge(std::mem::take(&mut *shard.lock().unwrap()));
+        }
+        stats
+    }
+}
+
+impl FileVisitor for ClassifyVisitor {
+    fn visit(&self, entry: &FileEntry<'_>) {
+        if entry.kind != EntryKind::File {
+            return;
+        }
+        let ext = Path::new(entry.name)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("");
+        let size = entry.logical;
+        if let (Some(d), true) = (&self.deep, size > 0) {
+            // Waits while the queue is full
+            let _ = d.jobs.send(DeepJob {
+                path: entry.path(),
+                ext: ext.to_string(),
+                size,
+            });
+            return;
+        }
+        let cat = basic_category_from_ext(ext);
+        let mut s = self.shards[shard_index(self.shards.len())].lock().unwrap();
+        s.count(ext, cat, size);
+        s.note_top(size, || entry.path());
+    }
+}
+
+/// Classify a tree with the platform scanner and a [`ClassifyVisitor`].
+/// When scanning anyway, attach the visitor to that scan instead.
+pub fn classify_directory(root: &Path, opt: &Options, mode: ClassifyMode) -> TypeStatistics {
+    let v = Arc::new(ClassifyVisitor::with_threads(mode, opt.threads));
+    let _ = crate::scan_directory_with_visitor(
+        root,
+        opt,
+        Arc::new(crate::platform_scanner()),
+        v.clone(),
+    );
+    v.finish()
 }
@@ -3,7 +3,7 @@ use std::{
     sync::atomic::{AtomicU64, Ordering},
 };
 
-use crate::Options;
+use crate::{visitor::EntryKind, FileEntry, Options, ScanContext};
 
 /// Check if a hardlink has already been counted
 /// Returns true if this is a duplicate that should be skipped
@@ -89,6 +89,20 @@ pub fn calculate_physical_size(opt: &Options, logical: u64, blocks: u64) -> u64
     }
 }
 
+/// Whether a non-directory with file type `ftype` (`S_IFMT` bits) is counted as
+/// a file: regular files, followed symlinks, and special files (fifo, socket,
+/// device) only with `Options::count_special_files` (Linux-only path).
+#[cfg(target_os = "linux")]
+#[inline]
+pub fn counts_as_file(opt: &Options, ftype: u32) -> bool {
+    match ftype {
+        libc::S_IFREG => true,
+        libc::S_IFLNK => opt.follow_links,
+        libc::S_IFDIR | 0 => false,
+        _ => opt.count_special_files,
+    }
+}
+
 /// Check if path should be excluded based on fast exclude optimization (Linux-only path)
 #[cfg(target_os = "linux")]
 #[inline]
@@ -97,3 +111,102 @@ pub fn should_fast_exclude(opt: &Options) -> bool {
         .iter()
         .any(|s| s.as_bytes().iter().any(|&c| c == b'/' || c == b'\\'))
 }
+
+/// Extra statx fields needed to fill a [`FileEntry`]: the times folded into
+/// `Stat` and inode/link count for the exclusive size, plus ctime and owner
+/// when a visitor is attached.
+#[cfg(all(target_os = "linux", not(target_env = "musl")))]
+#[inline]
+pub fn entry_statx_mask(ctx: &ScanContext) -> u32 {
+    let mask = libc::STATX_MTIME | libc::STATX_ATIME | libc::STATX_INO | libc::STATX_NLINK;
+    // Filter predicates may test ctime and ownership
+    if ctx.has_visitor() || ctx.options.filter.is_some() {
+        mask | libc::STATX_CTIME | libc::STATX_UID | libc::STATX_GID
+    } else {
+        mask
+    }
+}
+
+/// Build a visitor entry from a statx result (Linux-only path).
+/// `dev` uses the `st_dev` encoding so it matches std metadata.
+#[cfg(all(target_os = "linux", not(target_env = "musl")))]
+#[inline]
+pub fn statx_entry<'a>(
+    parent: &'a Path,
+    name: &'a [u8],
+    kind: EntryKind,
+    stx: &libc::statx,
+    logical: u64,
+    physical: u64,
+) -> FileEntry<'a> {
+    use std::os::unix::ffi::OsStrExt;
+    FileEntry {
+        logical,
+        physical,
+        allocated: (stx.stx_mask & libc::STATX_BLOCKS != 0).then_some(stx.stx_blocks * 512),
+        dev: libc::makedev(stx.stx_dev_major, stx.stx_dev_minor),
+        ino: stx.stx_ino,
+        mtime: stx.stx_mtime.tv_sec,
+        atime: stx.stx_atime.tv_sec,
+        ctime: stx.stx_ctime.tv_sec,
+        uid: (stx.stx_mask & libc::STATX_UID != 0).then_some(stx.stx_uid),
+        gid: (stx.stx_mask & libc::STATX_GID != 0).then_some(stx.stx_gid),
+        nlink: stx.stx_nlink as u64,
+        ..FileEntry::new(parent, std::ffi::OsStr::from_bytes(name), kind)
+    }
+}
+
+/// Record a file skipped by hardlink dedupe (Linux statx paths).
+#[cfg(all(target_os = "linux", not(target_env = "musl")))]
+#[inline]
+pub fn record_statx_link(ctx: &ScanContext, parent: &Path, name: &[u8], stx: &libc::statx) {
+    let opt = ctx.options;
+    if stx.stx_size >= opt.min_file_size {
+        let physical = calculate_physical_size(opt, stx.stx_size, stx.stx_blocks);
+        ctx.record_link(&statx_entry(
+            parent,
+            name,
+            EntryKind::File,
+            stx,
+            stx.stx_size,
+            physical,
+        ));
+    }
+}
+
+/// Build a visitor entry from std metadata (fallback paths)
+#[cfg(unix)]
+#[inline]
+pub fn metadata_entry<'a>(
+    parent: &'a Path,
+    name: &'a std::ffi::OsStr,
+    md: &std::fs::Metadata,
+    logical: u64,
+    physical: u64,
+) -> FileEntry<'a> {
+    use std::os::unix::fs::MetadataExt;
+    let ft = md.file_type();
+    let kind = if ft.is_file() {
+        EntryKind::File
+    } else if ft.is_dir() {
+        EntryKind::Dir
+    } else if ft.is_symlink() {
+        EntryKind::Symlink
+    } else {
+        EntryKind::Other
+    };
+    FileEntry {
+        logical,
+        physical,
+        allocated: Some(md.blocks() * 512),
+        dev: md.dev(),
+        ino: md.ino(),
+        mtime: md.mtime(),
+        atime: md.atime(),
+        ctime: md.ctime(),
+        uid: Some(md.uid()),
+        gid: Some(md.gid()),
+        nlink: md.nlink(),
+        ..FileEntry::new(parent, name, kind)
+    }
+}
@@ -4,32 +4,79 @@ use std::{
     sync::atomic::Ordering,
 };
 
+use serde::Serialize;
+
 use crate::Options;
 
-/// Recommended next step when a recoverable error occurs.
-#[derive(Debug, Clone, Copy)]
-#[allow(dead_code)]
-pub enum RecoveryAction {
-    SkipEntry,
-    SkipDirectory,
-    Retry,
-    Abort,
+/// Structured records kept per scan; beyond this only `error_count` grows.
+pub const MAX_ERROR_RECORDS: usize = 10_000;
+
+/// Coarse class of a scan error, for grouping in reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanErrorKind {
+    PermissionDenied,
+    /// Removed or replaced between listing and opening it
+    Vanished,
+    /// Too many levels of symbolic links (ELOOP)
+    Loop,
+    /// Device or filesystem I/O error (EIO)
+    Io,
+    /// Network filesystem or device timeout
+    Timeout,
+    Other,
+}
+
+impl ScanErrorKind {
+    /// Classify an OS error code (errno / GetLastError).
+    pub fn from_errno(errno: i32) -> Self {
+        #[cfg(unix)]
+        match errno {
+            libc::ELOOP => return Self::Loop,
+            libc::EIO => return Self::Io,
+            libc::ENOTDIR | libc::ESTALE => return Self::Vanished,
+            _ => {}
+        }
+        Self::from_io_kind(std::io::Error::from_raw_os_error(errno).kind())
+    }
+
+    fn from_io_kind(kind: std::io::ErrorKind) -> Self {
+        match kind {
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            std::io::ErrorKind::NotFound => Self::Vanished,
+            std::io::ErrorKind::TimedOut => Self::Timeout,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for ScanErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::PermissionDenied => "permission denied",
+            Self::Vanished => "vanished",
+            Self::Loop => "symlink loop",
+            Self::Io => "I/O error",
+            Self::Timeout => "timed out",
+            Self::Other => "other",
+        })
+    }
+}
+
+/// One path the scan could not read, as returned with the scan results.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScanErrorRecord {
+    pub path: PathBuf,
+    pub kind: ScanErrorKind,
+    /// Raw OS error code, when the failure came from the OS
+    pub errno: Option<i32>,
+    /// Failing system call (`open`, `FindFirstFileExW`, ...), when known
+    pub syscall: Option<&'static str>,
 }
 
-/// Typed scan errors; used for diagnostics and recovery hints.
+/// Typed scan errors; used for diagnostics.
 #[derive(Debug)]
-#[allow(dead_code)]
 pub enum ScanError {
-    IoError {
-        path: PathBuf,
-        source: std::io::Error,
-    },
-    PermissionDenied {
-        path: PathBuf,
-    },
-    InvalidPath {
-        path: PathBuf,
-    },
     SystemCall {
         path: PathBuf,
         call: &'static str,
@@ -40,11 +87,6 @@ pub enum ScanError {
 impl fmt::Display for ScanError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ScanError::IoError { path, source } => write!(f, "{}: {}", path.display(), source),
-            ScanError::PermissionDenied { path } => {
-                write!(f, "{}: permission denied", path.display())
-            }
-            ScanError::InvalidPath { path } => write!(f, "{}: invalid path", path.display()),
             ScanError::SystemCall { path, call, errno } => {
                 write!(f, "{}: {} failed (errno={})", path.display(), call, errno)
             }
@@ -52,97 +94,41 @@ impl fmt::Display for ScanError {
     }
 }
 
-#[allow(dead_code)]
-pub type ScanResult<T> = Result<T, ScanError>;
-
-#[allow(dead_code)]
-pub trait ErrorRecovery {
-    fn is_recoverable(&self) -> bool;
-    fn recovery_action(&self) -> RecoveryAction;
-}
-
-impl ErrorRecovery for ScanError {
-    fn is_recoverable(&self) -> bool {
-        matches!(
-            self.recovery_action(),
-            RecoveryAction::SkipEntry | RecoveryAction::SkipDirectory | RecoveryAction::Retry
-        )
-    }
-    fn recovery_action(&self) -> RecoveryAction {
-        match self {
-            ScanError::PermissionDenied { .. } => RecoveryAction::SkipEntry,
-            ScanError::InvalidPath { .. } => RecoveryAction::SkipEntry,
-            ScanError::IoError { source, .. } => match source.kind() {
-                std::io::ErrorKind::NotFound => RecoveryAction::SkipEntry,
-                std::io::ErrorKind::PermissionDenied => RecoveryAction::SkipEntry,
-                std::io::ErrorKind::Interrupted => RecoveryAction::Retry,
-                std::io::ErrorKind::WouldBlock => RecoveryAction::Retry,
-                _ => RecoveryAction::SkipEntry,
-            },
-            ScanError::SystemCall { errno, .. } => match *errno {
-                // EACCES, EPERM
-                13 | 1 => RecoveryAction::SkipEntry,
-                // ENOENT, ENOTDIR
-                2 | 20 => RecoveryAction::SkipEntry,
-                // EBUSY, EAGAIN
-                16 | 11 => RecoveryAction::Retry,
-                _ => RecoveryAction::SkipEntry,
-            },
+impl ScanError {
+    /// Structured form kept in the scan results.
+    pub fn record(&self) -> ScanErrorRecord {
+        let (path, kind, errno, syscall) = match self {
+            ScanError::SystemCall { path, call, errno } => (
+                path,
+                ScanErrorKind::from_errno(*errno),
+                Some(*errno),
+                Some(*call),
+            ),
+        };
+        ScanErrorRecord {
+            path: path.clone(),
+            kind,
+            errno,
+            syscall,
         }
     }
 }
 
-/// Increment counters and notify callback with a formatted typed error.
+/// Increment counters, keep a structured record and notify callback with a
+/// formatted typed error.
 #[inline]
 pub fn record_error(opt: &Options, err: &ScanError) {
     opt.error_count.fetch_add(1, Ordering::Relaxed);
-    if let Some(cb) = &opt.error_report {
-        cb(&err.to_string());
+    if let Ok(mut log) = opt.errors.lock() {
+        if log.len() < MAX_ERROR_RECORDS {
+            log.push(err.record());
+        }
     }
-}
-
-/// Compatibility shim: stringly-typed error report (legacy call sites)
-#[inline]
-#[allow(dead_code)]
-pub fn report_error(opt: &Options, path: &Path, error: &str) {
-    opt.error_count.fetch_add(1, Ordering::Relaxed);
     if let Some(cb) = &opt.error_report {
-        cb(&format!("{}: {}", path.display(), error));
+        cb(&err.to_string());
     }
 }
 
-/// Try an operation with typed error reporting and early return
-#[macro_export]
-macro_rules! try_with_error {
-    ($opt:expr, $path:expr, $op:expr, $call:expr) => {
-        match $op {
-            Ok(val) => val,
-            Err(e) => {
-                let p = std::path::PathBuf::from($path);
-                let se = $crate::error_handling::ScanError::IoError { path: p, source: e };
-                $crate::error_handling::record_error($opt, &se);
-                return;
-            }
-        }
-    };
-}
-
-/// Try an operation, continue loop on typed error
-#[macro_export]
-macro_rules! try_or_continue {
-    ($opt:expr, $path:expr, $op:expr, $call:expr) => {
-        match $op {
-            Ok(val) => val,
-            Err(e) => {
-                let p = std::path::PathBuf::from($path);
-                let se = $crate::error_handling::ScanError::IoError { path: p, source: e };
-                $crate::error_handling::record_error($opt, &se);
-                continue;
-            }
-        }
-    };
-}
-
 /// Build a SystemCall error from the last OS error (errno/GetLastError)
 #[inline]
 pub fn last_os_error_systemcall(path: &Path, call: &'static str) -> ScanError {
@@ -0,0 +1,353 @@
+//! Size estimates for scans stopped early by a time or file budget.
+//!
+//! Every fully read directory is a sample of its depth: its direct files,
+//! bytes and fan-out (subdirectories). The subtree below an unexplored
+//! directory at depth `d` is modelled as a branching process over these
+//! per-level averages, `T(d) = mean(d) + fanout(d) * T(d + 1)`, whose variance
+//! gives a 95% confidence interval. Below the deepest sampled level its
+//! averages repeat, with the fan-out capped below 1 so the tail converges.
+//! Partly read
+//! directories are estimated as if unread, so the interval leans high there.
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{Options, ScanTree};
+
+/// z for a two-sided 95% interval.
+const Z95: f64 = 1.96;
+
+/// Fan-out cap below the deepest sampled level, where the per-level averages
+/// are reused for every further level (caps the tail at 10x a directory).
+const MAX_TAIL_FANOUT: f64 = 0.9;
+
+/// Exact partial sum and the estimated total.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct EstimatedTotal {
+    /// Counted before the scan stopped.
+    pub counted: u64,
+    /// Expected total: `counted` plus the estimate for unexplored directories.
+    pub total: u64,
+    /// 95% confidence interval of the total (never below `counted`).
+    pub low: u64,
+    pub high: u64,
+}
+
+/// Estimate attached to a scan that ran out of budget.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SizeEstimate {
+    /// Directories queued or partly read when the budget ran out.
+    pub unexplored_dirs: u64,
+    /// Fully read directories the per-level averages come from.
+    pub sampled_dirs: u64,
+    pub files: EstimatedTotal,
+    pub logical: EstimatedTotal,
+    pub physical: EstimatedTotal,
+}
+
+/// Time/file budget of one scan, checked by the workers between jobs.
+pub(crate) struct Budget {
+    deadline: Option<Instant>,
+    max_files: u64,
+    files: AtomicU64,
+    hit: AtomicBool,
+}
+
+impl Budget {
+    pub(crate) fn new(opt: &Options) -> Self {
+        Self {
+            deadline: (opt.budget_ms > 0)
+                .then(|| Instant::now() + Duration::from_millis(opt.budget_ms)),
+            max_files: opt.budget_files,
+            files: AtomicU64::new(0),
+            hit: AtomicBool::new(false),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn counts_files(&self) -> bool {
+        self.max_files > 0
+    }
+
+    #[inline]
+    pub(crate) fn add_files(&self, n: u64) {
+        self.files.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// True once the budget is spent; workers then stop taking jobs.
+    #[inline]
+    pub(crate) fn exhausted(&self) -> bool {
+        if self.hit.load(Ordering::Relaxed) {
+            return true;
+        }
+        let spent = self.deadline.is_some_and(|d| Instant::now() >= d)
+            || (self.max_files > 0 && self.files.load(Ordering::Relaxed) >= self.max_files);
+        if spent {
+            self.hit.store(true, Ordering::Relaxed);
+        }
+        spent
+    }
+
+    /// Whether the scan was stopped by the budget.
+    pub(crate) fn was_hit(&self) -> bool {
+        self.hit.load(Ordering::Relaxed)
+    }
+}
+
+// files, logical, physical, fan-out
+const N: usize = 4;
+const FANOUT: usize = 3;
+
+#[derive(Clone, Copy, Default)]
+struct Level {
+    n: f64,
+    sum: [f64; N],
+    sum_sq: [f64; N],
+}
+
+impl Level {
+    fn add(&mut self, v: [f64; N]) {
+        self.n += 1.0;
+        for (i, x) in v.into_iter().enumerate() {
+            self.sum[i] += x;
+            self.sum_sq[i] += x * x;
+        }
+    }
+
+    fn mean(&self, i: usize) -> f64 {
+        self.sum[i] / self.n
+    }
+
+    /// Sample variance (0 for a single sample).
+    fn var(&self, i: usize) -> f64 {
+        if self.n < 2.0 {
+            return 0.0;
+        }
+        let m = self.mean(i);
+        ((self.sum_sq[i] - self.n * m * m) / (self.n - 1.0)).max(0.0)
+    }
+}
+
+/// Estimate totals from a stopped scan's tree, still holding per-directory
+/// (not rolled up) stats, and the depths of its unexplored directories.
+/// Unexplored directories must already be in `dirs` (marked incomplete).
+pub(crate) fn estimate(dirs: &ScanTree, unexplored: &[u32]) -> SizeEstimate {
+    // Parents come before children
+    let mut depth = vec![0usize; dirs.len()];
+    let mut levels: Vec<Level> = Vec::new();
+    let mut counted = [0u64; 3];
+    for id in dirs.ids() {
+        let d = match dirs.parent(id) {
+            Some(p) => depth[p as usize] + 1,
+            None => 0,
+        };
+        depth[id as usize] = d;
+        let s = dirs.stat(id);
+        counted[0] += s.files;
+        counted[1] += s.logical;
+        counted[2] += s.physical;
+        if s.incomplete > 0 {
+            continue;
+        }
+        if levels.len() <= d {
+            levels.resize(d + 1, Level::default());
+        }
+        let c = dirs.children(id).len();
+        levels[d].add([
+            s.files as f64,
+            s.logical as f64,
+            s.physical as f64,
+            c as f64,
+        ]);
+    }
+    // Unsampled levels borrow the nearest shallower (else deeper) sampled one
+    let sampled: Vec<usize> = (0..levels.len()).filter(|&d| levels[d].n > 0.0).collect();
+    let mut out = SizeEstimate {
+        unexplored_dirs: unexplored.len() as u64,
+        sampled_dirs: levels.iter().map(|l| l.n as u64).sum(),
+        ..SizeEstimate::default()
+    };
+    let Some(&deepest) = sampled.last() else {
+        let exact = |c| EstimatedTotal {
+            counted: c,
+            total: c,
+            low: c,
+            high: c,
+        };
+        out.files = exact(counted[0]);
+        out.logical = exact(counted[1]);
+        out.physical = exact(counted[2]);
+        return out;
+    };
+    let level_for = |d: usize| -> &Level {
+        let i = sampled
+            .iter()
+            .rev()
+            .find(|&&s| s <= d)
+            .unwrap_or(&sampled[0]);
+        &levels[*i]
+    };
+    let max_d = unexplored
+        .iter()
+        .map(|&d| d as usize)
+        .max()
+        .unwrap_or(0)
+        .max(deepest);
+    let mut k = vec![0f64; max_d + 1];
+    for &d in unexplored {
+        k[d as usize] += 1.0;
+    }
+
+    let mut totals = [EstimatedTotal::default(); 3];
+    for (q, t) in totals.iter_mut().enumerate() {
+        // Expected subtree value and its variance per depth, deepest first
+        let mut mean = vec![0f64; max_d + 3];
+        let mut var = vec![0f64; max_d + 3];
+        for d in (0..=max_d + 1).rev() {
+            let l = level_for(d);
+            if d > deepest {
+                // Stationary tail: T = mean + fanout * T
+                let mc = l.mean(FANOUT).min(MAX_TAIL_FANOUT);
+                mean[d] = l.mean(q) / (1.0 - mc);
+                var[d] = (l.var(q) + l.var(FANOUT) * mean[d] * mean[d]) / (1.0 - mc);
+            } else {
+                let (mc, vc) = (l.mean(FANOUT), l.var(FANOUT));
+                let (mt, vt) = (mean[d + 1], var[d + 1]);
+                mean[d] = l.mean(q) + mc * mt;
+                var[d] = l.var(q) + mc * vt + vc * mt * mt;
+            }
+        }
+        let mut est = 0.0;
+        let mut v = 0.0;
+        for d in 0..=max_d {
+            if k[d] == 0.0 {
+                continue;
+            }
+            est += k[d] * mean[d];
+            // Spread of k subtrees plus the uncertainty of the level means
+            v += k[d] * var[d] + k[d] * k[d] * var[d] / level_for(d).n;
+        }
+        let half = Z95 * v.sqrt();
+        let c = counted[q];
+        *t = EstimatedTotal {
+            counted: c,
+            total: c + est.round() as u64,
+            low: c + (est - half).max(0.0).round() as u64,
+            high: c + (est + half).round() as u64,
+        };
+    }
+    [out.files, out.logical, out.physical] = totals;
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::{Stat, StatMap};
+
+    fn dir(map: &mut StatMap, path: &str, files: u64, incomplete: bool) {
+        map.insert(
+            PathBuf::from(path),
+            Stat {
+                files,
+                logical: files * 100,
+                physical: files * 100,
+                incomplete: u64::from(incomplete),
+                ..Stat::default()
+            },
+        );
+    }
+
+    fn estimate_under(root: &str, map: &StatMap, unexplored: &[u32]) -> SizeEstimate {
+        estimate(&ScanTree::from_stat_map(Path::new(root), map), unexplored)
+    }
+
+    #[test]
+    fn no_samples_gives_the_counted_sums() {
+        let e = estimate(&ScanTree::default(), &[]);
+        assert_eq!(e, SizeEstimate::default());
+
+        // Only partly read directories: nothing to extrapolate from
+        let mut map = StatMap::default();
+        dir(&mut map, "/r", 3, true);
+        dir(&mut map, "/r/a", 0, true);
+        dir(&mut map, "/elsewhere", 7, false);
+        let e = estimate_under("/r", &map, &[0, 1]);
+        assert_eq!((e.unexplored_dirs, e.sampled_dirs), (2, 0));
+        let exact = EstimatedTotal {
+            counted: 3,
+            total: 3,
+            low: 3,
+            high: 3,
+        };
+        assert_eq!(e.files, exact);
+        assert_eq!(e.logical.high, 300);
+    }
+
+    #[test]
+    fn identical_samples_give_an_exact_estimate() {
+        // r/{a, b, c}: a and b read with 10 files each, c unexplored
+        let mut map = StatMap::default();
+        dir(&mut map, "/r", 1, false);
+        dir(&mut map, "/r/a", 10, false);
+        dir(&mut map, "/r/b", 10, false);
+        dir(&mut map, "/r/c", 0, true);
+        let e = estimate_under("/r", &map, &[1]);
+        assert_eq!(e.sampled_dirs, 3);
+        assert_eq!(
+            e.files,
+            EstimatedTotal {
+                counted: 21,
+                total: 31,
+                low: 31,
+                high: 31,
+            }
+        );
+        assert_eq!(e.physical.total, 3100);
+    }
+
+    #[test]
+    fn spread_samples_widen_the_interval() {
+        let mut map = StatMap::default();
+        dir(&mut map, "/r", 0, false);
+        dir(&mut map, "/r/a", 10, false);
+        dir(&mut map, "/r/b", 30, false);
+        dir(&mut map, "/r/c", 0, true);
+        let f = estimate_under("/r", &map, &[1]).files;
+        assert_eq!((f.counted, f.total), (40, 60));
+        // var 200, plus 200/2 for the mean: half width 1.96 * sqrt(300) = 34
+        assert_eq!((f.low, f.high), (40, 94));
+    }
+
+    #[test]
+    fn levels_below_the_samples_use_a_capped_fanout() {
+        // Only the root was read (5 files, one subdirectory): below it the
+        // fan-out of 1 is capped at 0.9, so the tail is 5 / (1 - 0.9)
+        let mut map = StatMap::default();
+        dir(&mut map, "/r", 5, false);
+        dir(&mut map, "/r/a", 0, true);
+        let f = estimate_under("/r", &map, &[1]).files;
+        assert_eq!((f.counted, f.total), (5, 55));
+    }
+
+    #[test]
+    fn file_budget_is_spent_once_reached() {
+        let mut opt = Options::default();
+        let unlimited = Budget::new(&opt);
+        assert!(!unlimited.counts_files() && !unlimited.exhausted());
+
+        opt.budget_files = 2;
+        let b = Budget::new(&opt);
+        assert!(b.counts_files());
+        b.add_files(1);
+        assert!(!b.exhausted() && !b.was_hit());
+        b.add_files(1);
+        assert!(b.exhausted() && b.was_hit());
+    }
+}
@@ -0,0 +1,254 @@
+//! Per-pattern accounting of what the exclude filters remove.
+//!
+//! With [`Options::exclude_stats`](crate::Options) set, a scan no longer prunes
+//! excluded paths: it walks them and charges their files to the first pattern
+//! that matches (globs, then regexes, then substrings, the order
+//! `path_excluded` checks them in), so the totals are unchanged but the cost of
+//! every pattern becomes visible. Everything below an excluded directory is
+//! charged to that directory's pattern.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+
+use dashmap::DashMap;
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{FileEntry, Options};
+
+/// Which exclude option a pattern came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExcludeKind {
+    Glob,
+    Regex,
+    Substring,
+}
+
+enum Matcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+    Substring(String),
+}
+
+struct Rule {
+    kind: ExcludeKind,
+    pattern: String,
+    matcher: Matcher,
+    files: AtomicU64,
+    dirs: AtomicU64,
+    logical: AtomicU64,
+    physical: AtomicU64,
+}
+
+impl Rule {
+    fn new(kind: ExcludeKind, pattern: &str, matcher: Matcher) -> Self {
+        Self {
+            kind,
+            pattern: pattern.to_string(),
+            matcher,
+            files: AtomicU64::new(0),
+            dirs: AtomicU64::new(0),
+            logical: AtomicU64::new(0),
+            physical: AtomicU64::new(0),
+        }
+    }
+}
+
+/// What one exclude pattern removed from the totals.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExcludeUsage {
+    pub kind: ExcludeKind,
+    pub pattern: String,
+    pub files: u64,
+    /// Excluded directories, not counting the ones below them.
+    pub dirs: u64,
+    pub logical: u64,
+    pub physical: u64,
+}
+
+/// Exclude accounting shared by every scan run with the same options.
+#[derive(Default)]
+pub struct ExcludeStats {
+    rules: OnceLock<Vec<Rule>>,
+    /// Excluded directories seen so far and the rule that excluded them
+    dirs: DashMap<PathBuf, usize>,
+}
+
+impl ExcludeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile the patterns of `opt`, once: later scans keep the first set.
+    /// Invalid patterns are skipped, as the exclude filters skip them.
+    pub(crate) fn start(&self, opt: &Options) {
+        self.rules.get_or_init(|| {
+            let globs = opt.exclude_glob.iter().filter_map(|g| {
+                let m = Glob::new(g).ok()?.compile_matcher();
+                Some(Rule::new(ExcludeKind::Glob, g, Matcher::Glob(m)))
+            });
+            let regexes = opt.exclude_regex.iter().filter_map(|r| {
+                let m = Regex::new(r).ok()?;
+                Some(Rule::new(ExcludeKind::Regex, r, Matcher::Regex(m)))
+            });
+            let substrings = opt
+                .exclude_contains
+                .iter()
+                .filter(|s| !s.is_empty())
+                .map(|s| Rule::new(ExcludeKind::Substring, s, Matcher::Substring(s.clone())));
+            globs.chain(regexes).chain(substrings).collect()
+        });
+    }
+
+    /// First rule matching `path`. Regexes also try the bare name, which is
+    /// what the backends' name fast path checks.
+    fn rule_for(&self, path: &Path) -> Option<usize> {
+        let rules = self.rules.get()?;
+        let s = path.to_string_lossy();
+        let name = path.file_name().map(|n| n.to_string_lossy());
+        rules.iter().position(|r| match &r.matcher {
+            Matcher::Glob(g) => g.is_match(path),
+            Matcher::Regex(re) => {
+                re.is_match(&s) || name.as_deref().is_some_and(|n| re.is_match(n))
+            }
+            Matcher::Substring(p) => s.contains(p.as_str()),
+        })
+    }
+
+    /// Rule excluding the directory `dir` (or one above it); remembers the
+    /// directory so its contents are charged to the same rule.
+    pub(crate) fn exclude_dir(&self, dir: &Path) -> Option<usize> {
+        let inherited = dir.parent().and_then(|p| self.dirs.get(p).map(|i| *i));
+        let i = inherited.or_else(|| self.rule_for(dir))?;
+        if self.dirs.insert(dir.to_path_buf(), i).is_none() && inherited.is_none() {
+            self.rules.get()?[i].dirs.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(i)
+    }
+
+    /// Rule excluding a file, if any.
+    #[inline]
+    pub(crate) fn rule_for_file(&self, entry: &FileEntry<'_>) -> Option<usize> {
+        if self.rules.get().map_or(true, |r| r.is_empty()) {
+            return None;
+        }
+        self.dirs
+            .get(entry.parent)
+            .map(|i| *i)
+            .or_else(|| self.rule_for(&entry.path()))
+    }
+
+    /// Charge an excluded file to rule `i`.
+    pub(crate) fn charge(&self, i: usize, entry: &FileEntry<'_>) {
+        if let Some(r) = self.rules.get().and_then(|r| r.get(i)) {
+            r.files.fetch_add(1, Ordering::Relaxed);
+            r.logical.fetch_add(entry.logical, Ordering::Relaxed);
+            r.physical.fetch_add(entry.physical, Ordering::Relaxed);
+        }
+    }
+
+    /// Usage per pattern, in the order the patterns are checked.
+    pub fn report(&self) -> Vec<ExcludeUsage> {
+        self.rules
+            .get()
+            .map(|rules| {
+                rules
+                    .iter()
+                    .map(|r| ExcludeUsage {
+                        kind: r.kind,
+                        pattern: r.pattern.clone(),
+                        files: r.files.load(Ordering::Relaxed),
+                        dirs: r.dirs.load(Ordering::Relaxed),
+                        logical: r.logical.load(Ordering::Relaxed),
+                        physical: r.physical.load(Ordering::Relaxed),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::field_reassign_with_default)]
+    use super::*;
+    use crate::EntryKind;
+
+    #[test]
+    fn overlapping_patterns_charge_each_file_once() {
+        let mut opt = Options::default();
+        opt.exclude_glob = vec!["**/*.log".into()];
+        // The invalid regex and the empty substring are skipped, as the filters do
+        opt.exclude_regex = vec!["^cache$".into(), "(".into()];
+        opt.exclude_contains = vec!["tmp".into(), String::new()];
+        let stats = ExcludeStats::new();
+        stats.start(&opt);
+        // Later scans keep the first set of patterns
+        stats.start(&Options::default());
+
+        // The regex matches the bare name; the glob and substring would not
+        let (r, cache) = (Path::new("/r"), Path::new("/r/cache"));
+        assert_eq!(stats.exclude_dir(cache), Some(1));
+        assert_eq!(stats.exclude_dir(cache), Some(1));
+        // Below an excluded directory its pattern wins over any other match
+        assert_eq!(stats.exclude_dir(&cache.join("tmp")), Some(1));
+        assert_eq!(stats.exclude_dir(&r.join("src")), None);
+
+        let files: [(PathBuf, &str, u64); 5] = [
+            (cache.join("tmp"), "x.log", 1),
+            (cache.to_path_buf(), "a", 2),
+            // Glob, then regex, then substring
+            (r.to_path_buf(), "tmp.log", 4),
+            (r.join("src"), "tmp", 8),
+            (r.join("src"), "main.rs", 16),
+        ];
+        let mut charged = Vec::new();
+        for (parent, name, size) in &files {
+            let entry = FileEntry {
+                logical: *size,
+                physical: *size * 2,
+                ..FileEntry::new(parent, name.as_ref(), EntryKind::File)
+            };
+            let rule = stats.rule_for_file(&entry);
+            if let Some(i) = rule {
+                stats.charge(i, &entry);
+            }
+            charged.push(rule);
+        }
+        assert_eq!(charged, [Some(1), Some(1), Some(0), Some(2), None]);
+
+        let report: Vec<_> = stats
+            .report()
+            .into_iter()
+            .map(|u| (u.kind, u.pattern, u.files, u.dirs, u.logical, u.physical))
+            .collect();
+        assert_eq!(
+            report,
+            [
+                (ExcludeKind::Glob, "**/*.log".into(), 1, 0, 4, 8),
+                (ExcludeKind::Regex, "^cache$".into(), 2, 1, 3, 6),
+                (ExcludeKind::Substring, "tmp".into(), 1, 0, 8, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn without_patterns_nothing_is_excluded() {
+        let stats = ExcludeStats::new();
+        let entry = FileEntry::new(Path::new("/r"), "a".as_ref(), EntryKind::File);
+        assert_eq!(stats.rule_for_file(&entry), None);
+        let mut opt = Options::default();
+        opt.exclude_contains.clear();
+        stats.start(&opt);
+        assert_eq!(stats.exclude_dir(Path::new("/r/d")), None);
+        assert_eq!(stats.rule_for_file(&entry), None);
+        assert!(stats.report().is_empty());
+    }
+}
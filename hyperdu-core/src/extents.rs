@@ -0,0 +1,250 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use ahash::AHashMap as HashMap;
+use dashmap::DashMap;
+use serde::Serialize;
+
+use crate::{EntryKind, FileEntry, FileVisitor};
+
+/// Extent-level usage of a directory subtree.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ExtentStats {
+    /// Bytes in extents no other file references; freed when deleted.
+    pub exclusive: u64,
+    /// Bytes in shared (reflinked, deduplicated, snapshotted) extents, summed per
+    /// file, so an extent referenced twice in the subtree counts twice.
+    pub shared: u64,
+    /// Distinct shared extents whose references inside the scan all lie in this
+    /// subtree, each counted once. Freed as well unless something outside the
+    /// scan (another tree, a snapshot) still references them.
+    pub shared_unique: u64,
+}
+
+impl ExtentStats {
+    pub fn merge(&mut self, other: &ExtentStats) {
+        self.exclusive += other.exclusive;
+        self.shared += other.shared;
+        self.shared_unique += other.shared_unique;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ExtentFile {
+    pub shared: u64,
+    pub exclusive: u64,
+    pub path: PathBuf,
+}
+
+/// Extent breakdown of a scan.
+#[derive(Clone, Debug, Default)]
+pub struct ExtentReport {
+    /// Files with the most shared bytes, largest first.
+    pub top_shared: Vec<ExtentFile>,
+    /// Rolled-up extent stats per directory.
+    pub per_dir: HashMap<PathBuf, ExtentStats>,
+}
+
+#[derive(Default)]
+struct SharedExtent {
+    length: u64,
+    // Directories referencing the extent (consecutive repeats collapsed)
+    dirs: Vec<PathBuf>,
+}
+
+/// Visitor that maps every counted file with FIEMAP and splits its bytes into
+/// exclusive and shared extents.
+///
+/// Opens each file, so it is much slower than a plain scan; pair it with
+/// `Options::extent_accounting` so CoW filesystems keep physical sizes. Works on
+/// any Linux filesystem with FIEMAP support (btrfs, XFS, ext4, ...). Files that
+/// cannot be mapped, and every file on other platforms, count their physical
+/// size as exclusive.
+pub struct ExtentVisitor {
+    top: usize,
+    dirs: DashMap<PathBuf, ExtentStats>,
+    // Shared extents keyed by (dev, physical offset)
+    shared: DashMap<(u64, u64), SharedExtent>,
+    top_shared: Mutex<BinaryHeap<Reverse<ExtentFile>>>,
+}
+
+impl ExtentVisitor {
+    /// Keep the `top` files with the most shared bytes.
+    pub fn new(top: usize) -> Self {
+        Self {
+            top,
+            dirs: DashMap::new(),
+            shared: DashMap::new(),
+            top_shared: Mutex::new(BinaryHeap::new()),
+        }
+    }
+
+    /// Account one file from its extents as `(physical offset, length, shared)`.
+    pub(crate) fn add_extents(
+        &self,
+        entry: &FileEntry<'_>,
+        extents: impl IntoIterator<Item = (u64, u64, bool)>,
+    ) {
+        let mut s = ExtentStats::default();
+        for (phys, len, shared) in extents {
+            if !shared {
+                s.exclusive += len;
+                continue;
+            }
+            s.shared += len;
+            let mut e = self.shared.entry((entry.dev, phys)).or_default();
+            e.length = e.length.max(len);
+            if e.dirs.last().map(PathBuf::as_path) != Some(entry.parent) {
+                e.dirs.push(entry.parent.to_path_buf());
+            }
+        }
+        if s.shared > 0 {
+            self.note_shared(entry, &s);
+        }
+        if let Some(mut d) = self.dirs.get_mut(entry.parent) {
+            d.merge(&s);
+            return;
+        }
+        self.dirs
+            .entry(entry.parent.to_path_buf())
+            .or_default()
+            .merge(&s);
+    }
+
+    fn note_shared(&self, entry: &FileEntry<'_>, s: &ExtentStats) {
+        if self.top == 0 {
+            return;
+        }
+        let mut heap = self.top_shared.lock().unwrap();
+        if heap.len() >= self.top && heap.peek().is_some_and(|m| s.shared <= m.0.shared) {
+            return;
+        }
+        heap.push(Reverse(ExtentFile {
+            shared: s.shared,
+            exclusive: s.exclusive,
+            path: entry.path(),
+        }));
+        if heap.len() > self.top {
+            heap.pop();
+        }
+    }
+
+    pub fn report(&self, root: &Path) -> ExtentReport {
+        let mut per_dir: HashMap<PathBuf, ExtentStats> = self
+            .dirs
+            .iter()
+            .map(|e| (e.key().clone(), *e.value()))
+            .collect();
+        // Each shared extent counts once, at the deepest directory covering all
+        // of its references
+        for e in self.shared.iter() {
+            let x = e.value();
+            let mut common = x.dirs[0].clone();
+            for d in &x.dirs[1..] {
+                while !d.starts_with(&common) && common.pop() {}
+            }
+            per_dir.entry(common).or_default().shared_unique += x.length;
+        }
+        crate::rollup::rollup_to_root(&mut per_dir, root, ExtentStats::merge);
+        let mut top_shared: Vec<ExtentFile> = self
+            .top_shared
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.0.clone())
+            .collect();
+        top_shared.sort_unstable_by(|a, b| b.cmp(a));
+        ExtentReport {
+            top_shared,
+            per_dir,
+        }
+    }
+}
+
+impl FileVisitor for ExtentVisitor {
+    fn visit(&self, entry: &FileEntry<'_>) {
+        if entry.kind != EntryKind::File {
+            return;
+        }
+        let mut extents = Vec::new();
+        if entry.physical > 0 && map_extents(&entry.path(), &mut extents).is_ok() {
+            self.add_extents(entry, extents);
+        } else {
+            self.add_extents(entry, [(0, entry.physical, false)]);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn map_extents(path: &Path, out: &mut Vec<(u64, u64, bool)>) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // <linux/fiemap.h>
+    const FS_IOC_FIEMAP: u32 = 0xC020_660B;
+    const FIEMAP_FLAG_SYNC: u32 = 0x1;
+    const FIEMAP_EXTENT_LAST: u32 = 0x1;
+    const FIEMAP_EXTENT_UNKNOWN: u32 = 0x2;
+    const FIEMAP_EXTENT_DATA_INLINE: u32 = 0x200;
+    const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+    const BATCH: usize = 64;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Extent {
+        fe_logical: u64,
+        fe_physical: u64,
+        fe_length: u64,
+        fe_reserved64: [u64; 2],
+        fe_flags: u32,
+        fe_reserved: [u32; 3],
+    }
+    #[repr(C)]
+    struct Fiemap {
+        fm_start: u64,
+        fm_length: u64,
+        fm_flags: u32,
+        fm_mapped_extents: u32,
+        fm_extent_count: u32,
+        fm_reserved: u32,
+        fm_extents: [Extent; BATCH],
+    }
+
+    let file = std::fs::File::open(path)?;
+    let mut req: Fiemap = unsafe { std::mem::zeroed() };
+    let mut start = 0u64;
+    loop {
+        req.fm_start = start;
+        req.fm_length = u64::MAX;
+        req.fm_flags = FIEMAP_FLAG_SYNC;
+        req.fm_mapped_extents = 0;
+        req.fm_extent_count = BATCH as u32;
+        let rc = unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FIEMAP as _, &mut req) };
+        if rc < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let n = req.fm_mapped_extents as usize;
+        if n == 0 {
+            return Ok(());
+        }
+        for e in &req.fm_extents[..n] {
+            // Extents without a usable address cannot be matched across files
+            let addressable = e.fe_flags & (FIEMAP_EXTENT_UNKNOWN | FIEMAP_EXTENT_DATA_INLINE) == 0;
+            let shared = addressable && e.fe_flags & FIEMAP_EXTENT_SHARED != 0;
+            out.push((e.fe_physical, e.fe_length, shared));
+            if e.fe_flags & FIEMAP_EXTENT_LAST != 0 {
+                return Ok(());
+            }
+        }
+        let last = &req.fm_extents[n - 1];
+        start = last.fe_logical + last.fe_length;
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn map_extents(_path: &Path, _out: &mut Vec<(u64, u64, bool)>) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
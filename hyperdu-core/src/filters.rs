@@ -17,6 +17,18 @@ pub fn path_excluded(p: &Path, opt: &Options) -> bool {
     should_exclude_legacy(p, &opt.exclude_contains)
 }
 
+/// Whether a file passes the include globs/regexes; true when none are set.
+#[inline]
+pub fn path_included(p: &Path, opt: &Options) -> bool {
+    let glob = opt.include_glob_set.as_ref();
+    let regex = opt.include_regex_set.as_ref();
+    if glob.is_none() && regex.is_none() {
+        return true;
+    }
+    glob.is_some_and(|gs| gs.is_match(p))
+        || regex.is_some_and(|rs| rs.is_match(p.to_string_lossy().as_ref()))
+}
+
 #[inline(always)]
 fn should_exclude_legacy(p: &Path, patterns: &[String]) -> bool {
     if patterns.is_empty() {
@@ -66,20 +66,24 @@ impl FileSystemStrategy for BtrfsStrategy {
         "btrfs"
     }
     fn apply(&self, opt: &mut Options, report: &mut Vec<String>) -> FsApplyOutcome {
-        // On CoW/comp-possible FS, logical size is often cheaper; keep physical but avoid aggressive blocks path
-        // Switch to logical-only by default for better responsiveness
-        opt.compute_physical = false;
-        report.push("compute_physical=false".into());
+        // On CoW/comp-possible FS, physical double-counts reflinked/snapshotted extents.
+        // Switch to logical-only by default, unless FIEMAP extent accounting resolves sharing
+        let logical_only = !opt.extent_accounting;
+        if logical_only {
+            opt.compute_physical = false;
+            report.push("compute_physical=false".into());
+        } else {
+            report.push("compute_physical kept (extent accounting)".into());
+        }
         std::env::set_var("HYPERDU_GETDENTS_BUF_KB", "128");
         report.push("getdents_buf_kb=128".into());
         // Do not enable prefetch by default
         std::env::set_var("HYPERDU_PREFETCH", "0");
         report.push("prefetch=0".into());
-        let _ = opt; // placeholder for future
         FsApplyOutcome {
             recommended_threads: None,
             disable_uring: false,
-            recommend_logical_only: true,
+            recommend_logical_only: logical_only,
         }
     }
 }
@@ -155,43 +159,27 @@ fn fs_type_for_path_linux(p: &Path) -> Option<String> {
     let files = ["/proc/self/mountinfo", "/proc/mounts", "/etc/mtab"]; // best-effort
     for m in files {
         if let Ok(text) = fs::read_to_string(m) {
+            if m.ends_with("mountinfo") {
+                let table = crate::mounts::MountTable::parse_mountinfo(&text);
+                match table.mount_for(&path) {
+                    Some(mount) => return Some(mount.fs_type.clone()),
+                    None => continue,
+                }
+            }
             let mut best: Option<(usize, String)> = None; // (match_len, fstype)
             for line in text.lines() {
                 if line.is_empty() || line.starts_with('#') {
                     continue;
                 }
-                // mountinfo: many fields, mount point is 5th (post-root fields), fstype later; mounts: src mp fstype ...
-                if m.ends_with("mountinfo") {
-                    // format: ID parent major:minor root mount point options - fstype src opts
-                    // Split by ' - ' then take second part's first token as fstype
-                    if let Some(idx) = line.find(" - ") {
-                        let (pre, post) = line.split_at(idx);
-                        let pre_parts: Vec<&str> = pre.split_whitespace().collect();
-                        if pre_parts.len() >= 5 {
-                            let mp = pre_parts[4];
-                            if path.to_string_lossy().starts_with(mp) {
-                                let post_parts: Vec<&str> = post[3..].split_whitespace().collect();
-                                if !post_parts.is_empty() {
-                                    let fs = post_parts[0].to_string();
-                                    let l = mp.len();
-                                    if best.as_ref().map(|(bl, _)| l > *bl).unwrap_or(true) {
-                                        best = Some((l, fs));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    // mounts/mtab: src mp fstype ...
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 3 {
-                        let mp = parts[1];
-                        let fs = parts[2];
-                        if path.to_string_lossy().starts_with(mp) {
-                            let l = mp.len();
-                            if best.as_ref().map(|(bl, _)| l > *bl).unwrap_or(true) {
-                                best = Some((l, fs.to_string()));
-                            }
+                // mounts/mtab: src mp fstype ...
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 3 {
+                    let mp = parts[1];
+                    let fs = parts[2];
+                    if path.to_string_lossy().starts_with(mp) {
+                        let l = mp.len();
+                        if best.as_ref().map(|(bl, _)| l > *bl).unwrap_or(true) {
+                            best = Some((l, fs.to_string()));
                         }
                     }
                 }
@@ -0,0 +1,267 @@
+use std::path::{Path, PathBuf};
+
+use ahash::AHashMap as HashMap;
+use dashmap::DashMap;
+use serde::Serialize;
+
+use crate::{tree::DirArena, EntryKind, FileEntry, FileVisitor, StatMap};
+
+/// One inode with several links, as seen by a scan.
+#[derive(Clone, Debug, Serialize)]
+pub struct HardlinkGroup {
+    pub dev: u64,
+    pub ino: u64,
+    /// Link count reported by the filesystem; larger than `paths.len()` when
+    /// some links live outside the scanned tree.
+    pub nlink: u64,
+    pub logical: u64,
+    pub physical: u64,
+    /// Links found by the scan, sorted.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Hardlink groups of a scan, largest physical size first.
+#[derive(Clone, Debug, Default)]
+pub struct HardlinkReport {
+    pub groups: Vec<HardlinkGroup>,
+}
+
+impl HardlinkReport {
+    /// Physical bytes that extra links would add if every link were counted.
+    pub fn shared_bytes(&self) -> u64 {
+        self.groups
+            .iter()
+            .map(|g| g.physical * (g.paths.len() as u64).saturating_sub(1))
+            .sum()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Delta {
+    logical: i64,
+    physical: i64,
+}
+
+impl Delta {
+    fn merge(&mut self, other: &Delta) {
+        self.logical += other.logical;
+        self.physical += other.physical;
+    }
+}
+
+#[derive(Default)]
+struct Links {
+    nlink: u64,
+    logical: u64,
+    physical: u64,
+    // Each link with whether the scan counted its bytes
+    paths: Vec<(PathBuf, bool)>,
+}
+
+/// Visitor that groups files with more than one link by `(dev, ino)`.
+///
+/// Works with and without hardlink dedupe: counted links arrive via `visit`,
+/// links dropped by the dedupe via `visit_link`. Backends that do not report a
+/// link count (Windows, approximate sizes) produce no groups.
+#[derive(Default)]
+pub struct HardlinkVisitor {
+    groups: DashMap<(u64, u64), Links>,
+}
+
+impl HardlinkVisitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&self, entry: &FileEntry<'_>, counted: bool) {
+        if entry.kind != EntryKind::File || entry.nlink < 2 {
+            return;
+        }
+        let mut g = self.groups.entry((entry.dev, entry.ino)).or_default();
+        g.nlink = entry.nlink;
+        g.logical = entry.logical;
+        g.physical = entry.physical;
+        g.paths.push((entry.path(), counted));
+    }
+
+    pub fn report(&self) -> HardlinkReport {
+        let mut groups: Vec<HardlinkGroup> = self
+            .groups
+            .iter()
+            .map(|e| {
+                let (&(dev, ino), l) = (e.key(), e.value());
+                let mut paths: Vec<PathBuf> = l.paths.iter().map(|(p, _)| p.clone()).collect();
+                paths.sort_unstable();
+                HardlinkGroup {
+                    dev,
+                    ino,
+                    nlink: l.nlink,
+                    logical: l.logical,
+                    physical: l.physical,
+                    paths,
+                }
+            })
+            .collect();
+        groups.sort_unstable_by(|a, b| {
+            b.physical
+                .cmp(&a.physical)
+                .then_with(|| a.paths.cmp(&b.paths))
+        });
+        HardlinkReport { groups }
+    }
+
+    /// Re-attribute shared bytes in a rolled-up `map` (as returned by
+    /// `scan_directory`): each group's size is counted once and split evenly
+    /// across the directories of its links under `root`. File counts are left
+    /// unchanged.
+    pub fn apply_split(&self, root: &Path, map: &mut StatMap) {
+        let mut deltas: HashMap<PathBuf, Delta> = HashMap::default();
+        for g in self.groups.iter() {
+            let mut links: Vec<&(PathBuf, bool)> = g.paths.iter().collect();
+            links.sort_unstable();
+            let n = links.len() as u64;
+            for (i, (path, counted)) in links.into_iter().enumerate() {
+                // Spread the remainder over the first links so the shares add up
+                let share = |total: u64| (total / n + u64::from((i as u64) < total % n)) as i64;
+                let mut d = Delta {
+                    logical: share(g.logical),
+                    physical: share(g.physical),
+                };
+                if *counted {
+                    d.logical -= g.logical as i64;
+                    d.physical -= g.physical as i64;
+                }
+                let Some(parent) = path.parent() else {
+                    continue;
+                };
+                deltas.entry(parent.to_path_buf()).or_default().merge(&d);
+            }
+        }
+        crate::rollup::rollup_to_root(&mut deltas, root, Delta::merge);
+        for (p, s) in map.iter_mut() {
+            let Some(d) = deltas.get(p) else {
+                continue;
+            };
+            s.logical = s.logical.saturating_add_signed(d.logical);
+            s.physical = s.physical.saturating_add_signed(d.physical);
+        }
+    }
+}
+
+/// Multiply-linked inodes seen by a scan, used to credit each one to
+/// `Stat::exclusive` of the smallest directory that holds all of its links.
+#[derive(Default)]
+pub(crate) struct LinkTable {
+    inodes: DashMap<(u64, u64), SharedInode>,
+    // Scans of several roots: roots in command-line order, and the directory
+    // each deduped inode was counted in with its logical size
+    roots: Vec<PathBuf>,
+    counted: Option<DashMap<(u64, u64), (PathBuf, u64)>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SharedInode {
+    pub(crate) nlink: u64,
+    pub(crate) physical: u64,
+    pub(crate) dirs: Vec<PathBuf>,
+}
+
+impl LinkTable {
+    pub(crate) fn new(roots: &[PathBuf]) -> Self {
+        let multi = roots.len() > 1;
+        Self {
+            inodes: DashMap::new(),
+            roots: if multi { roots.to_vec() } else { Vec::new() },
+            counted: multi.then(DashMap::new),
+        }
+    }
+
+    /// Note one link; `counted` when hardlink dedupe charged its bytes here.
+    pub(crate) fn add(&self, entry: &FileEntry<'_>, counted: bool) {
+        let key = (entry.dev, entry.ino);
+        {
+            let mut s = self.inodes.entry(key).or_default();
+            s.nlink = entry.nlink;
+            s.physical = entry.physical;
+            s.dirs.push(entry.parent.to_path_buf());
+        }
+        if let Some(c) = self.counted.as_ref().filter(|_| counted) {
+            c.insert(key, (entry.parent.to_path_buf(), entry.logical));
+        }
+    }
+
+    /// Copy of the table, keyed by `(dev, ino)` (for checkpoints).
+    pub(crate) fn entries(&self) -> Vec<((u64, u64), SharedInode)> {
+        self.inodes
+            .iter()
+            .map(|e| (*e.key(), e.value().clone()))
+            .collect()
+    }
+
+    /// Refill from [`LinkTable::entries`] of an earlier run.
+    pub(crate) fn restore(&self, entries: &[((u64, u64), SharedInode)]) {
+        for (key, s) in entries {
+            self.inodes.insert(*key, s.clone());
+        }
+    }
+
+    /// Move each inode counted under a later root than another of its links to
+    /// that link's directory in the earliest root, as GNU du reading the roots
+    /// one after another would, so attribution does not depend on which worker
+    /// got there first. `dirs` holds per-directory (not rolled up) stats.
+    pub(crate) fn attribute_to_first_root(&self, dirs: &mut DirArena) {
+        let Some(counted) = &self.counted else {
+            return;
+        };
+        let rank = |d: &Path| {
+            self.roots
+                .iter()
+                .position(|r| d.starts_with(r))
+                .unwrap_or(usize::MAX)
+        };
+        for e in counted.iter() {
+            let (from, logical) = e.value();
+            let Some(s) = self.inodes.get(e.key()) else {
+                continue;
+            };
+            let Some(to) = s.dirs.iter().min_by_key(|d| (rank(d), *d)) else {
+                continue;
+            };
+            if rank(to) >= rank(from) {
+                continue;
+            }
+            let st = dirs.stat_mut(from);
+            st.files = st.files.saturating_sub(1);
+            st.logical = st.logical.saturating_sub(*logical);
+            st.physical = st.physical.saturating_sub(s.physical);
+            crate::common_ops::update_file_stats(dirs.stat_mut(to), *logical, s.physical);
+        }
+    }
+
+    /// Add each inode whose links were all seen to its common directory in the
+    /// per-directory (not yet rolled up) `dirs`. Inodes with links outside the
+    /// scan are never exclusive.
+    pub(crate) fn resolve(&self, dirs: &mut DirArena) {
+        for e in self.inodes.iter() {
+            let s = e.value();
+            if (s.dirs.len() as u64) < s.nlink {
+                continue;
+            }
+            let mut common = s.dirs[0].clone();
+            for d in &s.dirs[1..] {
+                while !d.starts_with(&common) && common.pop() {}
+            }
+            dirs.stat_mut(&common).exclusive += s.physical;
+        }
+    }
+}
+
+impl FileVisitor for HardlinkVisitor {
+    fn visit(&self, entry: &FileEntry<'_>) {
+        self.add(entry, true);
+    }
+
+    fn visit_link(&self, entry: &FileEntry<'_>) {
+        self.add(entry, false);
+    }
+}
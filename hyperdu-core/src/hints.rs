@@ -0,0 +1,164 @@
+//! Largest-first traversal from the sizes of an earlier scan.
+//!
+//! With `Options::size_hints` set, newly found directories are queued by their
+//! previous subtree file count (in power-of-two classes) instead of in
+//! discovery order, so the heaviest subtrees start early: the tail of the scan
+//! is not dominated by one big directory found late, and partial results show
+//! the big ones first. Directories without a hint (new since the earlier scan)
+//! or without files go last.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ahash::AHashMap as HashMap;
+use anyhow::{Context, Result};
+use crossbeam_deque::{Injector, Steal};
+use serde::Deserialize;
+
+use crate::{Job, StatMap};
+
+/// Previous size of one subtree. Orders by file count, then bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hint {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Previous subtree sizes by directory path (as spelled in the earlier scan).
+#[derive(Clone, Debug, Default)]
+pub struct SizeHints {
+    dirs: HashMap<PathBuf, Hint>,
+}
+
+impl SizeHints {
+    /// Hints from the rolled-up stats of an earlier scan (`scan_directory`).
+    pub fn from_stats(map: &StatMap) -> Self {
+        let dirs = map
+            .iter()
+            .map(|(p, s)| {
+                let hint = Hint {
+                    files: s.files,
+                    bytes: s.physical,
+                };
+                (p.clone(), hint)
+            })
+            .collect();
+        Self { dirs }
+    }
+
+    /// Hints from a JSON report written by `hyperdu-cli --json`.
+    pub fn from_report(path: &Path) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Row {
+            path: PathBuf,
+            files: u64,
+            physical: u64,
+        }
+        // Plain --json reports are bare rows; --json-errors wraps them next to the error list
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Report {
+            Object { entries: Vec<Row> },
+            Rows(Vec<Row>),
+        }
+        let data = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        let report: Report =
+            serde_json::from_slice(&data).with_context(|| format!("parse {}", path.display()))?;
+        let (Report::Object { entries: rows } | Report::Rows(rows)) = report;
+        let dirs = rows
+            .into_iter()
+            .map(|r| {
+                let hint = Hint {
+                    files: r.files,
+                    bytes: r.physical,
+                };
+                (r.path, hint)
+            })
+            .collect();
+        Ok(Self { dirs })
+    }
+
+    /// Count a file of `bytes` into every directory from its parent up to `root`.
+    pub(crate) fn add_file(&mut self, root: &Path, file: &Path, bytes: u64) {
+        for dir in file.ancestors().skip(1) {
+            if !dir.starts_with(root) {
+                break;
+            }
+            let h = self.dirs.entry(dir.to_path_buf()).or_default();
+            h.files += 1;
+            h.bytes += bytes;
+        }
+    }
+
+    /// Add the hints of another root.
+    pub fn extend(&mut self, other: SizeHints) {
+        self.dirs.extend(other.dirs);
+    }
+
+    pub fn get(&self, dir: &Path) -> Option<Hint> {
+        self.dirs.get(dir).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.dirs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+}
+
+// Weight classes: class n holds subtrees of 2^(n-1) to 2^n - 1 files; the top
+// one also takes everything larger.
+const CLASSES: usize = 40;
+
+/// Shared queues of fresh directory jobs, one lock-free injector per weight
+/// class, heaviest class first and discovery order within a class. Workers
+/// take from it after the high-priority injector and before the normal one.
+pub(crate) struct HintQueue {
+    hints: Arc<SizeHints>,
+    classes: Box<[Injector<Job>]>,
+}
+
+impl HintQueue {
+    pub(crate) fn new(hints: Arc<SizeHints>) -> Self {
+        Self {
+            hints,
+            classes: (0..CLASSES).map(|_| Injector::new()).collect(),
+        }
+    }
+
+    /// Queue `job` by its previous file count. Directories that held no files
+    /// or are not in the earlier scan go to `unranked` (the normal injector).
+    pub(crate) fn push(&self, job: Job, unranked: &Injector<Job>) {
+        let files = self.hints.get(&job.dir).map_or(0, |h| h.files);
+        let class = (u64::BITS - files.leading_zeros()) as usize;
+        match class {
+            0 => unranked.push(job),
+            c => self.classes[c.min(CLASSES - 1)].push(job),
+        }
+    }
+
+    pub(crate) fn pop(&self) -> Option<Job> {
+        for q in self.classes.iter().rev() {
+            loop {
+                match q.steal() {
+                    Steal::Success(job) => return Some(job),
+                    Steal::Empty => break,
+                    Steal::Retry => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// Remove every queued job.
+    pub(crate) fn drain(&self) -> Vec<Job> {
+        self.classes
+            .iter()
+            .flat_map(crate::drain_injector)
+            .collect()
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use ahash::AHashMap as HashMap;
+use dashmap::DashMap;
+
+use crate::{EntryKind, FileEntry, FileVisitor};
+
+/// Number of log2 buckets. Bucket 0 holds empty files, bucket `i` holds sizes in
+/// `[2^(i-1), 2^i)` and the last bucket everything from 512 GiB up.
+pub const HIST_BUCKETS: usize = 41;
+
+/// Log2 histogram of file sizes (bucketed by logical size).
+#[derive(Clone, Copy, Debug)]
+pub struct SizeHistogram {
+    /// Files per bucket.
+    pub files: [u64; HIST_BUCKETS],
+    /// Physical bytes per bucket.
+    pub bytes: [u64; HIST_BUCKETS],
+}
+
+impl Default for SizeHistogram {
+    fn default() -> Self {
+        Self {
+            files: [0; HIST_BUCKETS],
+            bytes: [0; HIST_BUCKETS],
+        }
+    }
+}
+
+impl SizeHistogram {
+    #[inline]
+    pub fn bucket_of(size: u64) -> usize {
+        ((u64::BITS - size.leading_zeros()) as usize).min(HIST_BUCKETS - 1)
+    }
+
+    /// Inclusive lower bound of a bucket; `None` as upper bound for the last one.
+    pub fn bucket_range(i: usize) -> (u64, Option<u64>) {
+        let lo = if i == 0 { 0 } else { 1u64 << (i - 1) };
+        let hi = (i + 1 < HIST_BUCKETS).then(|| 1u64 << i);
+        (lo, hi)
+    }
+
+    #[inline]
+    pub fn add(&mut self, logical: u64, physical: u64) {
+        let b = Self::bucket_of(logical);
+        self.files[b] += 1;
+        self.bytes[b] += physical;
+    }
+
+    pub fn merge(&mut self, other: &SizeHistogram) {
+        for i in 0..HIST_BUCKETS {
+            self.files[i] += other.files[i];
+            self.bytes[i] += other.bytes[i];
+        }
+    }
+
+    pub fn total_files(&self) -> u64 {
+        self.files.iter().sum()
+    }
+
+    /// Non-empty buckets as `(index, files, bytes)`.
+    pub fn nonzero(&self) -> impl Iterator<Item = (usize, u64, u64)> + '_ {
+        (0..HIST_BUCKETS)
+            .filter(|&i| self.files[i] > 0)
+            .map(|i| (i, self.files[i], self.bytes[i]))
+    }
+}
+
+/// Visitor that builds a size histogram per directory.
+///
+/// Attach it like [`crate::owner::OwnerVisitor`] and call
+/// [`HistogramVisitor::report`] for histograms rolled up to `root`. Safe to call
+/// while the scan is still running for a partial view.
+#[derive(Default)]
+pub struct HistogramVisitor {
+    dirs: DashMap<PathBuf, SizeHistogram>,
+}
+
+impl HistogramVisitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&self, root: &Path) -> HashMap<PathBuf, SizeHistogram> {
+        let mut per_dir: HashMap<PathBuf, SizeHistogram> = self
+            .dirs
+            .iter()
+            .map(|e| (e.key().clone(), *e.value()))
+            .collect();
+        crate::rollup::rollup_to_root(&mut per_dir, root, SizeHistogram::merge);
+        per_dir
+    }
+}
+
+impl FileVisitor for HistogramVisitor {
+    fn visit(&self, entry: &FileEntry<'_>) {
+        if entry.kind != EntryKind::File {
+            return;
+        }
+        if let Some(mut h) = self.dirs.get_mut(entry.parent) {
+            h.add(entry.logical, entry.physical);
+            return;
+        }
+        self.dirs
+            .entry(entry.parent.to_path_buf())
+            .or_default()
+            .add(entry.logical, entry.physical);
+    }
+}
@@ -0,0 +1,167 @@
+//! `.gitignore` / `.ignore` / `.duignore` aware filtering.
+//!
+//! Each directory's ignore files are read once, when the first entry below it
+//! is checked, and chained to the parent directory's rules. Matching follows
+//! gitignore: the last matching line wins, deeper files override shallower
+//! ones, `.ignore` overrides `.gitignore` and `.duignore` overrides both in the
+//! same directory, and nothing below an ignored directory can be re-included.
+//! Rules above a scan root are loaded up to the enclosing repository (the
+//! nearest ancestor with a `.git`). `.git` directories themselves always count
+//! as ignored.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use dashmap::DashMap;
+use globset::{GlobBuilder, GlobMatcher};
+
+/// Ignore files read in every directory, lowest precedence first.
+pub const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".duignore"];
+
+struct Rule {
+    glob: GlobMatcher,
+    negate: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    /// One ignore-file line; `None` for blanks, comments and bad globs.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // Trailing spaces are dropped unless escaped
+        let mut pat = line.trim_end_matches(' ');
+        if pat.ends_with('\\') && line.len() > pat.len() {
+            pat = &line[..pat.len() + 1];
+        }
+        if pat.is_empty() || pat.starts_with('#') {
+            return None;
+        }
+        let (negate, pat) = match pat.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pat.strip_prefix('\\').unwrap_or(pat)),
+        };
+        let (dir_only, pat) = match pat.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pat),
+        };
+        if pat.is_empty() {
+            return None;
+        }
+        // A slash anywhere but the end anchors the pattern to the file's directory
+        let glob = match pat.strip_prefix('/') {
+            Some(rest) => rest.to_string(),
+            None if pat.contains('/') => pat.to_string(),
+            None => format!("**/{pat}"),
+        };
+        let glob = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .backslash_escape(true)
+            .build()
+            .ok()?
+            .compile_matcher();
+        Some(Self {
+            glob,
+            negate,
+            dir_only,
+        })
+    }
+}
+
+/// Rules of one directory, linked to those of its parent.
+struct Node {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+    parent: Option<Arc<Node>>,
+    /// The directory itself is ignored (or lies below an ignored one).
+    ignored: bool,
+}
+
+impl Node {
+    /// Verdict of the deepest rule matching `path`: `Some(true)` = ignored,
+    /// `Some(false)` = re-included, `None` = no rule matched.
+    fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let mut node = Some(self);
+        while let Some(n) = node {
+            if let Ok(rel) = path.strip_prefix(&n.dir) {
+                let hit = n
+                    .rules
+                    .iter()
+                    .rev()
+                    .find(|r| (is_dir || !r.dir_only) && r.glob.is_match(rel));
+                if let Some(r) = hit {
+                    return Some(!r.negate);
+                }
+            }
+            node = n.parent.as_deref();
+        }
+        None
+    }
+}
+
+/// Per-scan cache of the ignore rules of every directory seen.
+pub struct IgnoreRules {
+    /// Outermost directories whose rules apply (repository roots or scan roots).
+    tops: Vec<PathBuf>,
+    nodes: DashMap<PathBuf, Arc<Node>>,
+}
+
+impl IgnoreRules {
+    /// Rules for a scan of `roots`.
+    pub fn new(roots: &[PathBuf]) -> Self {
+        let tops = roots
+            .iter()
+            .map(|r| {
+                r.ancestors()
+                    .find(|a| a.join(".git").exists())
+                    .unwrap_or(r)
+                    .to_path_buf()
+            })
+            .collect();
+        Self {
+            tops,
+            nodes: DashMap::new(),
+        }
+    }
+
+    /// Whether `path` (a directory if `is_dir`) is ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir && path.file_name() == Some(".git".as_ref()) {
+            return true;
+        }
+        let Some(parent) = path.parent() else {
+            return false;
+        };
+        let node = self.node(parent);
+        node.ignored || node.matches(path, is_dir) == Some(true)
+    }
+
+    fn node(&self, dir: &Path) -> Arc<Node> {
+        if let Some(n) = self.nodes.get(dir) {
+            return n.clone();
+        }
+        let parent = dir
+            .parent()
+            .filter(|p| {
+                !self.tops.iter().any(|t| t == dir) && self.tops.iter().any(|t| p.starts_with(t))
+            })
+            .map(|p| self.node(p));
+        let ignored = parent
+            .as_ref()
+            .is_some_and(|p| p.ignored || p.matches(dir, true) == Some(true))
+            || dir.file_name() == Some(".git".as_ref());
+        let rules = IGNORE_FILES
+            .iter()
+            .filter_map(|f| std::fs::read_to_string(dir.join(f)).ok())
+            .flat_map(|text| text.lines().filter_map(Rule::parse).collect::<Vec<_>>())
+            .collect();
+        let node = Arc::new(Node {
+            dir: dir.to_path_buf(),
+            rules,
+            parent,
+            ignored,
+        });
+        self.nodes.entry(dir.to_path_buf()).or_insert(node).clone()
+    }
+}
@@ -7,7 +7,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use sled::IVec;
 
-use crate::{filters::path_excluded, Options};
+use crate::{filters::path_excluded, hints::SizeHints, Options};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathSnapshot {
@@ -153,6 +153,19 @@ pub fn compute_delta(db: &sled::Db, root: &Path, opt: &Options) -> Result<DeltaS
     Ok(delta)
 }
 
+/// Subtree file counts and sizes under `root` as of the last snapshot, for
+/// largest-first scheduling (`Options::size_hints`).
+pub fn size_hints(db: &sled::Db, root: &Path) -> Result<SizeHints> {
+    let mut hints = SizeHints::default();
+    for kv in db.scan_prefix(encode_key(root)) {
+        let (_, v) = kv?;
+        if let Ok(snap) = serde_json::from_slice::<PathSnapshot>(&v) {
+            hints.add_file(root, &snap.path, snap.size);
+        }
+    }
+    Ok(hints)
+}
+
 pub fn snapshot_prune_removed(db: &sled::Db, root: &Path) -> Result<u64> {
     let mut removed = 0u64;
     let prefix = root.to_string_lossy().as_bytes().to_vec();
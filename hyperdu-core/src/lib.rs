@@ -19,28 +19,51 @@ use globset::{Glob, GlobSet, GlobSetBuilder};
 use regex::RegexSet;
 use serde::Serialize;
 
+pub mod age;
+pub mod checkpoint;
 pub mod classify;
 mod common_ops;
 mod error_handling;
+pub mod estimate;
+pub mod exclusions;
+pub mod extents;
 mod filters; // centralize filter helpers
 pub mod fs_strategy;
+pub mod hardlink;
+pub mod hints;
+pub mod histogram;
+pub mod ignore;
 pub mod incremental;
 pub mod memory_pool;
+pub mod mounts;
 mod options; // for OptionsBuilder
+pub mod owner;
 mod platform;
+pub mod predicate;
 mod rollup;
+mod scan_handle; // background scan + live snapshots
 mod scanner; // FileSystemScanner + platform default
+pub mod sparse;
+pub mod throttle;
+pub mod tree;
 mod tuning;
+pub mod visitor;
 
+pub use error_handling::{ScanErrorKind, ScanErrorRecord, MAX_ERROR_RECORDS};
 pub use options::{
     CompatConfig, FilterConfig, OptionsBuilder, OutputConfig, PerformanceConfig, TuningConfig,
     WindowsConfig,
 };
+use scan_handle::{LiveBuffer, LiveState};
+pub use scan_handle::{ScanHandle, ScanSnapshot};
 #[cfg(feature = "rayon-par")]
 pub use scanner::auto_parallel_scan;
 #[cfg(feature = "rayon-par")]
 pub use scanner::parallel_scan;
 pub use scanner::{platform_scanner, FileSystemScanner, PlatformScanner};
+use tree::DirArena;
+pub use tree::{MultiScan, NodeId, ScanTree};
+pub use visitor::{EntryKind, FileEntry, FileVisitor, VisitorSet};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CompatMode {
@@ -55,6 +78,73 @@ pub struct Stat {
     pub logical: u64,
     pub physical: u64,
     pub files: u64,
+    /// Subdirectories below this directory (the directory itself is not counted).
+    pub dirs: u64,
+    /// Symlinks seen and not followed.
+    pub symlinks: u64,
+    /// Newest file mtime in the subtree (Unix seconds, 0 = unknown).
+    pub max_mtime: i64,
+    /// Oldest file atime in the subtree (Unix seconds, 0 = unknown).
+    pub min_atime: i64,
+    /// Physical bytes deleting this directory would free: hardlinked files only
+    /// count here when every link lives below it. Files whose link count the
+    /// backend did not fetch are treated as unlinked.
+    pub exclusive: u64,
+    /// Directories in the subtree (itself included) that were not fully read:
+    /// opening or listing failed, or the scan was cancelled before reaching
+    /// them. Nonzero means the other fields are lower bounds.
+    pub incomplete: u64,
+    /// Physical bytes of files matched by ignore files, counted here instead
+    /// of in the fields above when `Options::ignore_report` is set.
+    pub ignored: u64,
+}
+
+impl Stat {
+    /// Accumulate another stat into this one (thread-map merge and rollup).
+    #[inline]
+    pub fn merge(&mut self, other: &Stat) {
+        self.logical += other.logical;
+        self.physical += other.physical;
+        self.files += other.files;
+        self.dirs += other.dirs;
+        self.symlinks += other.symlinks;
+        self.exclusive += other.exclusive;
+        self.incomplete += other.incomplete;
+        self.ignored += other.ignored;
+        self.note_times(other.max_mtime, other.min_atime);
+    }
+
+    /// Whether every directory of the subtree was read completely.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.incomplete == 0
+    }
+
+    /// Fold file timestamps into the mtime/atime bounds; 0 means unknown.
+    #[inline]
+    pub fn note_times(&mut self, mtime: i64, atime: i64) {
+        self.max_mtime = self.max_mtime.max(mtime);
+        if atime != 0 && (self.min_atime == 0 || atime < self.min_atime) {
+            self.min_atime = atime;
+        }
+    }
+
+    /// Files, directories and symlinks in the subtree (what `du --inodes`
+    /// reports, minus one for the directory itself).
+    #[inline]
+    pub fn inodes(&self) -> u64 {
+        self.files + self.dirs + self.symlinks
+    }
+}
+
+pub(crate) fn merge_stat_maps(dst: &mut StatMap, src: StatMap) {
+    if dst.is_empty() {
+        *dst = src;
+        return;
+    }
+    for (k, v) in src {
+        dst.entry(k).or_default().merge(&v);
+    }
 }
 
 #[derive(Clone)]
@@ -70,6 +160,7 @@ pub struct Options {
     pub compute_physical: bool, // if false, use logical size as physical (faster)
     pub dir_yield_every: Arc<AtomicUsize>, // 0 = no yielding; split large dirs every N entries
     pub approximate_sizes: bool, // if true and compute_physical=false, estimate regular file size (e.g., 4KiB) to avoid statx
+    pub extent_accounting: bool, // shared extents are resolved via FIEMAP (extents::ExtentVisitor); CoW strategies keep physical sizes
     pub disable_uring: bool,     // if true, force-disable io_uring backend even if compiled
     pub active_threads: Arc<AtomicUsize>, // runtime-tunable active worker threads (<= threads)
     pub uring_batch: Arc<AtomicUsize>, // dynamic batch size for io_uring statx (Linux only); default 128
@@ -85,15 +176,38 @@ pub struct Options {
     pub exclude_glob: Vec<String>,
     pub exclude_regex_set: Option<RegexSet>,
     pub exclude_glob_set: Option<GlobSet>,
+    pub exclude_stats: Option<Arc<exclusions::ExcludeStats>>, // when set, excluded paths are walked and charged to their pattern instead of pruned
+    // Include filters: when set, only matching files/symlinks are counted (full path; directories are still walked)
+    pub include_regex: Vec<String>,
+    pub include_glob: Vec<String>,
+    pub include_regex_set: Option<RegexSet>,
+    pub include_glob_set: Option<GlobSet>,
+    pub filter_expr: Vec<String>, // find-like predicates (see predicate), all must hold for a file to be counted
+    pub filter: Option<Arc<predicate::Predicate>>, // compiled filter_expr
+    pub ignore_files: bool,       // honour .gitignore/.ignore/.duignore in every directory
+    pub ignore_report: bool, // with ignore_files: walk ignored paths and sum them in Stat::ignored instead of dropping them
+    pub ignore_rules: Option<Arc<ignore::IgnoreRules>>, // per-scan rule cache, set up by the scan when ignore_files is on
     // Compatibility and correctness knobs
     pub compat_mode: CompatMode,
     pub count_hardlinks: bool, // if true, count hardlinks as separate (non-GNU). Default false = dedupe hardlinks like GNU du
     pub inode_cache: Option<Arc<DashMap<(u64, u64), ()>>>, // (dev, ino)
     pub error_count: Arc<AtomicU64>,
     pub error_report: Option<Arc<dyn Fn(&str) + Send + Sync + 'static>>, // optional error reporter
+    pub errors: Arc<std::sync::Mutex<Vec<ScanErrorRecord>>>, // structured errors of the latest scan run with these options
     pub one_file_system: bool,
+    pub count_special_files: bool, // count fifos, sockets and device nodes as files (du --inodes); Linux backends
     pub visited_bloom: Option<Arc<Bloom>>, // fast pre-check
     pub visited_dirs: Option<Arc<DashMap<(u64, u64), ()>>>, // loop detection when following links
+    pub checkpoint_path: Option<PathBuf>, // periodically save scan state here (see checkpoint)
+    pub checkpoint_every_secs: u64,
+    pub resume_from: Option<Arc<checkpoint::Checkpoint>>, // continue a checkpointed scan
+    pub budget_ms: u64, // stop after this much time and estimate the rest (0 = unlimited)
+    pub budget_files: u64, // stop after this many files and estimate the rest (0 = unlimited)
+    pub last_estimate: Arc<std::sync::Mutex<Option<estimate::SizeEstimate>>>, // set when a scan ran out of budget
+    pub io_ops_per_sec: u64, // directory reads + stat calls per second across all workers (0 = unlimited)
+    pub uring_max_inflight: usize, // cap on in-flight io_uring SQEs per worker; tuners stay below it (0 = SQ depth)
+    pub ioprio_idle: bool,         // run workers in the idle I/O priority class (Linux)
+    pub size_hints: Option<Arc<hints::SizeHints>>, // previous subtree sizes: visit the heaviest directories first
     // Keep progress lightweight: we intentionally do not accumulate sizes per-file here.
     // Adaptive tuning / scheduling preferences (configured by CLI config)
     pub tune_enabled: bool,
@@ -140,6 +254,7 @@ impl Default for Options {
                     .unwrap_or(0),
             )),
             approximate_sizes: false,
+            extent_accounting: false,
             disable_uring: std::env::var("HYPERDU_DISABLE_URING")
                 .ok()
                 .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
@@ -167,14 +282,36 @@ impl Default for Options {
             exclude_glob: Vec::new(),
             exclude_regex_set: None,
             exclude_glob_set: None,
+            exclude_stats: None,
+            include_regex: Vec::new(),
+            include_glob: Vec::new(),
+            include_regex_set: None,
+            include_glob_set: None,
+            filter_expr: Vec::new(),
+            filter: None,
+            ignore_files: false,
+            ignore_report: false,
+            ignore_rules: None,
             compat_mode: CompatMode::HyperDU,
             count_hardlinks: false,
             inode_cache: None,
             error_count: Arc::new(AtomicU64::new(0)),
             error_report: None,
+            errors: Arc::default(),
             one_file_system: false,
+            count_special_files: false,
             visited_bloom: None,
             visited_dirs: None,
+            checkpoint_path: None,
+            checkpoint_every_secs: 60,
+            resume_from: None,
+            budget_ms: 0,
+            budget_files: 0,
+            last_estimate: Arc::default(),
+            io_ops_per_sec: 0,
+            uring_max_inflight: 0,
+            ioprio_idle: false,
+            size_hints: None,
             cancel: Arc::new(AtomicBool::new(false)),
             tune_enabled: false,
             tune_interval_ms: 800,
@@ -236,6 +373,15 @@ pub struct ScanContext<'a> {
     pub(crate) high_injector: &'a Injector<Job>,
     pub(crate) normal_injector: &'a Injector<Job>,
     pub(crate) total_files: &'a std::sync::atomic::AtomicU64,
+    pub(crate) visitor: Option<&'a dyn FileVisitor>,
+    pub(crate) live: Option<&'a LiveBuffer>,
+    pub(crate) links: Option<&'a hardlink::LinkTable>,
+    pub(crate) unread: &'a std::cell::Cell<bool>,
+    /// Files of the current directory charged to the ignore or exclude
+    /// accounting instead of its stats
+    pub(crate) set_aside: &'a std::cell::Cell<u64>,
+    pub(crate) throttle: Option<&'a throttle::IoThrottle>,
+    pub(crate) hinted: Option<&'a hints::HintQueue>,
 }
 
 #[derive(Clone, Copy)]
@@ -248,15 +394,36 @@ pub struct DirContext<'a> {
 impl<'a> ScanContext<'a> {
     #[inline]
     pub fn enqueue_dir(&self, path: PathBuf, depth: u32) {
-        self.normal_injector.push(Job {
+        let excluded = self
+            .options
+            .exclude_stats
+            .as_ref()
+            .and_then(|x| x.exclude_dir(&path))
+            .is_some();
+        if let Some(rules) = &self.options.ignore_rules {
+            if !excluded && !self.options.ignore_report && rules.is_ignored(&path, true) {
+                return;
+            }
+        }
+        if let Some(live) = self.live {
+            live.adjust(&path, depth, None, 1);
+        }
+        let job = Job {
             dir: path,
             depth,
             resume: None,
-        });
+        };
+        match self.hinted {
+            Some(q) => q.push(job, self.normal_injector),
+            None => self.normal_injector.push(job),
+        }
     }
 
     #[inline]
     pub fn enqueue_resume(&self, path: PathBuf, depth: u32, resume: u64) {
+        if let Some(live) = self.live {
+            live.adjust(&path, depth, Some(resume), 1);
+        }
         self.high_injector.push(Job {
             dir: path,
             depth,
@@ -264,10 +431,139 @@ impl<'a> ScanContext<'a> {
         });
     }
 
+    /// Wait until `n` more directory reads or stat calls fit into
+    /// `Options::io_ops_per_sec`. Backends call this before each such call.
+    #[inline]
+    pub fn throttle_io(&self, n: u32) {
+        if let Some(t) = self.throttle {
+            t.acquire(n);
+        }
+    }
+
     #[inline]
     pub fn report_progress(&self, opt: &Options, path: Option<&Path>) {
         crate::common_ops::report_file_progress(opt, self.total_files, path);
     }
+
+    /// Whether a per-entry visitor is attached (backends widen their stat masks then).
+    #[inline]
+    pub fn has_visitor(&self) -> bool {
+        self.visitor.is_some()
+    }
+
+    /// Forward an entry to the attached visitor, if any.
+    #[inline]
+    pub fn visit(&self, entry: &FileEntry<'_>) {
+        if let Some(v) = self.visitor {
+            v.visit(entry);
+        }
+    }
+
+    /// Count a file into the directory's stats and forward it to the visitor.
+    #[inline]
+    pub fn record_file(&self, stat: &mut Stat, entry: &FileEntry<'_>) {
+        match self.admit(entry) {
+            Admit::Count => {}
+            Admit::Excluded(i) => {
+                if let Some(x) = &self.options.exclude_stats {
+                    x.charge(i, entry);
+                }
+                self.set_aside.set(self.set_aside.get() + 1);
+                return;
+            }
+            Admit::Ignored => {
+                stat.ignored += entry.physical;
+                self.set_aside.set(self.set_aside.get() + 1);
+                return;
+            }
+            Admit::Skip => return,
+        }
+        crate::common_ops::update_file_stats(stat, entry.logical, entry.physical);
+        stat.note_times(entry.mtime, entry.atime);
+        // Multiply-linked files are placed once all links are known
+        match self.links {
+            Some(links) if entry.nlink > 1 => links.add(entry, !self.options.count_hardlinks),
+            _ => stat.exclusive += entry.physical,
+        }
+        self.visit(entry);
+    }
+
+    /// Note a file skipped by hardlink dedupe and forward it to the visitor.
+    #[inline]
+    pub fn record_link(&self, entry: &FileEntry<'_>) {
+        if self.admit(entry) != Admit::Count {
+            return;
+        }
+        if let Some(links) = self.links.filter(|_| entry.nlink > 1) {
+            links.add(entry, false);
+        }
+        if let Some(v) = self.visitor {
+            v.visit_link(entry);
+        }
+    }
+
+    /// Report an error that left the current directory unread or partly read;
+    /// the directory is then marked incomplete.
+    #[inline]
+    pub(crate) fn record_dir_error(&self, err: &error_handling::ScanError) {
+        self.unread.set(true);
+        error_handling::record_error(self.options, err);
+    }
+
+    /// Count an unfollowed symlink and forward it to the visitor.
+    #[inline]
+    pub fn record_symlink(&self, stat: &mut Stat, entry: &FileEntry<'_>) {
+        if self.admit(entry) != Admit::Count {
+            return;
+        }
+        stat.symlinks += 1;
+        self.visit(entry);
+    }
+
+    /// How the exclude accounting, filters and ignore files treat a
+    /// non-directory entry.
+    #[inline]
+    fn admit(&self, entry: &FileEntry<'_>) -> Admit {
+        let opt = self.options;
+        if let Some(i) = opt
+            .exclude_stats
+            .as_ref()
+            .and_then(|x| x.rule_for_file(entry))
+        {
+            return Admit::Excluded(i);
+        }
+        if opt.filter.as_ref().is_some_and(|f| !f.matches(entry)) {
+            return Admit::Skip;
+        }
+        let filtered = opt.include_glob_set.is_some() || opt.include_regex_set.is_some();
+        if !filtered && opt.ignore_rules.is_none() {
+            return Admit::Count;
+        }
+        let path = entry.path();
+        if filtered && !crate::filters::path_included(&path, opt) {
+            return Admit::Skip;
+        }
+        match &opt.ignore_rules {
+            Some(rules) if rules.is_ignored(&path, false) => {
+                if opt.ignore_report {
+                    Admit::Ignored
+                } else {
+                    Admit::Skip
+                }
+            }
+            _ => Admit::Count,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Admit {
+    Count,
+    /// Matched an exclude pattern; only charged to it in `Options::exclude_stats`.
+    Excluded(usize),
+    /// Matched by an ignore file; only summed into `Stat::ignored`.
+    Ignored,
+    Skip,
 }
 
 #[inline]
@@ -298,15 +594,101 @@ fn compile_filters_in_place(opt: &mut Options) {
     } else {
         opt.exclude_glob_set = None;
     }
+    opt.include_regex_set = if opt.include_regex.is_empty() {
+        None
+    } else {
+        RegexSet::new(&opt.include_regex).ok()
+    };
+    // Unlike excludes, one bad include pattern drops the whole set, so that
+    // filter_error can refuse the scan instead of counting everything
+    opt.include_glob_set = if opt.include_glob.is_empty() {
+        None
+    } else {
+        let globs: Result<Vec<Glob>, _> = opt.include_glob.iter().map(|g| Glob::new(g)).collect();
+        globs.ok().and_then(|globs| {
+            let mut b = GlobSetBuilder::new();
+            for gl in globs {
+                b.add(gl);
+            }
+            b.build().ok()
+        })
+    };
+    opt.filter = if opt.filter_expr.is_empty() {
+        None
+    } else {
+        predicate::Predicate::parse_all(&opt.filter_expr)
+            .ok()
+            .map(Arc::new)
+    };
+    // Predicates need real metadata, not the approximate-size shortcuts
+    if opt.filter.is_some() {
+        opt.approximate_sizes = false;
+    }
+}
+
+/// Error for include patterns or filter expressions that did not compile (see
+/// compile_filters_in_place).
+fn filter_error(opt: &Options) -> Result<()> {
+    if !opt.include_glob.is_empty() && opt.include_glob_set.is_none() {
+        let bad = opt.include_glob.iter().find_map(|g| Glob::new(g).err());
+        return Err(anyhow!(
+            "invalid include glob: {}",
+            bad.map_or_else(|| "?".into(), |e| e.to_string())
+        ));
+    }
+    if !opt.include_regex.is_empty() && opt.include_regex_set.is_none() {
+        let bad = RegexSet::new(&opt.include_regex).err();
+        return Err(anyhow!(
+            "invalid include regex: {}",
+            bad.map_or_else(|| "?".into(), |e| e.to_string())
+        ));
+    }
+    if !opt.filter_expr.is_empty() && opt.filter.is_none() {
+        if let Err(e) = predicate::Predicate::parse_all(&opt.filter_expr) {
+            return Err(anyhow!("invalid filter expression: {e}"));
+        }
+    }
+    Ok(())
+}
+
+/// Options as a scan of `roots` uses them: filters compiled (or an error for
+/// bad ones), ignore rules set up, and excludes turned into accounting when
+/// `exclude_stats` is set.
+fn prepare_filters(opt: &Options, roots: &[PathBuf]) -> Result<Options> {
+    let mut compiled = opt.clone();
+    compile_filters_in_place(&mut compiled);
+    filter_error(&compiled)?;
+    if compiled.ignore_files {
+        compiled.ignore_rules = Some(Arc::new(ignore::IgnoreRules::new(roots)));
+    }
+    if let Some(stats) = &compiled.exclude_stats {
+        stats.start(&compiled);
+        // Walk excluded paths; ScanContext charges them to their pattern
+        compiled.exclude_contains.clear();
+        compiled.exclude_ac = None;
+        compiled.exclude_regex_set = None;
+        compiled.exclude_glob_set = None;
+    }
+    Ok(compiled)
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct Job {
     dir: PathBuf,
     depth: u32,
     resume: Option<u64>,
 }
 
+impl Job {
+    fn root(root: &Path) -> Self {
+        Job {
+            dir: root.to_path_buf(),
+            depth: 0,
+            resume: None,
+        }
+    }
+}
+
 pub fn scan_directory(root: impl AsRef<Path>, opt: &Options) -> Result<StatMap> {
     let scanner = Arc::new(crate::scanner::platform_scanner());
     scan_directory_with(root, opt, scanner)
@@ -319,43 +701,336 @@ pub fn scan_directory_with(
     opt: &Options,
     scanner: Arc<dyn FileSystemScanner>,
 ) -> Result<StatMap> {
-    #[cfg(any(feature = "prof-tracy", feature = "prof-puffin"))]
-    profiling::scope!("scan_directory");
-    let root = root.as_ref().to_path_buf();
+    scan_impl(root.as_ref(), opt, scanner, ScanHooks::default()).map(|t| t.to_stat_map())
+}
+
+/// Like scan_directory but returns the compact [`ScanTree`] instead of a
+/// path-keyed map. Prefer this for very large trees.
+pub fn scan_tree(root: impl AsRef<Path>, opt: &Options) -> Result<ScanTree> {
+    let scanner = Arc::new(crate::scanner::platform_scanner());
+    scan_tree_with(root, opt, scanner)
+}
+
+/// Variant of scan_tree that accepts a custom scanner implementation.
+pub fn scan_tree_with(
+    root: impl AsRef<Path>,
+    opt: &Options,
+    scanner: Arc<dyn FileSystemScanner>,
+) -> Result<ScanTree> {
+    scan_impl(root.as_ref(), opt, scanner, ScanHooks::default())
+}
+
+/// Variant of scan_directory_with that also reports every entry to `visitor`.
+/// Lets file-level analyses ride on the fast backends instead of a second walk.
+pub fn scan_directory_with_visitor(
+    root: impl AsRef<Path>,
+    opt: &Options,
+    scanner: Arc<dyn FileSystemScanner>,
+    visitor: Arc<dyn FileVisitor>,
+) -> Result<StatMap> {
+    let hooks = ScanHooks {
+        visitor: Some(visitor),
+        ..ScanHooks::default()
+    };
+    scan_impl(root.as_ref(), opt, scanner, hooks).map(|t| t.to_stat_map())
+}
+
+/// Start a scan on a background thread and return a handle that can be
+/// polled for partial results while it runs.
+pub fn start_scan(root: impl AsRef<Path>, opt: &Options) -> Result<ScanHandle> {
+    let scanner = Arc::new(crate::scanner::platform_scanner());
+    start_scan_with(root, opt, scanner)
+}
+
+/// Variant of start_scan that accepts a custom scanner implementation.
+pub fn start_scan_with(
+    root: impl AsRef<Path>,
+    opt: &Options,
+    scanner: Arc<dyn FileSystemScanner>,
+) -> Result<ScanHandle> {
+    spawn_scan(root.as_ref(), opt, scanner, None)
+}
+
+/// Variant of start_scan_with that also reports every entry to `visitor`.
+pub fn start_scan_with_visitor(
+    root: impl AsRef<Path>,
+    opt: &Options,
+    scanner: Arc<dyn FileSystemScanner>,
+    visitor: Arc<dyn FileVisitor>,
+) -> Result<ScanHandle> {
+    spawn_scan(root.as_ref(), opt, scanner, Some(visitor))
+}
+
+fn spawn_scan(
+    root: &Path,
+    opt: &Options,
+    scanner: Arc<dyn FileSystemScanner>,
+    visitor: Option<Arc<dyn FileVisitor>>,
+) -> Result<ScanHandle> {
+    let root = root.to_path_buf();
     if !root.exists() {
         return Err(anyhow!("root does not exist: {}", root.display()));
     }
+    let live = Arc::new(live_state_for(&root, opt));
+    let hooks = ScanHooks {
+        visitor,
+        live: Some(live.clone()),
+    };
+    let opt = opt.clone();
+    let cancel = opt.cancel.clone();
+    let thread_live = live.clone();
+    let thread = std::thread::spawn(move || {
+        let tree = scan_impl(&root, &opt, scanner, hooks)?;
+        thread_live.finish(&tree);
+        Ok(tree)
+    });
+    Ok(ScanHandle::new(live, cancel, thread))
+}
+
+/// Live state to start a scan with: seeded from `opt.resume_from` if set.
+fn live_state_for(root: &Path, opt: &Options) -> LiveState {
+    match &opt.resume_from {
+        Some(cp) => LiveState::resumed(root, cp.stats.clone(), &cp.jobs),
+        None => LiveState::new(root),
+    }
+}
+
+/// Optional per-scan extensions threaded into the worker loop.
+#[derive(Clone, Default)]
+struct ScanHooks {
+    visitor: Option<Arc<dyn FileVisitor>>,
+    live: Option<Arc<LiveState>>,
+}
+
+fn scan_impl(
+    root: &Path,
+    opt: &Options,
+    scanner: Arc<dyn FileSystemScanner>,
+    hooks: ScanHooks,
+) -> Result<ScanTree> {
+    let root = root.to_path_buf();
+    let out = run_pool(std::slice::from_ref(&root), opt, scanner, hooks)?;
+    let tree = out.tree(&root, &root);
+    if let Ok(mut last) = opt.last_estimate.lock() {
+        *last = tree.estimate.clone();
+    }
+    Ok(tree)
+}
+
+/// Scan several roots on one worker pool with one hardlink dedupe cache.
+/// Roots inside another root are read once, as part of the enclosing root.
+pub fn scan_roots(roots: &[PathBuf], opt: &Options) -> Result<MultiScan> {
+    let scanner = Arc::new(crate::scanner::platform_scanner());
+    scan_roots_with(roots, opt, scanner)
+}
+
+/// Variant of scan_roots that accepts a custom scanner implementation.
+pub fn scan_roots_with(
+    roots: &[PathBuf],
+    opt: &Options,
+    scanner: Arc<dyn FileSystemScanner>,
+) -> Result<MultiScan> {
+    let mut opt = opt.clone();
+    // Hardlinks shared between roots count once
+    if !opt.count_hardlinks && opt.inode_cache.is_none() {
+        opt.inode_cache = Some(Arc::new(DashMap::new()));
+    }
+    let out = run_pool(roots, &opt, scanner, ScanHooks::default())?;
+    let outer = outermost(roots);
+    let mut total = Stat::default();
+    let trees = roots
+        .iter()
+        .map(|root| {
+            let top = outer
+                .iter()
+                .find(|o| root.starts_with(o))
+                .map_or(root.as_path(), |o| o);
+            out.tree(root, top)
+        })
+        .collect::<Vec<_>>();
+    for o in &outer {
+        if let Some(tree) = trees.iter().find(|t| t.root_path() == *o) {
+            total.merge(&tree.stat(ScanTree::ROOT));
+            total.dirs += 1;
+        }
+    }
+    // Hardlinks spread over several roots are placed above all of them
+    total.exclusive += out.dirs.exclusive_outside(&outer);
+    Ok(MultiScan { trees, total })
+}
+
+/// Roots not inside another root, duplicates once.
+fn outermost(roots: &[PathBuf]) -> Vec<&Path> {
+    let mut out: Vec<&Path> = Vec::new();
+    for r in roots {
+        let nested = roots.iter().any(|o| o != r && r.starts_with(o));
+        if !nested && !out.contains(&r.as_path()) {
+            out.push(r);
+        }
+    }
+    out
+}
+
+/// What the worker pool leaves for the caller to build trees from.
+struct PoolOutput {
+    /// Per-directory stats (not rolled up) of all roots, hardlinks resolved.
+    dirs: DirArena,
+    errors: Vec<ScanErrorRecord>,
+    /// Directories left queued by a cancel or a spent budget, with depths
+    /// relative to their outermost root.
+    unexplored: Vec<(PathBuf, u32)>,
+    budget_hit: bool,
+}
+
+impl PoolOutput {
+    /// Rolled-up tree of `root` (inside the outermost root `top`), with the
+    /// scan's errors under it and a size estimate when the budget ran out.
+    fn tree(&self, root: &Path, top: &Path) -> ScanTree {
+        let mut tree = self.dirs.subtree(root);
+        tree.estimate = self.estimate(&tree, top);
+        tree.roll_up();
+        tree.errors = self
+            .errors
+            .iter()
+            .filter(|e| e.path.starts_with(root))
+            .cloned()
+            .collect();
+        tree
+    }
+
+    /// Size estimate for the per-directory tree `dirs` (inside the outermost
+    /// root `top`) when the budget ran out.
+    fn estimate(&self, dirs: &ScanTree, top: &Path) -> Option<estimate::SizeEstimate> {
+        let root = dirs.root_path();
+        if !self.budget_hit {
+            return None;
+        }
+        let offset = root.strip_prefix(top).map_or(0, |rel| {
+            rel.components()
+                .filter(|c| matches!(c, std::path::Component::Normal(_)))
+                .count() as u32
+        });
+        let depths: Vec<u32> = self
+            .unexplored
+            .iter()
+            .filter(|(dir, _)| dir.starts_with(root))
+            .map(|(_, depth)| depth.saturating_sub(offset))
+            .collect();
+        Some(estimate::estimate(dirs, &depths))
+    }
+}
+
+fn run_pool(
+    roots: &[PathBuf],
+    opt: &Options,
+    scanner: Arc<dyn FileSystemScanner>,
+    mut hooks: ScanHooks,
+) -> Result<PoolOutput> {
+    #[cfg(any(feature = "prof-tracy", feature = "prof-puffin"))]
+    profiling::scope!("scan_directory");
+    let Some(root) = roots.first().cloned() else {
+        return Err(anyhow!("no roots to scan"));
+    };
+    if let Some(missing) = roots.iter().find(|r| !r.exists()) {
+        return Err(anyhow!("root does not exist: {}", missing.display()));
+    }
+    // The caller's error list only describes the latest scan
+    opt.errors.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    // Checkpoints and live snapshots describe a single root
+    let single = opt.checkpoint_path.is_some() || opt.resume_from.is_some() || hooks.live.is_some();
+    if single && roots.len() > 1 {
+        return Err(anyhow!("checkpoints and live snapshots take a single root"));
+    }
+    if let Some(cp) = &opt.resume_from {
+        if cp.root() != root {
+            return Err(anyhow!(
+                "checkpoint is for {}, not {}",
+                cp.root().display(),
+                root.display()
+            ));
+        }
+    }
 
     let threads = opt.threads.max(1);
     let high_injector: Arc<Injector<Job>> = Arc::new(Injector::new());
     let normal_injector: Arc<Injector<Job>> = Arc::new(Injector::new());
-    high_injector.push(Job {
-        dir: root.clone(),
-        depth: 0,
-        resume: None,
-    });
+    let hint_queue = opt
+        .size_hints
+        .clone()
+        .map(|h| Arc::new(hints::HintQueue::new(h)));
+    match &opt.resume_from {
+        // Partly read directories first, like freshly yielded ones
+        Some(cp) => {
+            for job in &cp.jobs {
+                match (job.resume, &hint_queue) {
+                    (Some(_), _) => high_injector.push(job.clone()),
+                    (None, Some(q)) => q.push(job.clone(), &normal_injector),
+                    (None, None) => normal_injector.push(job.clone()),
+                }
+            }
+        }
+        None => {
+            for r in outermost(roots) {
+                high_injector.push(Job::root(r));
+            }
+        }
+    }
+    // Checkpoints are taken from the live state, so make sure there is one
+    let checkpointing = opt.checkpoint_path.is_some() || opt.resume_from.is_some();
+    if checkpointing && hooks.live.is_none() {
+        hooks.live = Some(Arc::new(live_state_for(&root, opt)));
+    }
 
     let total_files = Arc::new(AtomicU64::new(0));
 
     let workers: Vec<Worker<Job>> = (0..threads).map(|_| Worker::new_fifo()).collect();
     let stealers = workers.iter().map(|w| w.stealer()).collect::<Vec<_>>();
 
-    let mut compiled = opt.clone();
-    compile_filters_in_place(&mut compiled);
+    let mut compiled = prepare_filters(opt, roots)?;
+    // Per-scan error log; appended to the caller's once the scan is done
+    compiled.errors = Arc::default();
+    let errors = compiled.errors.clone();
     let options = Arc::new(compiled);
 
     // Start adaptive tuner if enabled
     let _tuner = tuning::start_if_enabled(options.clone(), total_files.clone());
+    let links = Arc::new(hardlink::LinkTable::new(roots));
+    if let Some(cp) = &opt.resume_from {
+        checkpoint::restore(cp, &options, &links);
+    }
+    let gate = Arc::new(checkpoint::PauseGate::new(threads));
+    let budget = Arc::new(estimate::Budget::new(&options));
+    let io_throttle = throttle::IoThrottle::from_options(&options).map(Arc::new);
+    let writer = match (&opt.checkpoint_path, &hooks.live) {
+        (Some(path), Some(live)) => Some(checkpoint::start_writer(
+            root.clone(),
+            path.clone(),
+            options.clone(),
+            gate.clone(),
+            live.clone(),
+            links.clone(),
+        )),
+        _ => None,
+    };
 
     let mut handles = Vec::with_capacity(threads);
     for (i, local) in workers.into_iter().enumerate() {
         let high_ref = high_injector.clone();
         let normal_ref = normal_injector.clone();
+        let hint_ref = hint_queue.clone();
         let stealers_ref = stealers.clone();
         let options = options.clone();
         let total_files = total_files.clone();
         let scanner = scanner.clone();
+        let hooks = hooks.clone();
+        let links = links.clone();
+        let gate = gate.clone();
+        let budget = budget.clone();
+        let io_throttle = io_throttle.clone();
         let handle = std::thread::spawn(move || {
+            if options.ioprio_idle {
+                throttle::set_idle_io_priority();
+            }
             #[cfg(target_os = "linux")]
             {
                 if std::env::var("HYPERDU_PIN_THREADS").ok().as_deref() == Some("1") {
@@ -380,12 +1055,26 @@ pub fn scan_directory_with(
             }
             #[cfg(any(feature = "prof-tracy", feature = "prof-puffin"))]
             profiling::register_thread!();
+            // The backend fills `local_map` per job; without a live state it is
+            // folded into this worker's arena right away
             let mut local_map: StatMap = HashMap::default();
+            let mut dirs = DirArena::default();
+            let mut live_buf = LiveBuffer::default();
             let mut next = i % stealers_ref.len().max(1);
             loop {
                 if options.cancel.load(std::sync::atomic::Ordering::Relaxed) {
                     break;
                 }
+                if budget.exhausted() {
+                    break;
+                }
+                if gate.requested() {
+                    if let Some(live) = &hooks.live {
+                        live.publish(&mut local_map, &mut live_buf);
+                    }
+                    gate.park();
+                    continue;
+                }
                 // Runtime thread throttling: only first `active_threads` workers fetch jobs
                 let act = options
                     .active_threads
@@ -396,28 +1085,31 @@ pub fn scan_directory_with(
                 }
                 let job = local.pop().or_else(|| match high_ref.steal() {
                     Steal::Success(j) => Some(j),
-                    Steal::Empty => match normal_ref.steal() {
-                        Steal::Success(j) => Some(j),
-                        Steal::Empty => {
-                            let mut found = None;
-                            let len = stealers_ref.len();
-                            for k in 0..len {
-                                let idx = (next + k) % len;
-                                match stealers_ref[idx].steal() {
-                                    Steal::Success(j) => {
-                                        found = Some(j);
-                                        break;
+                    Steal::Empty => match hint_ref.as_ref().and_then(|q| q.pop()) {
+                        Some(j) => Some(j),
+                        None => match normal_ref.steal() {
+                            Steal::Success(j) => Some(j),
+                            Steal::Empty => {
+                                let mut found = None;
+                                let len = stealers_ref.len();
+                                for k in 0..len {
+                                    let idx = (next + k) % len;
+                                    match stealers_ref[idx].steal() {
+                                        Steal::Success(j) => {
+                                            found = Some(j);
+                                            break;
+                                        }
+                                        Steal::Retry => {}
+                                        Steal::Empty => {}
                                     }
-                                    Steal::Retry => {}
-                                    Steal::Empty => {}
                                 }
+                                if len > 0 {
+                                    next = (next + 1) % len;
+                                }
+                                found
                             }
-                            if len > 0 {
-                                next = (next + 1) % len;
-                            }
-                            found
-                        }
-                        Steal::Retry => None,
+                            Steal::Retry => None,
+                        },
                     },
                     Steal::Retry => None,
                 });
@@ -425,42 +1117,100 @@ pub fn scan_directory_with(
                 let Some(Job { dir, depth, resume }) = job else {
                     break;
                 };
-                if path_excluded(&dir, &options) {
-                    continue;
+                if !path_excluded(&dir, &options) {
+                    #[cfg(any(feature = "prof-tracy", feature = "prof-puffin"))]
+                    profiling::scope!("process_dir_loop");
+                    let unread = std::cell::Cell::new(false);
+                    let set_aside = std::cell::Cell::new(0);
+                    let ctx = ScanContext {
+                        options: &options,
+                        high_injector: &high_ref,
+                        normal_injector: &normal_ref,
+                        hinted: hint_ref.as_deref(),
+                        total_files: &total_files,
+                        visitor: hooks.visitor.as_deref(),
+                        live: hooks.live.as_ref().map(|_| &live_buf),
+                        links: Some(&links),
+                        unread: &unread,
+                        set_aside: &set_aside,
+                        throttle: io_throttle.as_deref(),
+                    };
+                    let dctx = DirContext {
+                        dir: &dir,
+                        depth,
+                        resume,
+                    };
+                    let files = |m: &StatMap| m.get(&dir).map_or(0, |s| s.files);
+                    let before = budget.counts_files().then(|| files(&local_map));
+                    scanner.process_dir(&ctx, &dctx, &mut local_map);
+                    if let Some(before) = before {
+                        budget.add_files(files(&local_map).saturating_sub(before));
+                    }
+                    note_dir_done(&mut local_map, &dir, unread.get());
+                }
+                match &hooks.live {
+                    Some(live) => {
+                        live_buf.adjust(&dir, depth, resume, -1);
+                        if live_buf.due() {
+                            live.publish(&mut local_map, &mut live_buf);
+                        }
+                    }
+                    None => dirs.add_map(local_map.drain()),
                 }
-                #[cfg(any(feature = "prof-tracy", feature = "prof-puffin"))]
-                profiling::scope!("process_dir_loop");
-                let ctx = ScanContext {
-                    options: &options,
-                    high_injector: &high_ref,
-                    normal_injector: &normal_ref,
-                    total_files: &total_files,
-                };
-                let dctx = DirContext {
-                    dir: &dir,
-                    depth,
-                    resume,
-                };
-                scanner.process_dir(&ctx, &dctx, &mut local_map);
             }
-            local_map
+            gate.exit();
+            // Leftover jobs stay pending in the live state (for the final
+            // checkpoint) and are marked unread after the merge.
+            let leftover: Vec<Job> = std::iter::from_fn(|| local.pop()).collect();
+            if let Some(live) = &hooks.live {
+                live.publish(&mut local_map, &mut live_buf);
+            }
+            (dirs, leftover)
         });
         handles.push(handle);
     }
 
-    // Merge thread maps
-    let mut merged: StatMap = HashMap::default();
+    // Merge thread arenas
+    let mut merged = DirArena::default();
+    let mut leftover = Vec::new();
     for h in handles {
-        for (k, v) in h.join().unwrap_or_default() {
-            let e = merged.entry(k).or_default();
-            e.logical += v.logical;
-            e.physical += v.physical;
-            e.files += v.files;
+        let (dirs, jobs) = h.join().unwrap_or_default();
+        merged.absorb(dirs);
+        leftover.extend(jobs);
+    }
+    drop(writer);
+    let stopped_early =
+        options.cancel.load(std::sync::atomic::Ordering::Relaxed) || budget.was_hit();
+    if let (Some(path), Some(live)) = (&opt.checkpoint_path, &hooks.live) {
+        if stopped_early {
+            let cp = checkpoint::capture(&root, live, &options, &links);
+            checkpoint::save_or_report(&cp, path, &options);
+        } else {
+            let _ = std::fs::remove_file(path);
         }
     }
+    if let Some(live) = &hooks.live {
+        merged.add_map(live.final_stats());
+    }
+    leftover.extend(drain_injector(&high_injector));
+    leftover.extend(drain_injector(&normal_injector));
+    leftover.extend(hint_queue.iter().flat_map(|q| q.drain()));
+    let unexplored = leftover.iter().map(|j| (j.dir.clone(), j.depth)).collect();
+    mark_unread(&mut merged, leftover.into_iter());
+    links.attribute_to_first_root(&mut merged);
+    links.resolve(&mut merged);
 
-    let merged = rollup::rollup_child_to_parent(merged);
-    Ok(merged)
+    let errors = std::mem::take(&mut *errors.lock().unwrap_or_else(|e| e.into_inner()));
+    if let Ok(mut all) = opt.errors.lock() {
+        let room = MAX_ERROR_RECORDS.saturating_sub(all.len());
+        all.extend(errors.iter().take(room).cloned());
+    }
+    Ok(PoolOutput {
+        dirs: merged,
+        errors,
+        unexplored,
+        budget_hit: budget.was_hit(),
+    })
 }
 
 /// Experimental rayon-based internal scheduler. Uses a rayon thread-pool with `opt.threads`
@@ -473,6 +1223,7 @@ pub fn scan_directory_rayon(root: impl AsRef<Path>, opt: &Options) -> Result<Sta
     if !root.exists() {
         return Err(anyhow!("root does not exist: {}", root.display()));
     }
+    opt.errors.lock().unwrap_or_else(|e| e.into_inner()).clear();
     let threads = opt.threads.max(1);
     let high_injector: Arc<Injector<Job>> = Arc::new(Injector::new());
     let normal_injector: Arc<Injector<Job>> = Arc::new(Injector::new());
@@ -481,13 +1232,17 @@ pub fn scan_directory_rayon(root: impl AsRef<Path>, opt: &Options) -> Result<Sta
         depth: 0,
         resume: None,
     });
+    let hint_queue = opt
+        .size_hints
+        .clone()
+        .map(|h| Arc::new(hints::HintQueue::new(h)));
     let total_files = Arc::new(AtomicU64::new(0));
-    let mut compiled = opt.clone();
-    compile_filters_in_place(&mut compiled);
-    let options = Arc::new(compiled);
+    let options = Arc::new(prepare_filters(opt, std::slice::from_ref(&root))?);
     let workers: Vec<Worker<Job>> = (0..threads).map(|_| Worker::new_fifo()).collect();
     let stealers = workers.iter().map(|w| w.stealer()).collect::<Vec<_>>();
-    let merged = Arc::new(std::sync::Mutex::new(HashMap::default()));
+    let merged = Arc::new(std::sync::Mutex::new(DirArena::default()));
+    let links = Arc::new(hardlink::LinkTable::default());
+    let io_throttle = throttle::IoThrottle::from_options(&options).map(Arc::new);
     let pool = ThreadPoolBuilder::new()
         .num_threads(threads)
         .build()
@@ -497,13 +1252,20 @@ pub fn scan_directory_rayon(root: impl AsRef<Path>, opt: &Options) -> Result<Sta
             for (i, local) in workers.into_iter().enumerate() {
                 let high_ref = high_injector.clone();
                 let normal_ref = normal_injector.clone();
+                let hint_ref = hint_queue.clone();
                 let stealers_ref = stealers.clone();
                 let options = options.clone();
                 let total_files = total_files.clone();
                 let merged = merged.clone();
                 let scanner2 = scanner.clone();
+                let links = links.clone();
+                let io_throttle = io_throttle.clone();
                 s.spawn(move |_| {
+                    if options.ioprio_idle {
+                        throttle::set_idle_io_priority();
+                    }
                     let mut local_map: StatMap = HashMap::default();
+                    let mut dirs = DirArena::default();
                     let mut next = i % stealers_ref.len().max(1);
                     loop {
                         if options.cancel.load(std::sync::atomic::Ordering::Relaxed) {
@@ -511,28 +1273,31 @@ pub fn scan_directory_rayon(root: impl AsRef<Path>, opt: &Options) -> Result<Sta
                         }
                         let job = local.pop().or_else(|| match high_ref.steal() {
                             Steal::Success(j) => Some(j),
-                            Steal::Empty => match normal_ref.steal() {
-                                Steal::Success(j) => Some(j),
-                                Steal::Empty => {
-                                    let mut found = None;
-                                    let len = stealers_ref.len();
-                                    for k in 0..len {
-                                        let idx = (next + k) % len;
-                                        match stealers_ref[idx].steal() {
-                                            Steal::Success(j) => {
-                                                found = Some(j);
-                                                break;
+                            Steal::Empty => match hint_ref.as_ref().and_then(|q| q.pop()) {
+                                Some(j) => Some(j),
+                                None => match normal_ref.steal() {
+                                    Steal::Success(j) => Some(j),
+                                    Steal::Empty => {
+                                        let mut found = None;
+                                        let len = stealers_ref.len();
+                                        for k in 0..len {
+                                            let idx = (next + k) % len;
+                                            match stealers_ref[idx].steal() {
+                                                Steal::Success(j) => {
+                                                    found = Some(j);
+                                                    break;
+                                                }
+                                                Steal::Retry => {}
+                                                Steal::Empty => {}
                                             }
-                                            Steal::Retry => {}
-                                            Steal::Empty => {}
                                         }
+                                        if len > 0 {
+                                            next = (next + 1) % len;
+                                        }
+                                        found
                                     }
-                                    if len > 0 {
-                                        next = (next + 1) % len;
-                                    }
-                                    found
-                                }
-                                Steal::Retry => None,
+                                    Steal::Retry => None,
+                                },
                             },
                             Steal::Retry => None,
                         });
@@ -542,11 +1307,20 @@ pub fn scan_directory_rayon(root: impl AsRef<Path>, opt: &Options) -> Result<Sta
                         if path_excluded(&dir, &options) {
                             continue;
                         }
+                        let unread = std::cell::Cell::new(false);
+                        let set_aside = std::cell::Cell::new(0);
                         let ctx = ScanContext {
                             options: &options,
                             high_injector: &high_ref,
                             normal_injector: &normal_ref,
+                            hinted: hint_ref.as_deref(),
                             total_files: &total_files,
+                            visitor: None,
+                            live: None,
+                            links: Some(&links),
+                            unread: &unread,
+                            set_aside: &set_aside,
+                            throttle: io_throttle.as_deref(),
                         };
                         let dctx = DirContext {
                             dir: &dir,
@@ -554,21 +1328,53 @@ pub fn scan_directory_rayon(root: impl AsRef<Path>, opt: &Options) -> Result<Sta
                             resume,
                         };
                         scanner2.process_dir(&ctx, &dctx, &mut local_map);
+                        note_dir_done(&mut local_map, &dir, unread.get());
+                        dirs.add_map(local_map.drain());
                     }
-                    let mut g = merged.lock().unwrap();
-                    for (k, v) in local_map {
-                        let e: &mut Stat = g.entry(k).or_default();
-                        e.logical += v.logical;
-                        e.physical += v.physical;
-                        e.files += v.files;
-                    }
+                    mark_unread(&mut dirs, std::iter::from_fn(|| local.pop()));
+                    merged.lock().unwrap().absorb(dirs);
                 });
             }
         });
     });
-    let merged = std::mem::take(&mut *merged.lock().unwrap());
-    let merged = rollup::rollup_child_to_parent(merged);
-    Ok(merged)
+    let mut merged = std::mem::take(&mut *merged.lock().unwrap());
+    mark_unread(&mut merged, drain_injector(&high_injector));
+    mark_unread(&mut merged, drain_injector(&normal_injector));
+    mark_unread(&mut merged, hint_queue.iter().flat_map(|q| q.drain()));
+    links.resolve(&mut merged);
+    let mut tree = merged.subtree(&root);
+    tree.roll_up();
+    Ok(tree.to_stat_map())
+}
+
+/// Make sure a processed directory has an entry (directories that could not
+/// be opened still count) and mark it incomplete if reading it failed.
+#[inline]
+fn note_dir_done(map: &mut StatMap, dir: &Path, unread: bool) {
+    if unread {
+        map.entry(dir.to_path_buf()).or_default().incomplete += 1;
+    } else if !map.contains_key(dir) {
+        map.insert(dir.to_path_buf(), Stat::default());
+    }
+}
+
+/// Mark directories whose jobs never ran (left queued by a cancel) incomplete.
+/// A pending resume job means the directory was only partly read.
+fn mark_unread(dirs: &mut DirArena, jobs: impl Iterator<Item = Job>) {
+    for job in jobs {
+        dirs.stat_mut(&job.dir).incomplete += 1;
+    }
+}
+
+/// Pop every job still queued in `injector`.
+fn drain_injector(injector: &Injector<Job>) -> impl Iterator<Item = Job> + '_ {
+    std::iter::from_fn(move || loop {
+        match injector.steal() {
+            Steal::Success(j) => return Some(j),
+            Steal::Empty => return None,
+            Steal::Retry => {}
+        }
+    })
 }
 
 use crate::filters::path_excluded;
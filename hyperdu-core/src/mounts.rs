@@ -0,0 +1,229 @@
+//! Per-filesystem breakdown of a scan from the mount table.
+//!
+//! [`MountVisitor`] sums each directory's own files during the scan;
+//! [`MountVisitor::report`] then assigns every directory to the mount it lives
+//! on (longest mount point prefix, as the kernel resolves paths) and lists the
+//! mounts below the root that the scan never entered, e.g. the ones
+//! `one_file_system` skipped.
+
+use std::path::{Path, PathBuf};
+
+use ahash::AHashMap as HashMap;
+use dashmap::DashMap;
+use serde::Serialize;
+
+use crate::{EntryKind, FileEntry, FileVisitor, Stat};
+
+/// One line of `/proc/self/mountinfo`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Mount {
+    pub id: u32,
+    pub parent_id: u32,
+    /// Device as `major:minor`.
+    pub dev: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    pub source: String,
+}
+
+/// Mounts visible to this process, in mount order.
+#[derive(Clone, Debug, Default)]
+pub struct MountTable {
+    mounts: Vec<Mount>,
+}
+
+impl MountTable {
+    /// Read `/proc/self/mountinfo`. Empty when it is unavailable (non-Linux).
+    pub fn read() -> Self {
+        std::fs::read_to_string("/proc/self/mountinfo")
+            .map(|text| Self::parse_mountinfo(&text))
+            .unwrap_or_default()
+    }
+
+    /// Parse mountinfo text, skipping malformed lines. Format:
+    /// `ID PARENT MAJ:MIN ROOT MOUNT_POINT OPTS [OPTIONAL..] - FSTYPE SOURCE SUPER_OPTS`
+    pub fn parse_mountinfo(text: &str) -> Self {
+        let mounts = text
+            .lines()
+            .filter_map(|line| {
+                let (pre, post) = line.split_once(" - ")?;
+                let pre: Vec<&str> = pre.split_whitespace().collect();
+                let mut post = post.split_whitespace();
+                if pre.len() < 5 {
+                    return None;
+                }
+                Some(Mount {
+                    id: pre[0].parse().ok()?,
+                    parent_id: pre[1].parse().ok()?,
+                    dev: pre[2].to_string(),
+                    mount_point: PathBuf::from(unescape(pre[4])),
+                    fs_type: post.next()?.to_string(),
+                    source: post.next().map(unescape).unwrap_or_default(),
+                })
+            })
+            .collect();
+        Self { mounts }
+    }
+
+    pub fn mounts(&self) -> &[Mount] {
+        &self.mounts
+    }
+
+    /// Mount an absolute path lives on: the longest mount point above it, the
+    /// later one when a mount point is mounted over.
+    pub fn mount_for(&self, path: &Path) -> Option<&Mount> {
+        self.mounts
+            .iter()
+            .filter(|m| path.starts_with(&m.mount_point))
+            .max_by_key(|m| m.mount_point.as_os_str().len())
+    }
+}
+
+/// Undo the octal escapes mountinfo uses for space, tab, newline and backslash.
+fn unescape(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        let oct = b
+            .get(i + 1..i + 4)
+            .filter(|d| d.iter().all(|c| (b'0'..=b'7').contains(c)));
+        match oct {
+            Some(d) if b[i] == b'\\' => {
+                out.push(d.iter().fold(0u8, |n, c| (n << 3) | (c - b'0')));
+                i += 4;
+            }
+            _ => {
+                out.push(b[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Usage on one filesystem. `stat.dirs` counts the directories read on it.
+#[derive(Clone, Debug, Serialize)]
+pub struct MountUsage {
+    pub mount: Mount,
+    pub stat: Stat,
+}
+
+/// Per-filesystem breakdown of a scan.
+#[derive(Clone, Debug, Default)]
+pub struct MountReport {
+    /// Filesystems the scan read from, physical size descending.
+    pub mounts: Vec<MountUsage>,
+    /// Mounts below the root the scan did not enter, by mount point.
+    pub skipped: Vec<Mount>,
+    /// Each directory's own files (not rolled up: a subtree can span several
+    /// filesystems) and the index of its filesystem in `mounts`.
+    pub per_dir: HashMap<PathBuf, (usize, Stat)>,
+}
+
+/// Visitor that sums each directory's own files for a [`MountReport`].
+///
+/// Attribution goes by path, so it works the same on every backend; on
+/// systems without `/proc/self/mountinfo` the report stays empty.
+#[derive(Default)]
+pub struct MountVisitor {
+    dirs: DashMap<PathBuf, Stat>,
+}
+
+impl MountVisitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assign the directories below `root` to the mounts of `table`.
+    pub fn report(&self, root: &Path, table: &MountTable) -> MountReport {
+        // Mount points are absolute and resolved; scanned paths are spelled
+        // like `root`
+        let abs_root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let mut own: HashMap<PathBuf, Stat> = self
+            .dirs
+            .iter()
+            .map(|e| (e.key().clone(), *e.value()))
+            .collect();
+        own.entry(root.to_path_buf()).or_default();
+        let mut by_id: HashMap<u32, MountUsage> = HashMap::default();
+        let mut dir_mount = Vec::with_capacity(own.len());
+        for (dir, s) in own {
+            let Ok(rel) = dir.strip_prefix(root) else {
+                continue;
+            };
+            let Some(m) = table.mount_for(&abs_root.join(rel)) else {
+                continue;
+            };
+            let u = by_id.entry(m.id).or_insert_with(|| MountUsage {
+                mount: m.clone(),
+                stat: Stat::default(),
+            });
+            u.stat.merge(&s);
+            u.stat.dirs += 1;
+            dir_mount.push((dir, m.id, s));
+        }
+        let mut out = MountReport {
+            skipped: table
+                .mounts()
+                .iter()
+                .filter(|m| {
+                    m.mount_point != abs_root
+                        && m.mount_point.starts_with(&abs_root)
+                        && !by_id.contains_key(&m.id)
+                        // Not hidden by a later mount on the same point
+                        && table.mount_for(&m.mount_point).map(|t| t.id) == Some(m.id)
+                })
+                .cloned()
+                .collect(),
+            ..MountReport::default()
+        };
+        out.skipped
+            .sort_unstable_by(|a, b| a.mount_point.cmp(&b.mount_point));
+        out.mounts = by_id.into_values().collect();
+        out.mounts.sort_unstable_by(|a, b| {
+            b.stat
+                .physical
+                .cmp(&a.stat.physical)
+                .then_with(|| a.mount.mount_point.cmp(&b.mount.mount_point))
+        });
+        let index: HashMap<u32, usize> = out
+            .mounts
+            .iter()
+            .enumerate()
+            .map(|(i, u)| (u.mount.id, i))
+            .collect();
+        out.per_dir = dir_mount
+            .into_iter()
+            .map(|(dir, id, s)| (dir, (index[&id], s)))
+            .collect();
+        out
+    }
+}
+
+impl FileVisitor for MountVisitor {
+    fn visit(&self, entry: &FileEntry<'_>) {
+        let mut s = Stat::default();
+        match entry.kind {
+            // Register entered directories, so empty mounts still show up
+            EntryKind::Dir => {
+                self.dirs.entry(entry.path()).or_default();
+                return;
+            }
+            EntryKind::Symlink => s.symlinks = 1,
+            _ => {
+                s.files = 1;
+                s.logical = entry.logical;
+                s.physical = entry.physical;
+            }
+        }
+        if let Some(mut d) = self.dirs.get_mut(entry.parent) {
+            d.merge(&s);
+            return;
+        }
+        self.dirs
+            .entry(entry.parent.to_path_buf())
+            .or_default()
+            .merge(&s);
+    }
+}
@@ -6,6 +6,9 @@ pub struct FilterConfig {
     pub exclude_contains: Vec<String>,
     pub exclude_regex: Vec<String>,
     pub exclude_glob: Vec<String>,
+    pub include_regex: Vec<String>,
+    pub include_glob: Vec<String>,
+    pub filter_expr: Vec<String>,
     pub max_depth: Option<u32>,
     pub min_file_size: Option<u64>,
 }
@@ -15,6 +18,7 @@ pub struct PerformanceConfig {
     pub threads: Option<usize>,
     pub compute_physical: Option<bool>,
     pub approximate_sizes: Option<bool>,
+    pub extent_accounting: Option<bool>,
     pub one_file_system: Option<bool>,
     pub follow_links: Option<bool>,
     pub prefer_inner_rayon: Option<bool>,
@@ -49,12 +53,16 @@ pub struct OptionsBuilder {
     pub exclude_contains: Vec<String>,
     pub exclude_regex: Vec<String>,
     pub exclude_glob: Vec<String>,
+    pub include_regex: Vec<String>,
+    pub include_glob: Vec<String>,
+    pub filter_expr: Vec<String>,
     pub max_depth: Option<u32>,
     pub min_file_size: Option<u64>,
     pub follow_links: Option<bool>,
     pub threads: Option<usize>,
     pub compute_physical: Option<bool>,
     pub approximate_sizes: Option<bool>,
+    pub extent_accounting: Option<bool>,
     pub one_file_system: Option<bool>,
     pub progress_every: Option<u64>,
     pub compat_mode: Option<CompatMode>,
@@ -84,6 +92,24 @@ impl OptionsBuilder {
         self.exclude_glob = list.into_iter().collect();
         self
     }
+    /// Count only files whose full path matches one of these regexes
+    /// (or an include glob); directories are still traversed.
+    pub fn with_include_regex(mut self, list: impl IntoIterator<Item = String>) -> Self {
+        self.include_regex = list.into_iter().collect();
+        self
+    }
+    /// Count only files whose full path matches one of these globs
+    /// (or an include regex); directories are still traversed.
+    pub fn with_include_glob(mut self, list: impl IntoIterator<Item = String>) -> Self {
+        self.include_glob = list.into_iter().collect();
+        self
+    }
+    /// Count only files matching every one of these predicate expressions
+    /// (see [`crate::predicate`]), e.g. `size>100M and mtime>90d`.
+    pub fn with_filter_expr(mut self, list: impl IntoIterator<Item = String>) -> Self {
+        self.filter_expr = list.into_iter().collect();
+        self
+    }
     pub fn with_filters(mut self, cfg: FilterConfig) -> Self {
         if !cfg.exclude_contains.is_empty() {
             self.exclude_contains = cfg.exclude_contains;
@@ -94,6 +120,15 @@ impl OptionsBuilder {
         if !cfg.exclude_glob.is_empty() {
             self.exclude_glob = cfg.exclude_glob;
         }
+        if !cfg.include_regex.is_empty() {
+            self.include_regex = cfg.include_regex;
+        }
+        if !cfg.include_glob.is_empty() {
+            self.include_glob = cfg.include_glob;
+        }
+        if !cfg.filter_expr.is_empty() {
+            self.filter_expr = cfg.filter_expr;
+        }
         self.max_depth = cfg.max_depth.or(self.max_depth);
         self.min_file_size = cfg.min_file_size.or(self.min_file_size);
         self
@@ -122,6 +157,10 @@ impl OptionsBuilder {
         self.approximate_sizes = Some(v);
         self
     }
+    pub fn extent_accounting(mut self, v: bool) -> Self {
+        self.extent_accounting = Some(v);
+        self
+    }
     pub fn one_file_system(mut self, v: bool) -> Self {
         self.one_file_system = Some(v);
         self
@@ -130,6 +169,7 @@ impl OptionsBuilder {
         self.threads = cfg.threads.or(self.threads);
         self.compute_physical = cfg.compute_physical.or(self.compute_physical);
         self.approximate_sizes = cfg.approximate_sizes.or(self.approximate_sizes);
+        self.extent_accounting = cfg.extent_accounting.or(self.extent_accounting);
         self.one_file_system = cfg.one_file_system.or(self.one_file_system);
         self.follow_links = cfg.follow_links.or(self.follow_links);
         self.prefer_inner_rayon = cfg.prefer_inner_rayon.or(self.prefer_inner_rayon);
@@ -189,6 +229,9 @@ impl OptionsBuilder {
         if let Some(v) = self.approximate_sizes {
             opt.approximate_sizes = v;
         }
+        if let Some(v) = self.extent_accounting {
+            opt.extent_accounting = v;
+        }
         if let Some(v) = self.one_file_system {
             opt.one_file_system = v;
         }
@@ -228,6 +271,15 @@ impl OptionsBuilder {
         if !self.exclude_glob.is_empty() {
             opt.exclude_glob = self.exclude_glob;
         }
+        if !self.include_regex.is_empty() {
+            opt.include_regex = self.include_regex;
+        }
+        if !self.include_glob.is_empty() {
+            opt.include_glob = self.include_glob;
+        }
+        if !self.filter_expr.is_empty() {
+            opt.filter_expr = self.filter_expr;
+        }
         // Initialize runtime-tunable active_threads to full threads
         opt.active_threads
             .store(opt.threads.max(1), std::sync::atomic::Ordering::Relaxed);
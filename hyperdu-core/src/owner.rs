@@ -0,0 +1,251 @@
+use std::path::{Path, PathBuf};
+
+use ahash::AHashMap as HashMap;
+use dashmap::DashMap;
+
+use crate::{FileEntry, FileVisitor, Stat};
+
+/// Usage split by owning user and group.
+#[derive(Clone, Debug, Default)]
+pub struct OwnerStats {
+    pub users: HashMap<u32, Stat>,
+    pub groups: HashMap<u32, Stat>,
+}
+
+impl OwnerStats {
+    fn add(&mut self, uid: u32, gid: u32, logical: u64, physical: u64) {
+        for s in [
+            self.users.entry(uid).or_default(),
+            self.groups.entry(gid).or_default(),
+        ] {
+            s.logical += logical;
+            s.physical += physical;
+            s.files += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &OwnerStats) {
+        for (k, v) in &other.users {
+            self.users.entry(*k).or_default().merge(v);
+        }
+        for (k, v) in &other.groups {
+            self.groups.entry(*k).or_default().merge(v);
+        }
+    }
+
+    /// Users sorted by physical size, largest first.
+    pub fn users_by_size(&self) -> Vec<(u32, Stat)> {
+        sorted_by_size(&self.users)
+    }
+
+    /// Groups sorted by physical size, largest first.
+    pub fn groups_by_size(&self) -> Vec<(u32, Stat)> {
+        sorted_by_size(&self.groups)
+    }
+}
+
+fn sorted_by_size(m: &HashMap<u32, Stat>) -> Vec<(u32, Stat)> {
+    let mut v: Vec<(u32, Stat)> = m.iter().map(|(k, s)| (*k, *s)).collect();
+    v.sort_unstable_by_key(|(k, s)| (std::cmp::Reverse(s.physical), *k));
+    v
+}
+
+/// Per-owner breakdown of a scan: whole-scan totals plus one rolled-up entry
+/// per directory that has counted files somewhere below it.
+#[derive(Clone, Debug, Default)]
+pub struct OwnerReport {
+    pub total: OwnerStats,
+    pub per_dir: HashMap<PathBuf, OwnerStats>,
+}
+
+/// Visitor that collects file usage per uid/gid and directory.
+///
+/// Attach with `scan_directory_with_visitor` (or `start_scan_with_visitor`) and
+/// call [`OwnerVisitor::report`] after the scan. Files whose owner the backend
+/// did not fetch (approximate sizes, Windows) are not counted.
+#[derive(Default)]
+pub struct OwnerVisitor {
+    dirs: DashMap<PathBuf, OwnerStats>,
+}
+
+impl OwnerVisitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Roll the per-directory counts up to `root` and return the report.
+    pub fn report(&self, root: &Path) -> OwnerReport {
+        let mut per_dir: HashMap<PathBuf, OwnerStats> = self
+            .dirs
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect();
+        crate::rollup::rollup_to_root(&mut per_dir, root, OwnerStats::merge);
+        let total = per_dir.get(root).cloned().unwrap_or_default();
+        OwnerReport { total, per_dir }
+    }
+}
+
+impl FileVisitor for OwnerVisitor {
+    fn visit(&self, entry: &FileEntry<'_>) {
+        if entry.kind != crate::EntryKind::File {
+            return;
+        }
+        let (Some(uid), Some(gid)) = (entry.uid, entry.gid) else {
+            return;
+        };
+        if let Some(mut s) = self.dirs.get_mut(entry.parent) {
+            s.add(uid, gid, entry.logical, entry.physical);
+            return;
+        }
+        self.dirs
+            .entry(entry.parent.to_path_buf())
+            .or_default()
+            .add(uid, gid, entry.logical, entry.physical);
+    }
+}
+
+/// Cached uid/gid to name lookups via the passwd/group databases.
+/// Unknown ids (and every id on Windows) fall back to the number.
+#[derive(Default)]
+pub struct OwnerNames {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl OwnerNames {
+    pub fn user(&mut self, uid: u32) -> &str {
+        self.users
+            .entry(uid)
+            .or_insert_with(|| lookup_user(uid).unwrap_or_else(|| uid.to_string()))
+    }
+
+    pub fn group(&mut self, gid: u32) -> &str {
+        self.groups
+            .entry(gid)
+            .or_insert_with(|| lookup_group(gid).unwrap_or_else(|| gid.to_string()))
+    }
+}
+
+/// Uid of a user name, or the name itself if numeric.
+pub fn user_id(name: &str) -> Option<u32> {
+    name.parse().ok().or_else(|| lookup_user_id(name))
+}
+
+/// Gid of a group name, or the name itself if numeric.
+pub fn group_id(name: &str) -> Option<u32> {
+    name.parse().ok().or_else(|| lookup_group_id(name))
+}
+
+/// Run a `get*_r` style lookup, growing the scratch buffer on ERANGE.
+#[cfg(unix)]
+fn with_name_buf(mut f: impl FnMut(&mut [libc::c_char]) -> libc::c_int) -> Option<()> {
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        match f(&mut buf) {
+            0 => return Some(()),
+            libc::ERANGE if buf.len() < (1 << 20) => buf.resize(buf.len() * 2, 0),
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn lookup_user(uid: u32) -> Option<String> {
+    let mut name = None;
+    with_name_buf(|buf| {
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut res: *mut libc::passwd = std::ptr::null_mut();
+        let rc = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut res) };
+        if rc == 0 && !res.is_null() {
+            let s = unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) };
+            name = Some(s.to_string_lossy().into_owned());
+        }
+        rc
+    })?;
+    name
+}
+
+#[cfg(unix)]
+fn lookup_group(gid: u32) -> Option<String> {
+    let mut name = None;
+    with_name_buf(|buf| {
+        let mut grp: libc::group = unsafe { std::mem::zeroed() };
+        let mut res: *mut libc::group = std::ptr::null_mut();
+        let rc = unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut res) };
+        if rc == 0 && !res.is_null() {
+            let s = unsafe { std::ffi::CStr::from_ptr(grp.gr_name) };
+            name = Some(s.to_string_lossy().into_owned());
+        }
+        rc
+    })?;
+    name
+}
+
+#[cfg(unix)]
+fn lookup_user_id(name: &str) -> Option<u32> {
+    let c_name = std::ffi::CString::new(name).ok()?;
+    let mut uid = None;
+    with_name_buf(|buf| {
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut res: *mut libc::passwd = std::ptr::null_mut();
+        let rc = unsafe {
+            libc::getpwnam_r(
+                c_name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut res,
+            )
+        };
+        if rc == 0 && !res.is_null() {
+            uid = Some(pwd.pw_uid);
+        }
+        rc
+    })?;
+    uid
+}
+
+#[cfg(unix)]
+fn lookup_group_id(name: &str) -> Option<u32> {
+    let c_name = std::ffi::CString::new(name).ok()?;
+    let mut gid = None;
+    with_name_buf(|buf| {
+        let mut grp: libc::group = unsafe { std::mem::zeroed() };
+        let mut res: *mut libc::group = std::ptr::null_mut();
+        let rc = unsafe {
+            libc::getgrnam_r(
+                c_name.as_ptr(),
+                &mut grp,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut res,
+            )
+        };
+        if rc == 0 && !res.is_null() {
+            gid = Some(grp.gr_gid);
+        }
+        rc
+    })?;
+    gid
+}
+
+#[cfg(not(unix))]
+fn lookup_user_id(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+fn lookup_group_id(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+fn lookup_user(_uid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(not(unix))]
+fn lookup_group(_gid: u32) -> Option<String> {
+    None
+}
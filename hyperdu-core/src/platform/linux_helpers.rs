@@ -42,6 +42,12 @@ pub unsafe fn dirent_dtype(ptr: *const u8) -> u8 {
     *ptr.add(18)
 }
 
+/// getdents64: read d_ino field (byte offset 0..7)
+#[inline(always)]
+pub unsafe fn dirent_ino(ptr: *const u8) -> u64 {
+    *(ptr as *const u64)
+}
+
 /// getdents64: read d_off field (byte offset 8..15)
 #[inline(always)]
 pub unsafe fn dirent_d_off(ptr: *const u8) -> u64 {
@@ -107,10 +113,24 @@ pub fn statx_dev(stx: &libc::statx) -> u64 {
     ((stx.stx_dev_major as u64) << 32) | (stx.stx_dev_minor as u64)
 }
 
-/// Check if current device matches parent for one-file-system.
-/// Uses statx on glibc; fstatat metadata fallback on musl.
+/// Device ID from an fstat result, encoded like the child devices it is
+/// compared with: as [`statx_dev`] on glibc, the raw `st_dev` on musl.
+#[inline]
+pub fn stat_dev(st: &libc::stat) -> u64 {
+    #[cfg(not(target_env = "musl"))]
+    {
+        ((libc::major(st.st_dev) as u64) << 32) | (libc::minor(st.st_dev) as u64)
+    }
+    #[cfg(target_env = "musl")]
+    {
+        st.st_dev as u64
+    }
+}
+
+/// Whether directory `name` of `dirfd` is on `parent_dev`, for one-file-system.
+/// Uses statx on glibc; fstatat metadata fallback on musl. Entries that cannot
+/// be stat'ed count as same-device, so opening them reports the error.
 #[inline]
-#[allow(dead_code)]
 pub fn check_one_file_system(
     dirfd: libc::c_int,
     name: &[u8],
@@ -123,17 +143,22 @@ pub fn check_one_file_system(
 
     #[cfg(not(target_env = "musl"))]
     {
-        if let Some(stx) = do_statx(dirfd, name, libc::AT_SYMLINK_NOFOLLOW, libc::STATX_INO) {
-            return statx_dev(&stx) == parent_dev;
+        let mut flags = libc::AT_SYMLINK_NOFOLLOW;
+        if !matches!(
+            opt.compat_mode,
+            crate::CompatMode::GnuStrict | crate::CompatMode::PosixStrict
+        ) {
+            flags |= libc::AT_NO_AUTOMOUNT;
         }
-        false
+        do_statx(dirfd, name, flags, libc::STATX_INO)
+            .map_or(true, |stx| statx_dev(&stx) == parent_dev)
     }
 
     #[cfg(target_env = "musl")]
     {
         let c_name = match CString::new(name) {
             Ok(s) => s,
-            Err(_) => return false,
+            Err(_) => return true,
         };
         let mut st: libc::stat = unsafe { std::mem::zeroed() };
         let rc = unsafe {
@@ -144,11 +169,7 @@ pub fn check_one_file_system(
                 libc::AT_SYMLINK_NOFOLLOW,
             )
         };
-        if rc == 0 {
-            let dev = st.st_dev as u64;
-            return dev == parent_dev;
-        }
-        false
+        rc != 0 || st.st_dev as u64 == parent_dev
     }
 }
 
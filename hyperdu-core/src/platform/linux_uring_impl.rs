@@ -1,11 +1,23 @@
 // Minimal io_uring STATX pipeline. Falls back to stable implementation if unavailable.
 use std::{
-    cell::RefCell, collections::VecDeque, ffi::CString, os::unix::ffi::OsStrExt, time::Instant,
+    cell::RefCell,
+    collections::VecDeque,
+    ffi::{CString, OsStr},
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    time::Instant,
 };
 
 use io_uring::{opcode, IoUring};
 
-use crate::{memory_pool::BufferGuard, DirContext, ScanContext, StatMap};
+use crate::{
+    common_ops::{
+        calculate_physical_size, check_hardlink_duplicate, entry_statx_mask, metadata_entry,
+        report_file_progress, statx_entry,
+    },
+    memory_pool::BufferGuard,
+    DirContext, EntryKind, FileEntry, ScanContext, Stat, StatMap,
+};
 
 struct RingCtx {
     ring: IoUring,
@@ -80,10 +92,9 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
     }
     let fd = unsafe { libc::open(c_path.as_ptr(), open_flags) };
     if fd < 0 {
-        crate::error_handling::record_error(
-            opt,
-            &crate::error_handling::last_os_error_systemcall(dir, "open"),
-        );
+        ctx.record_dir_error(&crate::error_handling::last_os_error_systemcall(
+            dir, "open",
+        ));
         return;
     }
     if let Some(off) = resume {
@@ -96,7 +107,7 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
     let mut st_cur: libc::stat = unsafe { std::mem::zeroed() };
     let cur_dev: u64 = unsafe {
         if libc::fstat(fd, &mut st_cur as *mut _) == 0 {
-            st_cur.st_dev
+            crate::platform::linux_helpers::stat_dev(&st_cur)
         } else {
             0
         }
@@ -104,6 +115,7 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
 
     let stat_cur = map.entry(dir.to_path_buf()).or_default();
     let files_before = stat_cur.files;
+    let set_aside_before = ctx.set_aside.get();
     // getdents64 buffer via RAII thread-local pool to avoid reallocs
     fn buf_size() -> usize {
         if let Ok(s) = std::env::var("HYPERDU_GETDENTS_BUF_KB") {
@@ -117,16 +129,21 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
     let buf = guard.as_mut_slice();
 
     // Window size and slot arrays
-    let sq_depth = opt
-        .uring_sq_depth
-        .load(std::sync::atomic::Ordering::Relaxed)
-        .max(1);
+    let sq_depth = crate::throttle::cap_inflight(
+        opt.uring_max_inflight,
+        opt.uring_sq_depth
+            .load(std::sync::atomic::Ordering::Relaxed)
+            .max(1),
+    );
     let batch_cfg = opt
         .uring_batch
         .load(std::sync::atomic::Ordering::Relaxed)
         .max(1);
     let mut window = sq_depth; // in-flight target equals SQ depth (may adapt on SQE pressure)
-    let mut results: Vec<io_uring::types::statx> =
+
+    // io_uring::types::statx is an opaque zero-sized type; back the slots with the
+    // real libc layout so the kernel has somewhere to write.
+    let mut results: Vec<libc::statx> =
         (0..window).map(|_| unsafe { std::mem::zeroed() }).collect();
     let mut items: Vec<Option<(CString, u8)>> = (0..window).map(|_| None).collect();
     let mut free: Vec<usize> = (0..window).rev().collect(); // stack of free slot indices
@@ -142,6 +159,7 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
     if need_ino {
         mask |= libc::STATX_INO;
     }
+    mask |= entry_statx_mask(ctx);
     let mut flags = if opt.follow_links {
         0
     } else {
@@ -179,7 +197,9 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                     let Some(slot) = free.pop() else { break };
                     items[slot] = Some((name, dt));
                     let (ref nm, _dt) = items[slot].as_ref().unwrap();
-                    let statxbuf: *mut io_uring::types::statx = (&mut results[slot]) as *mut _;
+                    ctx.throttle_io(1);
+                    let statxbuf =
+                        (&mut results[slot]) as *mut libc::statx as *mut io_uring::types::statx;
                     let sqe = opcode::Statx::new(io_uring::types::Fd(fd), nm.as_ptr(), statxbuf)
                         .mask(mask)
                         .flags(flags)
@@ -219,113 +239,18 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                     }
                     let slot = cqe.user_data() as usize;
-                    if res >= 0 && slot < items.len() {
-                        if let Some((ref nm, dt)) = items[slot] {
-                            let stx: libc::statx = unsafe {
-                                std::ptr::read_unaligned(
-                                    (&results[slot]) as *const _ as *const libc::statx,
-                                )
-                            };
-                            let mode = stx.stx_mode as u32;
-                            let ftype = mode & libc::S_IFMT;
-                            if ftype == libc::S_IFDIR || (ftype == 0 && dt == libc::DT_DIR) {
-                                if opt.max_depth == 0 || depth < opt.max_depth {
-                                    use std::ffi::OsStr;
-                                    let child = dir.join(OsStr::from_bytes(nm.as_bytes()));
-                                    if crate::filters::path_excluded(&child, opt) {
-                                        // skip
-                                    } else if opt.one_file_system {
-                                        let child_dev = ((stx.stx_dev_major as u64) << 32)
-                                            | (stx.stx_dev_minor as u64);
-                                        if child_dev == cur_dev {
-                                            ctx.normal_injector.push(crate::Job {
-                                                dir: child,
-                                                depth: depth + 1,
-                                                resume: None,
-                                            });
-                                        }
-                                    } else {
-                                        ctx.enqueue_dir(child, depth + 1);
-                                    }
-                                }
-                            } else if ftype == libc::S_IFREG
-                                || (opt.follow_links && ftype == libc::S_IFLNK)
-                                || (ftype == 0 && dt == libc::DT_REG)
-                            {
-                                if ftype == libc::S_IFREG {
-                                    let dev = ((stx.stx_dev_major as u64) << 32)
-                                        | (stx.stx_dev_minor as u64);
-                                    let ino = stx.stx_ino;
-                                    if crate::common_ops::check_hardlink_duplicate(opt, dev, ino) {
-                                        items[slot] = None;
-                                        free.push(slot);
-                                        inflight -= 1;
-                                        completed += 1;
-                                        continue;
-                                    }
-                                }
-                                let logical = stx.stx_size;
-                                use std::ffi::OsStr;
-                                let child = dir.join(OsStr::from_bytes(nm.as_bytes()));
-                                if logical >= opt.min_file_size {
-                                    let physical = crate::common_ops::calculate_physical_size(
-                                        opt,
-                                        logical,
-                                        stx.stx_blocks,
-                                    );
-                                    crate::common_ops::update_file_stats(
-                                        stat_cur, logical, physical,
-                                    );
-                                    crate::common_ops::report_file_progress(
-                                        opt,
-                                        ctx.total_files,
-                                        Some(&child),
-                                    );
-                                } else if ftype == 0 {
-                                    // immediate fallback when type info is missing
-                                    if let Ok(md) = std::fs::symlink_metadata(&child) {
-                                        if md.file_type().is_file() {
-                                            let l = md.len();
-                                            if l >= opt.min_file_size {
-                                                crate::common_ops::update_file_stats(
-                                                    stat_cur, l, l,
-                                                );
-                                                crate::common_ops::report_file_progress(
-                                                    opt,
-                                                    ctx.total_files,
-                                                    Some(&child),
-                                                );
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    } else if res < 0 && slot < items.len() {
-                        // STATX failed: try metadata-based fallback
-                        if let Some((ref nm, dt)) = items[slot] {
-                            use std::ffi::OsStr;
-                            let child = dir.join(OsStr::from_bytes(nm.as_bytes()));
-                            if dt == libc::DT_DIR {
-                                if (opt.max_depth == 0 || depth < opt.max_depth)
-                                    && !crate::filters::path_excluded(&child, opt)
-                                {
-                                    ctx.enqueue_dir(child, depth + 1);
-                                }
-                            } else if let Ok(md) = std::fs::symlink_metadata(&child) {
-                                if md.file_type().is_file() {
-                                    let l = md.len();
-                                    if l >= opt.min_file_size {
-                                        crate::common_ops::update_file_stats(stat_cur, l, l);
-                                        crate::common_ops::report_file_progress(
-                                            opt,
-                                            ctx.total_files,
-                                            Some(&child),
-                                        );
-                                    }
-                                }
-                            }
-                        }
+                    if let Some((nm, dt)) = items.get(slot).and_then(|it| it.as_ref()) {
+                        handle_statx_result(
+                            ctx,
+                            dir,
+                            depth,
+                            cur_dev,
+                            stat_cur,
+                            res,
+                            nm,
+                            *dt,
+                            &results[slot],
+                        );
                     }
                     items[slot] = None;
                     free.push(slot);
@@ -347,6 +272,7 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
             }
         }
 
+        ctx.throttle_io(1);
         nread = unsafe {
             syscall(
                 SYS_GETDENTS64,
@@ -355,6 +281,12 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                 buf.len(),
             )
         } as isize;
+        if nread < 0 {
+            ctx.record_dir_error(&crate::error_handling::last_os_error_systemcall(
+                dir,
+                "getdents64",
+            ));
+        }
         if nread <= 0 {
             break;
         }
@@ -370,9 +302,25 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                 continue;
             }
             if dtype == libc::DT_DIR {
-                if opt.max_depth == 0 || depth < opt.max_depth {
-                    let child = dir.join(std::ffi::OsStr::from_bytes(name_slice));
-                    ctx.enqueue_dir(child, depth + 1);
+                let name = OsStr::from_bytes(name_slice);
+                let child = dir.join(name);
+                // Excluded directories are neither reported nor descended into
+                if (opt.max_depth == 0 || depth < opt.max_depth)
+                    && !crate::filters::path_excluded(&child, opt)
+                {
+                    if opt.one_file_system {
+                        ctx.throttle_io(1);
+                    }
+                    if crate::platform::linux_helpers::check_one_file_system(
+                        fd, name_slice, cur_dev, opt,
+                    ) {
+                        let ino = unsafe { crate::platform::linux_helpers::dirent_ino(ptr) };
+                        ctx.visit(&FileEntry {
+                            ino,
+                            ..FileEntry::new(dir, name, EntryKind::Dir)
+                        });
+                        ctx.enqueue_dir(child, depth + 1);
+                    }
                 }
                 bpos += reclen;
                 continue;
@@ -390,7 +338,9 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                     let Some(slot) = free.pop() else { break };
                     items[slot] = Some((name, dt));
                     let (ref nm, _dt) = items[slot].as_ref().unwrap();
-                    let statxbuf: *mut io_uring::types::statx = (&mut results[slot]) as *mut _;
+                    ctx.throttle_io(1);
+                    let statxbuf =
+                        (&mut results[slot]) as *mut libc::statx as *mut io_uring::types::statx;
                     let sqe = opcode::Statx::new(io_uring::types::Fd(fd), nm.as_ptr(), statxbuf)
                         .mask(mask)
                         .flags(flags)
@@ -427,79 +377,18 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                     }
                     let slot = cqe.user_data() as usize;
-                    if res >= 0 && slot < items.len() {
-                        if let Some((ref nm, _dt)) = items[slot] {
-                            let stx: libc::statx = unsafe {
-                                std::ptr::read_unaligned(
-                                    (&results[slot]) as *const _ as *const libc::statx,
-                                )
-                            };
-                            let mode = stx.stx_mode as u32;
-                            let ftype = mode & libc::S_IFMT;
-                            if ftype == libc::S_IFDIR {
-                                if opt.max_depth == 0 || depth < opt.max_depth {
-                                    use std::ffi::OsStr;
-                                    let child = dir.join(OsStr::from_bytes(nm.as_bytes()));
-                                    if opt.one_file_system {
-                                        let child_dev = ((stx.stx_dev_major as u64) << 32)
-                                            | (stx.stx_dev_minor as u64);
-                                        if child_dev == cur_dev {
-                                            ctx.normal_injector.push(crate::Job {
-                                                dir: child,
-                                                depth: depth + 1,
-                                                resume: None,
-                                            });
-                                        }
-                                    } else {
-                                        ctx.enqueue_dir(child, depth + 1);
-                                    }
-                                }
-                            } else if ftype == libc::S_IFREG
-                                || (opt.follow_links && ftype == libc::S_IFLNK)
-                            {
-                                if ftype == libc::S_IFREG && !opt.count_hardlinks {
-                                    if let Some(cache) = &opt.inode_cache {
-                                        let dev = ((stx.stx_dev_major as u64) << 32)
-                                            | (stx.stx_dev_minor as u64);
-                                        let ino = stx.stx_ino;
-                                        if cache.insert((dev, ino), ()).is_some() {
-                                            items[slot] = None;
-                                            free.push(slot);
-                                            inflight -= 1;
-                                            completed += 1;
-                                            continue;
-                                        }
-                                    }
-                                }
-                                let logical = stx.stx_size;
-                                if logical >= opt.min_file_size {
-                                    let physical = if opt.compute_physical {
-                                        let pr = stx.stx_blocks * 512u64;
-                                        if pr == 0 {
-                                            logical
-                                        } else {
-                                            pr
-                                        }
-                                    } else {
-                                        logical
-                                    };
-                                    stat_cur.logical += logical;
-                                    stat_cur.physical += physical;
-                                    stat_cur.files += 1;
-                                    if opt.progress_every > 0 {
-                                        let n = ctx
-                                            .total_files
-                                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
-                                            + 1;
-                                        if n % opt.progress_every == 0 {
-                                            if let Some(cb) = &opt.progress_callback {
-                                                cb(n);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+                    if let Some((nm, dt)) = items.get(slot).and_then(|it| it.as_ref()) {
+                        handle_statx_result(
+                            ctx,
+                            dir,
+                            depth,
+                            cur_dev,
+                            stat_cur,
+                            res,
+                            nm,
+                            *dt,
+                            &results[slot],
+                        );
                     }
                     items[slot] = None;
                     free.push(slot);
@@ -513,7 +402,6 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
         }
     }
     // Final drain
-    // Final drain
     let t0 = Instant::now();
     while inflight > 0 || !pending.is_empty() {
         {
@@ -525,7 +413,9 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                 let Some(slot) = free.pop() else { break };
                 items[slot] = Some((name, dt));
                 let (ref nm, _dt) = items[slot].as_ref().unwrap();
-                let statxbuf: *mut io_uring::types::statx = (&mut results[slot]) as *mut _;
+                ctx.throttle_io(1);
+                let statxbuf =
+                    (&mut results[slot]) as *mut libc::statx as *mut io_uring::types::statx;
                 let sqe = opcode::Statx::new(io_uring::types::Fd(fd), nm.as_ptr(), statxbuf)
                     .mask(mask)
                     .flags(flags)
@@ -551,107 +441,18 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                         .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                 }
                 let slot = cqe.user_data() as usize;
-                if res >= 0 && slot < items.len() {
-                    if let Some((ref nm, dt)) = items[slot] {
-                        let stx: libc::statx = unsafe {
-                            std::ptr::read_unaligned(
-                                (&results[slot]) as *const _ as *const libc::statx,
-                            )
-                        };
-                        let mode = stx.stx_mode as u32;
-                        let ftype = mode & libc::S_IFMT;
-                        if ftype == libc::S_IFDIR || (ftype == 0 && dt == libc::DT_DIR) {
-                            if opt.max_depth == 0 || depth < opt.max_depth {
-                                use std::ffi::OsStr;
-                                let child = dir.join(OsStr::from_bytes(nm.as_bytes()));
-                                if crate::filters::path_excluded(&child, opt) {
-                                    // skip
-                                } else if opt.one_file_system {
-                                    let child_dev = ((stx.stx_dev_major as u64) << 32)
-                                        | (stx.stx_dev_minor as u64);
-                                    if child_dev == cur_dev {
-                                        ctx.normal_injector.push(crate::Job {
-                                            dir: child,
-                                            depth: depth + 1,
-                                            resume: None,
-                                        });
-                                    }
-                                } else {
-                                    ctx.enqueue_dir(child, depth + 1);
-                                }
-                            }
-                        } else if ftype == libc::S_IFREG
-                            || (opt.follow_links && ftype == libc::S_IFLNK)
-                            || (ftype == 0 && dt == libc::DT_REG)
-                        {
-                            if ftype == libc::S_IFREG {
-                                let dev =
-                                    ((stx.stx_dev_major as u64) << 32) | (stx.stx_dev_minor as u64);
-                                let ino = stx.stx_ino;
-                                if crate::common_ops::check_hardlink_duplicate(opt, dev, ino) {
-                                    items[slot] = None;
-                                    free.push(slot);
-                                    inflight -= 1;
-                                    completed += 1;
-                                    continue;
-                                }
-                            }
-                            use std::ffi::OsStr;
-                            let child = dir.join(OsStr::from_bytes(nm.as_bytes()));
-                            let logical = stx.stx_size;
-                            if logical >= opt.min_file_size {
-                                let physical = crate::common_ops::calculate_physical_size(
-                                    opt,
-                                    logical,
-                                    stx.stx_blocks,
-                                );
-                                crate::common_ops::update_file_stats(stat_cur, logical, physical);
-                                crate::common_ops::report_file_progress(
-                                    opt,
-                                    ctx.total_files,
-                                    Some(&child),
-                                );
-                            } else if ftype == 0 {
-                                if let Ok(md) = std::fs::symlink_metadata(&child) {
-                                    if md.file_type().is_file() {
-                                        let l = md.len();
-                                        if l >= opt.min_file_size {
-                                            crate::common_ops::update_file_stats(stat_cur, l, l);
-                                            crate::common_ops::report_file_progress(
-                                                opt,
-                                                ctx.total_files,
-                                                Some(&child),
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                } else if res < 0 && slot < items.len() {
-                    if let Some((ref nm, dt)) = items[slot] {
-                        use std::ffi::OsStr;
-                        let child = dir.join(OsStr::from_bytes(nm.as_bytes()));
-                        if dt == libc::DT_DIR {
-                            if (opt.max_depth == 0 || depth < opt.max_depth)
-                                && !crate::filters::path_excluded(&child, opt)
-                            {
-                                ctx.enqueue_dir(child, depth + 1);
-                            }
-                        } else if let Ok(md) = std::fs::symlink_metadata(&child) {
-                            if md.file_type().is_file() {
-                                let l = md.len();
-                                if l >= opt.min_file_size {
-                                    crate::common_ops::update_file_stats(stat_cur, l, l);
-                                    crate::common_ops::report_file_progress(
-                                        opt,
-                                        ctx.total_files,
-                                        Some(&child),
-                                    );
-                                }
-                            }
-                        }
-                    }
+                if let Some((nm, dt)) = items.get(slot).and_then(|it| it.as_ref()) {
+                    handle_statx_result(
+                        ctx,
+                        dir,
+                        depth,
+                        cur_dev,
+                        stat_cur,
+                        res,
+                        nm,
+                        *dt,
+                        &results[slot],
+                    );
                 }
                 items[slot] = None;
                 free.push(slot);
@@ -682,7 +483,8 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
     // Fallback: If we attempted to stat non-directory entries (enq>0) but ended up
     // recognizing no files for this directory (files unchanged), re-scan this
     // directory with a conservative per-entry stat approach and metadata fallback.
-    if enq > 0 && stat_cur.files == files_before {
+    // Files set aside as ignored or excluded were recognized too.
+    if enq > 0 && stat_cur.files == files_before && ctx.set_aside.get() == set_aside_before {
         // Re-open directory and iterate non-directory entries only.
         let c_path = match CString::new(dir.as_os_str().as_bytes()) {
             Ok(s) => s,
@@ -701,6 +503,7 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
             let mut guard2 = BufferGuard::borrow(buf_size());
             let buf2 = guard2.as_mut_slice();
             loop {
+                ctx.throttle_io(1);
                 let nread2 = unsafe {
                     syscall(
                         SYS_GETDENTS64,
@@ -724,7 +527,6 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                         continue;
                     }
                     // Build full path for filtering and optional progress-path callback
-                    use std::ffi::OsStr;
                     let child_path = dir.join(OsStr::from_bytes(name_slice));
                     if crate::filters::path_excluded(&child_path, opt) {
                         continue;
@@ -734,9 +536,8 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                     }
 
                     // Try statx relative to dirfd first
-                    let mut logical: u64 = 0;
-                    let mut physical: u64 = 0;
-                    let mut ok_file = false;
+                    let name = OsStr::from_bytes(name_slice);
+                    let mut entry: Option<FileEntry> = None;
                     if let Ok(cn) = CString::new(name_slice) {
                         let mut stx: libc::statx = unsafe { std::mem::zeroed() };
                         let mut flags = if opt.follow_links {
@@ -758,45 +559,59 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
                         if opt.compute_physical {
                             mask |= libc::STATX_BLOCKS;
                         }
+                        if !opt.count_hardlinks {
+                            mask |= libc::STATX_INO;
+                        }
+                        mask |= entry_statx_mask(ctx);
+                        ctx.throttle_io(1);
                         let rc = unsafe { libc::statx(fd2, cn.as_ptr(), flags, mask, &mut stx) };
                         if rc == 0 {
                             let mode = stx.stx_mode as u32;
                             let ftype = mode & libc::S_IFMT;
+                            // Hardlinks deduped by the first pass (or another
+                            // directory) are already accounted for
+                            let dev =
+                                ((stx.stx_dev_major as u64) << 32) | (stx.stx_dev_minor as u64);
+                            if ftype == libc::S_IFREG
+                                && check_hardlink_duplicate(opt, dev, stx.stx_ino)
+                            {
+                                continue;
+                            }
                             if ftype == libc::S_IFREG
                                 || (opt.follow_links && ftype == libc::S_IFLNK)
                                 || ftype == 0
                             {
-                                logical = stx.stx_size;
+                                let logical = stx.stx_size;
                                 if logical >= opt.min_file_size {
-                                    physical = crate::common_ops::calculate_physical_size(
-                                        opt,
+                                    let physical =
+                                        calculate_physical_size(opt, logical, stx.stx_blocks);
+                                    entry = Some(statx_entry(
+                                        dir,
+                                        name_slice,
+                                        EntryKind::File,
+                                        &stx,
                                         logical,
-                                        stx.stx_blocks,
-                                    );
-                                    ok_file = true;
+                                        physical,
+                                    ));
                                 }
                             }
                         }
                     }
                     // Fallback to metadata if statx was unusable
-                    if !ok_file {
+                    if entry.is_none() {
                         if let Ok(md) = std::fs::symlink_metadata(&child_path) {
                             if md.file_type().is_file() {
-                                logical = md.len();
+                                let logical = md.len();
                                 if logical >= opt.min_file_size {
-                                    physical = logical; // best-effort without blocks
-                                    ok_file = true;
+                                    // best-effort without blocks
+                                    entry = Some(metadata_entry(dir, name, &md, logical, logical));
                                 }
                             }
                         }
                     }
-                    if ok_file {
-                        crate::common_ops::update_file_stats(stat_cur, logical, physical);
-                        crate::common_ops::report_file_progress(
-                            opt,
-                            ctx.total_files,
-                            Some(&child_path),
-                        );
+                    if let Some(entry) = entry {
+                        ctx.record_file(stat_cur, &entry);
+                        report_file_progress(opt, ctx.total_files, Some(&child_path));
                     }
                 }
             }
@@ -805,3 +620,94 @@ fn process_with_ring(ring: &mut IoUring, ctx: &ScanContext, dctx: &DirContext, m
     }
     unsafe { libc::close(fd) };
 }
+
+/// Apply one STATX completion for entry `nm` of `dir`: enqueue directories,
+/// count regular files, and fall back to `symlink_metadata` when the kernel
+/// returned an error or no type information.
+#[allow(clippy::too_many_arguments)]
+fn handle_statx_result(
+    ctx: &ScanContext,
+    dir: &Path,
+    depth: u32,
+    cur_dev: u64,
+    stat_cur: &mut Stat,
+    res: i32,
+    nm: &CString,
+    dt: u8,
+    stx: &libc::statx,
+) {
+    let opt = ctx.options;
+    let name = OsStr::from_bytes(nm.as_bytes());
+    let descend = opt.max_depth == 0 || depth < opt.max_depth;
+    if res < 0 {
+        // STATX failed: try metadata-based fallback
+        let child = dir.join(name);
+        if dt == libc::DT_DIR {
+            if descend && !crate::filters::path_excluded(&child, opt) {
+                ctx.visit(&FileEntry::new(dir, name, EntryKind::Dir));
+                ctx.enqueue_dir(child, depth + 1);
+            }
+        } else if let Ok(md) = std::fs::symlink_metadata(&child) {
+            if md.file_type().is_file() {
+                let l = md.len();
+                if l >= opt.min_file_size {
+                    ctx.record_file(stat_cur, &metadata_entry(dir, name, &md, l, l));
+                    report_file_progress(opt, ctx.total_files, Some(&child));
+                }
+            }
+        }
+        return;
+    }
+    let ftype = stx.stx_mode as u32 & libc::S_IFMT;
+    if ftype == libc::S_IFDIR || (ftype == 0 && dt == libc::DT_DIR) {
+        if !descend {
+            return;
+        }
+        let child = dir.join(name);
+        if crate::filters::path_excluded(&child, opt) {
+            return;
+        }
+        if opt.one_file_system {
+            let child_dev = ((stx.stx_dev_major as u64) << 32) | (stx.stx_dev_minor as u64);
+            if child_dev != cur_dev {
+                return;
+            }
+        }
+        ctx.visit(&statx_entry(dir, nm.as_bytes(), EntryKind::Dir, stx, 0, 0));
+        ctx.enqueue_dir(child, depth + 1);
+    } else if crate::common_ops::counts_as_file(opt, ftype) || (ftype == 0 && dt == libc::DT_REG) {
+        if ftype != 0 && ftype != libc::S_IFLNK {
+            let dev = ((stx.stx_dev_major as u64) << 32) | (stx.stx_dev_minor as u64);
+            if check_hardlink_duplicate(opt, dev, stx.stx_ino) {
+                crate::common_ops::record_statx_link(ctx, dir, nm.as_bytes(), stx);
+                return;
+            }
+        }
+        let child = dir.join(name);
+        let logical = stx.stx_size;
+        if logical >= opt.min_file_size {
+            let physical = calculate_physical_size(opt, logical, stx.stx_blocks);
+            ctx.record_file(
+                stat_cur,
+                &statx_entry(dir, nm.as_bytes(), EntryKind::File, stx, logical, physical),
+            );
+            report_file_progress(opt, ctx.total_files, Some(&child));
+        } else if ftype == 0 {
+            // immediate fallback when type info is missing
+            if let Ok(md) = std::fs::symlink_metadata(&child) {
+                if md.file_type().is_file() {
+                    let l = md.len();
+                    if l >= opt.min_file_size {
+                        ctx.record_file(stat_cur, &metadata_entry(dir, name, &md, l, l));
+                        report_file_progress(opt, ctx.total_files, Some(&child));
+                    }
+                }
+            }
+        }
+    } else if ftype == libc::S_IFLNK {
+        ctx.record_symlink(
+            stat_cur,
+            &statx_entry(dir, nm.as_bytes(), EntryKind::Symlink, stx, 0, 0),
+        );
+    }
+}
@@ -1,13 +1,17 @@
 use std::sync::atomic::Ordering;
 
+#[cfg(target_env = "musl")]
+use crate::common_ops::metadata_entry;
+#[cfg(not(target_env = "musl"))]
+use crate::common_ops::{entry_statx_mask, metadata_entry, record_statx_link, statx_entry};
 use crate::{
     common_ops::{
         calculate_physical_size, check_hardlink_duplicate, check_visited_directory,
-        should_fast_exclude, update_file_stats,
+        should_fast_exclude,
     },
-    error_handling::{last_os_error_systemcall, record_error},
+    error_handling::last_os_error_systemcall,
     memory_pool::BufferGuard,
-    name_matches, DirContext, ScanContext, StatMap,
+    name_matches, DirContext, EntryKind, FileEntry, ScanContext, StatMap,
 };
 
 pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
@@ -15,7 +19,10 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
     let depth = dctx.depth;
     let resume = dctx.resume;
     let opt = ctx.options;
-    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+    use std::{
+        ffi::{CString, OsStr},
+        os::unix::ffi::OsStrExt,
+    };
 
     const SYS_GETDENTS64: libc::c_long = 217; // x86_64
                                               // Fast-path: if exclude patterns contain no path separators, we can
@@ -32,14 +39,14 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
     }
     let fd = unsafe { libc::open(c_path.as_ptr(), open_flags) };
     if fd < 0 {
-        record_error(opt, &last_os_error_systemcall(dir, "open"));
+        ctx.record_dir_error(&last_os_error_systemcall(dir, "open"));
         return;
     }
     // Current directory device id for one-file-system check
     let mut st_cur: libc::stat = unsafe { std::mem::zeroed() };
     let cur_dev: u64 = unsafe {
         if libc::fstat(fd, &mut st_cur as *mut _) == 0 {
-            st_cur.st_dev
+            crate::platform::linux_helpers::stat_dev(&st_cur)
         } else {
             0
         }
@@ -81,6 +88,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
     loop {
         #[cfg(any(feature = "prof-tracy", feature = "prof-puffin"))]
         profiling::scope!("getdents64_loop");
+        ctx.throttle_io(1);
         let nread = unsafe {
             libc::syscall(
                 SYS_GETDENTS64,
@@ -89,6 +97,9 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                 buf.len(),
             )
         } as isize;
+        if nread < 0 {
+            ctx.record_dir_error(&last_os_error_systemcall(dir, "getdents64"));
+        }
         if nread <= 0 {
             break;
         }
@@ -106,6 +117,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
             let d_off = unsafe { crate::platform::linux_helpers::dirent_d_off(ptr) };
             let d_reclen = unsafe { crate::platform::linux_helpers::dirent_reclen(ptr) };
             let d_type = unsafe { crate::platform::linux_helpers::dirent_dtype(ptr) };
+            let d_ino = unsafe { crate::platform::linux_helpers::dirent_ino(ptr) };
             let name_slice =
                 unsafe { crate::platform::linux_helpers::dirent_name_slice(ptr, d_reclen) };
             if name_slice == b"." || name_slice == b".." {
@@ -122,7 +134,6 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
             let is_lnk = dtype == libc::DT_LNK;
 
             if !fast_exclude {
-                use std::ffi::OsStr;
                 let child_path = dir.join(OsStr::from_bytes(name_slice));
                 if crate::path_excluded(&child_path, opt) {
                     bpos += d_reclen;
@@ -130,62 +141,27 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                 }
             }
             if is_lnk && !opt.follow_links {
+                ctx.record_symlink(
+                    stat_cur,
+                    &FileEntry {
+                        ino: d_ino,
+                        ..FileEntry::new(dir, OsStr::from_bytes(name_slice), EntryKind::Symlink)
+                    },
+                );
                 bpos += d_reclen;
                 continue;
             }
 
             if is_dir_hint {
                 if opt.max_depth == 0 || depth < opt.max_depth {
-                    use std::ffi::OsStr;
                     // one-file-system: compare child dev to current dev
                     if opt.one_file_system {
-                        #[cfg(not(target_env = "musl"))]
-                        {
-                            // Use statx to fetch device id once (comparable cost to fstatat)
-                            let mut stx: libc::statx = unsafe { std::mem::zeroed() };
-                            if let Ok(cn) = CString::new(name_slice) {
-                                let mut flags = libc::AT_SYMLINK_NOFOLLOW;
-                                if !matches!(
-                                    opt.compat_mode,
-                                    crate::CompatMode::GnuStrict | crate::CompatMode::PosixStrict
-                                ) {
-                                    flags |= libc::AT_STATX_DONT_SYNC;
-                                    #[cfg(target_os = "linux")]
-                                    {
-                                        flags |= libc::AT_NO_AUTOMOUNT;
-                                    }
-                                }
-                                let rc = unsafe {
-                                    libc::statx(
-                                        fd,
-                                        cn.as_ptr(),
-                                        flags,
-                                        libc::STATX_TYPE | libc::STATX_INO | libc::STATX_MODE,
-                                        &mut stx,
-                                    )
-                                };
-                                if rc == 0 {
-                                    let child_dev = ((stx.stx_dev_major as u64) << 32)
-                                        | (stx.stx_dev_minor as u64);
-                                    if child_dev != cur_dev {
-                                        bpos += d_reclen;
-                                        continue;
-                                    }
-                                }
-                            }
-                        }
-                        #[cfg(target_env = "musl")]
-                        {
-                            // Fallback: use std metadata (best-effort)
-                            use std::os::unix::fs::MetadataExt;
-                            let child_path = dir.join(OsStr::from_bytes(name_slice));
-                            if let Ok(md) = std::fs::symlink_metadata(&child_path) {
-                                let child_dev = md.dev() as u64;
-                                if child_dev != cur_dev {
-                                    bpos += d_reclen;
-                                    continue;
-                                }
-                            }
+                        ctx.throttle_io(1);
+                        if !crate::platform::linux_helpers::check_one_file_system(
+                            fd, name_slice, cur_dev, opt,
+                        ) {
+                            bpos += d_reclen;
+                            continue;
                         }
                     }
                     // Symlink loop detection (optional)
@@ -205,6 +181,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                                         flags |= libc::AT_NO_AUTOMOUNT;
                                     }
                                 }
+                                ctx.throttle_io(1);
                                 let rc = unsafe {
                                     libc::statx(
                                         fd,
@@ -229,6 +206,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                             // Fallback: use std metadata to approximate loop detection
                             use std::os::unix::fs::MetadataExt;
                             let child_path = dir.join(OsStr::from_bytes(name_slice));
+                            ctx.throttle_io(1);
                             if let Ok(md) = std::fs::symlink_metadata(&child_path) {
                                 let dev = cur_dev;
                                 let ino = md.ino() as u64;
@@ -240,14 +218,25 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                         }
                     }
                     let child_path = dir.join(OsStr::from_bytes(name_slice));
+                    ctx.visit(&FileEntry {
+                        ino: d_ino,
+                        ..FileEntry::new(dir, OsStr::from_bytes(name_slice), EntryKind::Dir)
+                    });
                     ctx.enqueue_dir(child_path, depth + 1);
                 }
             } else if dtype == libc::DT_REG {
                 // Approximate size path to avoid statx when allowed
                 if !opt.compute_physical && opt.approximate_sizes && opt.min_file_size == 0 {
                     let logical = 4096u64; // estimate 4KiB per regular file
-                    update_file_stats(stat_cur, logical, logical);
-                    use std::ffi::OsStr;
+                    ctx.record_file(
+                        stat_cur,
+                        &FileEntry {
+                            logical,
+                            physical: logical,
+                            ino: d_ino,
+                            ..FileEntry::new(dir, OsStr::from_bytes(name_slice), EntryKind::File)
+                        },
+                    );
                     let child_path = dir.join(OsStr::from_bytes(name_slice));
                     ctx.report_progress(opt, Some(&child_path));
                 } else {
@@ -289,6 +278,8 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                         if need_ino {
                             mask |= libc::STATX_INO;
                         }
+                        mask |= entry_statx_mask(ctx);
+                        ctx.throttle_io(1);
                         let rc = unsafe { libc::statx(fd, c_name.as_ptr(), flags, mask, &mut stx) };
                         if rc == 0 {
                             // Hardlink dedupe (strict modes)
@@ -296,6 +287,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                                 ((stx.stx_dev_major as u64) << 32) | (stx.stx_dev_minor as u64);
                             let ino = stx.stx_ino;
                             if check_hardlink_duplicate(opt, dev, ino) {
+                                record_statx_link(ctx, dir, name_slice, &stx);
                                 bpos += d_reclen;
                                 continue;
                             }
@@ -303,8 +295,17 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                             if logical >= opt.min_file_size {
                                 let physical =
                                     calculate_physical_size(opt, logical, stx.stx_blocks);
-                                update_file_stats(stat_cur, logical, physical);
-                                use std::ffi::OsStr;
+                                ctx.record_file(
+                                    stat_cur,
+                                    &statx_entry(
+                                        dir,
+                                        name_slice,
+                                        EntryKind::File,
+                                        &stx,
+                                        logical,
+                                        physical,
+                                    ),
+                                );
                                 let child_path = dir.join(OsStr::from_bytes(name_slice));
                                 ctx.report_progress(opt, Some(&child_path));
                             }
@@ -312,14 +313,18 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                     }
                     #[cfg(target_env = "musl")]
                     {
-                        use std::ffi::OsStr;
-                        let child_path = dir.join(OsStr::from_bytes(name_slice));
+                        let name = OsStr::from_bytes(name_slice);
+                        let child_path = dir.join(name);
+                        ctx.throttle_io(1);
                         if let Ok(md) = std::fs::symlink_metadata(&child_path) {
                             if md.file_type().is_file() {
                                 let logical = md.len();
                                 if logical >= opt.min_file_size {
                                     let physical = logical; // best effort on musl
-                                    update_file_stats(stat_cur, logical, physical);
+                                    ctx.record_file(
+                                        stat_cur,
+                                        &metadata_entry(dir, name, &md, logical, physical),
+                                    );
                                     ctx.report_progress(opt, Some(&child_path));
                                 }
                             }
@@ -364,31 +369,35 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                     if need_ino {
                         mask |= libc::STATX_INO;
                     }
+                    mask |= entry_statx_mask(ctx);
+                    ctx.throttle_io(1);
                     let rc = unsafe { libc::statx(fd, c_name.as_ptr(), flags, mask, &mut stx) };
                     if rc == 0 {
                         let mode = stx.stx_mode as u32;
                         let ftype = mode & libc::S_IFMT;
                         if ftype == libc::S_IFDIR {
                             if opt.max_depth == 0 || depth < opt.max_depth {
-                                use std::ffi::OsStr;
                                 let child_path = dir.join(OsStr::from_bytes(name_slice));
-                                ctx.normal_injector.push(crate::Job {
-                                    dir: child_path,
-                                    depth: depth + 1,
-                                    resume: None,
-                                });
+                                ctx.visit(&statx_entry(
+                                    dir,
+                                    name_slice,
+                                    EntryKind::Dir,
+                                    &stx,
+                                    0,
+                                    0,
+                                ));
+                                ctx.enqueue_dir(child_path, depth + 1);
                             }
-                        } else if ftype == libc::S_IFREG
-                            || (opt.follow_links && ftype == libc::S_IFLNK)
-                        {
-                            // Dedupe only for regular files
-                            if ftype == libc::S_IFREG
+                        } else if crate::common_ops::counts_as_file(opt, ftype) {
+                            // Dedupe every inode that is not a followed symlink
+                            if ftype != libc::S_IFLNK
                                 && check_hardlink_duplicate(
                                     opt,
                                     ((stx.stx_dev_major as u64) << 32) | (stx.stx_dev_minor as u64),
                                     stx.stx_ino,
                                 )
                             {
+                                record_statx_link(ctx, dir, name_slice, &stx);
                                 bpos += d_reclen;
                                 continue;
                             }
@@ -396,24 +405,43 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                             if logical >= opt.min_file_size {
                                 let physical =
                                     calculate_physical_size(opt, logical, stx.stx_blocks);
-                                update_file_stats(stat_cur, logical, physical);
-                                use std::ffi::OsStr;
+                                ctx.record_file(
+                                    stat_cur,
+                                    &statx_entry(
+                                        dir,
+                                        name_slice,
+                                        EntryKind::File,
+                                        &stx,
+                                        logical,
+                                        physical,
+                                    ),
+                                );
                                 let child_path = dir.join(OsStr::from_bytes(name_slice));
                                 ctx.report_progress(opt, Some(&child_path));
                             }
+                        } else if ftype == libc::S_IFLNK {
+                            ctx.record_symlink(
+                                stat_cur,
+                                &statx_entry(dir, name_slice, EntryKind::Symlink, &stx, 0, 0),
+                            );
                         }
                     } else {
-                        use std::ffi::OsStr;
-                        let child_path = dir.join(OsStr::from_bytes(name_slice));
+                        let name = OsStr::from_bytes(name_slice);
+                        let child_path = dir.join(name);
+                        ctx.throttle_io(1);
                         if let Ok(md) = std::fs::symlink_metadata(&child_path) {
                             if md.file_type().is_dir() {
                                 if opt.max_depth == 0 || depth < opt.max_depth {
+                                    ctx.visit(&metadata_entry(dir, name, &md, 0, 0));
                                     ctx.enqueue_dir(child_path, depth + 1);
                                 }
                             } else if md.file_type().is_file() {
                                 let logical = md.len();
                                 if logical >= opt.min_file_size {
-                                    update_file_stats(stat_cur, logical, logical);
+                                    ctx.record_file(
+                                        stat_cur,
+                                        &metadata_entry(dir, name, &md, logical, logical),
+                                    );
                                     ctx.report_progress(opt, Some(&child_path));
                                 }
                             }
@@ -422,17 +450,24 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                 }
                 #[cfg(target_env = "musl")]
                 {
-                    use std::ffi::OsStr;
-                    let child_path = dir.join(OsStr::from_bytes(name_slice));
+                    let name = OsStr::from_bytes(name_slice);
+                    let child_path = dir.join(name);
+                    ctx.throttle_io(1);
                     if let Ok(md) = std::fs::symlink_metadata(&child_path) {
                         if md.file_type().is_dir() {
                             if opt.max_depth == 0 || depth < opt.max_depth {
+                                ctx.visit(&metadata_entry(dir, name, &md, 0, 0));
                                 ctx.enqueue_dir(child_path, depth + 1);
                             }
-                        } else if md.file_type().is_file() {
+                        } else if md.file_type().is_file()
+                            || (opt.count_special_files && !md.file_type().is_symlink())
+                        {
                             let logical = md.len();
                             if logical >= opt.min_file_size {
-                                update_file_stats(stat_cur, logical, logical);
+                                ctx.record_file(
+                                    stat_cur,
+                                    &metadata_entry(dir, name, &md, logical, logical),
+                                );
                                 ctx.report_progress(opt, Some(&child_path));
                             }
                         }
@@ -1,11 +1,9 @@
 use std::ptr::read_unaligned;
 
 use crate::{
-    common_ops::{
-        check_hardlink_duplicate, check_visited_directory, report_file_progress, update_file_stats,
-    },
-    error_handling::{last_os_error_systemcall, record_error},
-    DirContext, ScanContext, StatMap,
+    common_ops::{check_hardlink_duplicate, check_visited_directory, report_file_progress},
+    error_handling::last_os_error_systemcall,
+    DirContext, EntryKind, FileEntry, ScanContext, StatMap,
 };
 
 #[repr(C)]
@@ -68,7 +66,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
         )
     };
     if fd < 0 {
-        record_error(opt, &last_os_error_systemcall(dir, "open"));
+        ctx.record_dir_error(&last_os_error_systemcall(dir, "open"));
         return;
     }
     // Current dir device id
@@ -114,6 +112,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
             if !opt.follow_links {
                 options |= FSOPT_NOFOLLOW;
             }
+            ctx.throttle_io(1);
             let n = getattrlistbulk(
                 fd,
                 &mut al as *mut _,
@@ -169,6 +168,10 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                 let is_dir = objtype == VDIR;
                 let is_lnk = objtype == VLNK;
                 if is_lnk && !opt.follow_links {
+                    ctx.record_symlink(
+                        stat_cur,
+                        &FileEntry::new(dir, OsStr::from_bytes(name_slice), EntryKind::Symlink),
+                    );
                     offset += reclen;
                     continue;
                 }
@@ -181,22 +184,22 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
 
                 if is_dir {
                     if opt.max_depth == 0 || depth < opt.max_depth {
+                        ctx.visit(&FileEntry::new(
+                            dir,
+                            OsStr::from_bytes(name_slice),
+                            EntryKind::Dir,
+                        ));
                         ctx.enqueue_dir(child.clone(), depth + 1);
                     }
                 } else {
                     // Hardlink dedupe
-                    if let Ok(c_child) = CString::new(child.as_os_str().as_bytes()) {
-                        let mut st: libc::stat = std::mem::zeroed();
-                        let rc = libc::lstat(c_child.as_ptr(), &mut st);
-                        if rc == 0 {
-                            let dev = st.st_dev as u64;
-                            let ino = st.st_ino;
-                            if check_hardlink_duplicate(opt, dev, ino) {
-                                offset += reclen;
-                                continue;
-                            }
-                        }
-                    }
+                    let mut st: libc::stat = std::mem::zeroed();
+                    let have_st = match CString::new(child.as_os_str().as_bytes()) {
+                        Ok(c_child) => libc::lstat(c_child.as_ptr(), &mut st) == 0,
+                        Err(_) => false,
+                    };
+                    let duplicate =
+                        have_st && check_hardlink_duplicate(opt, st.st_dev as u64, st.st_ino);
                     let logical = totalsize as u64;
                     if logical >= opt.min_file_size {
                         let physical = if opt.compute_physical {
@@ -208,8 +211,32 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                         } else {
                             logical
                         };
-                        update_file_stats(stat_cur, logical, physical);
-                        report_file_progress(opt, ctx.total_files, Some(&child));
+                        let mut entry = FileEntry {
+                            logical,
+                            physical,
+                            allocated: opt.compute_physical.then_some(allocsize as u64),
+                            ..FileEntry::new(dir, OsStr::from_bytes(name_slice), EntryKind::File)
+                        };
+                        if have_st {
+                            entry.dev = st.st_dev as u64;
+                            entry.ino = st.st_ino;
+                            entry.mtime = st.st_mtime;
+                            entry.atime = st.st_atime;
+                            entry.ctime = st.st_ctime;
+                            entry.uid = Some(st.st_uid);
+                            entry.gid = Some(st.st_gid);
+                            entry.nlink = st.st_nlink as u64;
+                        }
+                        if duplicate {
+                            ctx.record_link(&entry);
+                        } else {
+                            ctx.record_file(stat_cur, &entry);
+                            report_file_progress(opt, ctx.total_files, Some(&child));
+                        }
+                    }
+                    if duplicate {
+                        offset += reclen;
+                        continue;
                     }
                 }
 
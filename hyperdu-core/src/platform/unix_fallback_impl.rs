@@ -3,10 +3,10 @@ use std::sync::atomic::Ordering;
 use crate::{
     common_ops::{
         calculate_physical_size, check_hardlink_duplicate, check_visited_directory,
-        report_file_progress, update_file_stats,
+        entry_statx_mask, metadata_entry, report_file_progress, statx_entry, update_file_stats,
     },
-    error_handling::{last_os_error_systemcall, record_error},
-    name_contains_patterns_bytes, DirContext, ScanContext, StatMap,
+    error_handling::last_os_error_systemcall,
+    name_contains_patterns_bytes, DirContext, EntryKind, FileEntry, ScanContext, StatMap,
 };
 
 pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
@@ -29,9 +29,11 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
     };
     if fd < 0 {
         ctx.total_files.fetch_add(0, Ordering::Relaxed); // keep Ordering imported
-        record_error(opt, &last_os_error_systemcall(dir, "open"));
+        ctx.record_dir_error(&last_os_error_systemcall(dir, "open"));
         return;
     }
+    // readdir buffers getdents internally; count one read per directory
+    ctx.throttle_io(1);
     let d = unsafe { libc::fdopendir(fd) };
     if d.is_null() {
         unsafe { libc::close(fd) };
@@ -93,6 +95,13 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
         let is_dir = dtype == libc::DT_DIR;
         let is_lnk = dtype == libc::DT_LNK;
         if is_lnk && !opt.follow_links {
+            ctx.record_symlink(
+                stat_cur,
+                &FileEntry {
+                    ino: entry.d_ino as u64,
+                    ..FileEntry::new(dir, OsStr::from_bytes(name_b), EntryKind::Symlink)
+                },
+            );
             continue;
         }
 
@@ -103,6 +112,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                     let mut st_child: libc::stat = unsafe { std::mem::zeroed() };
                     let cn = CString::new(name_b).ok();
                     if let Some(cn) = cn {
+                        ctx.throttle_io(1);
                         let rc = unsafe {
                             libc::fstatat(
                                 dirfd,
@@ -121,6 +131,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                         let mut st: libc::stat = unsafe { std::mem::zeroed() };
                         let cn = CString::new(name_b).ok();
                         if let Some(cn) = cn {
+                            ctx.throttle_io(1);
                             let rc = unsafe {
                                 libc::fstatat(
                                     dirfd,
@@ -144,14 +155,24 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                         }
                     }
                 }
+                ctx.visit(&FileEntry {
+                    ino: entry.d_ino as u64,
+                    ..FileEntry::new(dir, OsStr::from_bytes(name_b), EntryKind::Dir)
+                });
                 ctx.enqueue_dir(child, depth + 1);
             }
         } else if dtype == libc::DT_REG {
             if !opt.compute_physical && opt.approximate_sizes && opt.min_file_size == 0 {
                 let logical = 4096u64;
-                stat_cur.logical += logical;
-                stat_cur.physical += logical;
-                stat_cur.files += 1;
+                ctx.record_file(
+                    stat_cur,
+                    &FileEntry {
+                        logical,
+                        physical: logical,
+                        ino: entry.d_ino as u64,
+                        ..FileEntry::new(dir, OsStr::from_bytes(name_b), EntryKind::File)
+                    },
+                );
                 if opt.progress_every > 0 {
                     let n = ctx.total_files.fetch_add(1, Ordering::Relaxed) + 1;
                     if n % opt.progress_every == 0 {
@@ -186,12 +207,17 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                 } else {
                     libc::AT_SYMLINK_NOFOLLOW
                 };
+                ctx.throttle_io(1);
                 let rc = unsafe {
                     libc::statx(
                         dirfd,
                         c_name.as_ptr(),
                         flags,
-                        libc::STATX_SIZE | libc::STATX_BLOCKS | libc::STATX_INO | libc::STATX_MODE,
+                        libc::STATX_SIZE
+                            | libc::STATX_BLOCKS
+                            | libc::STATX_INO
+                            | libc::STATX_MODE
+                            | entry_statx_mask(ctx),
                         &mut stx,
                     )
                 };
@@ -204,6 +230,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                                 ((stx.stx_dev_major as u64) << 32) | (stx.stx_dev_minor as u64);
                             let ino = stx.stx_ino;
                             if check_hardlink_duplicate(opt, dev, ino) {
+                                crate::common_ops::record_statx_link(ctx, dir, name_b, &stx);
                                 continue;
                             }
                         }
@@ -217,9 +244,10 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                         } else {
                             logical
                         };
-                        stat_cur.logical += logical;
-                        stat_cur.physical += physical_eff;
-                        stat_cur.files += 1;
+                        ctx.record_file(
+                            stat_cur,
+                            &statx_entry(dir, name_b, EntryKind::File, &stx, logical, physical_eff),
+                        );
                         if opt.progress_every > 0 {
                             let n = ctx.total_files.fetch_add(1, Ordering::Relaxed) + 1;
                             if n % opt.progress_every == 0 {
@@ -235,13 +263,21 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                     }
                 } else {
                     let child = dir.join(OsStr::from_bytes(name_b));
+                    ctx.throttle_io(1);
                     if let Ok(md) = std::fs::symlink_metadata(&child) {
                         if md.file_type().is_file() {
                             let logical = md.len();
                             if logical >= opt.min_file_size {
-                                stat_cur.logical += logical;
-                                stat_cur.physical += logical;
-                                stat_cur.files += 1;
+                                ctx.record_file(
+                                    stat_cur,
+                                    &metadata_entry(
+                                        dir,
+                                        OsStr::from_bytes(name_b),
+                                        &md,
+                                        logical,
+                                        logical,
+                                    ),
+                                );
                                 if opt.progress_every > 0 {
                                     let n = ctx.total_files.fetch_add(1, Ordering::Relaxed) + 1;
                                     if n % opt.progress_every == 0 {
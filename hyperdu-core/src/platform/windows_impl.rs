@@ -1,6 +1,35 @@
 use std::sync::atomic::Ordering;
 
-use crate::{wname_contains_patterns_lossy, DirContext, ScanContext, StatMap};
+use crate::{
+    wname_contains_patterns_lossy, DirContext, EntryKind, FileEntry, ScanContext, StatMap,
+};
+
+/// Convert a FILETIME tick count (100ns since 1601-01-01) to Unix seconds.
+#[inline]
+fn filetime_to_unix(ticks: u64) -> i64 {
+    if ticks == 0 {
+        return 0;
+    }
+    (ticks / 10_000_000) as i64 - 11_644_473_600
+}
+
+#[inline]
+fn find_data_entry<'a>(
+    dir: &'a std::path::Path,
+    name: &'a std::ffi::OsStr,
+    kind: EntryKind,
+    data: &windows::Win32::Storage::FileSystem::WIN32_FIND_DATAW,
+) -> FileEntry<'a> {
+    let ft = |t: windows::Win32::Foundation::FILETIME| {
+        filetime_to_unix(((t.dwHighDateTime as u64) << 32) | (t.dwLowDateTime as u64))
+    };
+    FileEntry {
+        mtime: ft(data.ftLastWriteTime),
+        atime: ft(data.ftLastAccessTime),
+        ctime: ft(data.ftCreationTime),
+        ..FileEntry::new(dir, name, kind)
+    }
+}
 
 pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
     let dir = dctx.dir;
@@ -100,6 +129,8 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
             0
         };
         let mut data: WIN32_FIND_DATAW = std::mem::zeroed();
+        // FindNextFileW is served from the large-fetch buffer; count the listing once
+        ctx.throttle_io(1);
         let handle = match FindFirstFileExW(
             PCWSTR(pattern.as_ptr()),
             FindExInfoBasic,
@@ -110,10 +141,10 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
         ) {
             Ok(h) => h,
             Err(_) => {
-                crate::error_handling::record_error(
-                    opt,
-                    &crate::error_handling::last_os_error_systemcall(dir, "FindFirstFileExW"),
-                );
+                ctx.record_dir_error(&crate::error_handling::last_os_error_systemcall(
+                    dir,
+                    "FindFirstFileExW",
+                ));
                 return;
             }
         };
@@ -139,6 +170,10 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
             let is_dir = (data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY.0) != 0;
             let is_reparse = (data.dwFileAttributes & FILE_ATTRIBUTE_REPARSE_POINT.0) != 0;
             if is_reparse && !opt.follow_links {
+                ctx.record_symlink(
+                    stat_cur,
+                    &find_data_entry(dir, &name, EntryKind::Symlink, &data),
+                );
                 continue;
             }
 
@@ -165,6 +200,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                             wide_buf.truncate(base_len);
                             wide_buf.extend_from_slice(&data.cFileName[..name_len]);
                             wide_buf.push(0);
+                            ctx.throttle_io(1);
                             if let Ok(h) = CreateFileW(
                                 PCWSTR(wide_buf.as_ptr()),
                                 0x80,
@@ -203,6 +239,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                             }
                         }
                     }
+                    ctx.visit(&find_data_entry(dir, &name, EntryKind::Dir, &data));
                     ctx.enqueue_dir(child, depth + 1);
                 }
             } else {
@@ -217,6 +254,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                         wide_buf.truncate(base_len);
                         wide_buf.extend_from_slice(&data.cFileName[..name_len]);
                         wide_buf.push(0);
+                        ctx.throttle_io(1);
                         if let Ok(handle_file) = CreateFileW(
                             PCWSTR(wide_buf.as_ptr()),
                             0x80,
@@ -247,6 +285,7 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                     #[cfg(any(feature = "prof-tracy", feature = "prof-puffin"))]
                     profiling::scope!("GetCompressedFileSizeW");
                     let mut physical = logical;
+                    let mut allocated = None;
                     if opt.compute_physical {
                         wide_buf.truncate(base_len);
                         wide_buf.extend_from_slice(&data.cFileName[..name_len]);
@@ -257,9 +296,18 @@ pub fn process_dir(ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
                         let combined = ((high as u64) << 32) | (low as u64);
                         if low != u32::MAX {
                             physical = combined;
+                            allocated = Some(combined);
                         }
                     }
-                    crate::common_ops::update_file_stats(stat_cur, logical, physical);
+                    ctx.record_file(
+                        stat_cur,
+                        &FileEntry {
+                            logical,
+                            physical,
+                            allocated,
+                            ..find_data_entry(dir, &name, EntryKind::File, &data)
+                        },
+                    );
                     let child = dir.join(&name);
                     ctx.report_progress(opt, Some(&child));
                 }
@@ -333,6 +381,7 @@ fn try_fast_enum(
     let mut iosb: IO_STATUS_BLOCK = unsafe { std::mem::zeroed() };
     let stat_cur = map.entry(dir.to_path_buf()).or_default();
     loop {
+        ctx.throttle_io(1);
         let status: NTSTATUS = unsafe {
             NtQueryDirectoryFile(
                 h,
@@ -396,7 +445,22 @@ fn try_fast_enum(
             let attrs = info.FileAttributes;
             let is_dir = (attrs & 0x10) != 0; // FILE_ATTRIBUTE_DIRECTORY
             let is_reparse = (attrs & 0x400) != 0; // FILE_ATTRIBUTE_REPARSE_POINT
+            let entry = FileEntry {
+                dev: vol_serial,
+                ino: file_id_u64(info),
+                mtime: filetime_to_unix(info.LastWriteTime as u64),
+                atime: filetime_to_unix(info.LastAccessTime as u64),
+                ctime: filetime_to_unix(info.ChangeTime as u64),
+                ..FileEntry::new(dir, &os, EntryKind::Other)
+            };
             if is_reparse && !opt.follow_links {
+                ctx.record_symlink(
+                    stat_cur,
+                    &FileEntry {
+                        kind: EntryKind::Symlink,
+                        ..entry
+                    },
+                );
                 if next == 0 {
                     break;
                 } else {
@@ -415,18 +479,20 @@ fn try_fast_enum(
                             continue;
                         }
                     }
-                    ctx.normal_injector.push(crate::Job {
-                        dir: child,
-                        depth: depth + 1,
-                        resume: None,
+                    ctx.visit(&FileEntry {
+                        kind: EntryKind::Dir,
+                        ..entry
                     });
+                    ctx.enqueue_dir(child, depth + 1);
                 }
             } else {
                 let logical = info.EndOfFile as u64;
 
                 let mut physical = logical;
+                let mut allocated = None;
                 if opt.compute_physical {
                     let alloc = info.AllocationSize as u64;
+                    allocated = Some(alloc);
                     if alloc != 0 {
                         physical = alloc;
                     }
@@ -447,9 +513,16 @@ fn try_fast_enum(
                     }
                 }
                 if logical >= opt.min_file_size {
-                    stat_cur.logical += logical;
-                    stat_cur.physical += physical;
-                    stat_cur.files += 1;
+                    ctx.record_file(
+                        stat_cur,
+                        &FileEntry {
+                            kind: EntryKind::File,
+                            logical,
+                            physical,
+                            allocated,
+                            ..entry
+                        },
+                    );
                     if opt.progress_every > 0 {
                         let n = ctx.total_files.fetch_add(1, Ordering::Relaxed) + 1;
                         if n % opt.progress_every == 0 {
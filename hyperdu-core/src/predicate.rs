@@ -0,0 +1,562 @@
+//! find-like predicate expressions over the metadata a scan already has.
+//!
+//! Tests are combined with `and`/`&&`, `or`/`||`, `not`/`!` and parentheses;
+//! adjacent tests are ANDed as in find. A test is `FIELD OP VALUE` without
+//! spaces (quote values that contain spaces or parentheses):
+//!
+//! | field | ops | value |
+//! |---|---|---|
+//! | `size` | `= != < <= > >=` | logical bytes; `K`..`P` (or `KiB`..) are powers of 1024, `KB`.. of 1000 |
+//! | `mtime`, `atime`, `ctime` | `= != < <= > >=` | age in `s`, `m`, `h`, `d`, `w` or `y`; days without a unit |
+//! | `type` | `= !=` | `f` file, `l` symlink, `o` other |
+//! | `owner`, `group` | `= !=` | name or numeric id |
+//! | `name`, `path` | `= != ~ !~` | exact text, or a glob with `~` |
+//!
+//! `size>100M and mtime>90d` selects files over 100 MiB not modified for 90 days.
+//! Expressions only see non-directory entries; directories are always walked.
+
+use std::{
+    ffi::OsString,
+    fmt,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use globset::{Glob, GlobMatcher};
+
+use crate::{age::TimeKind, EntryKind, FileEntry};
+
+/// A compiled expression. Ages are measured from the time it was parsed.
+#[derive(Debug)]
+pub struct Predicate {
+    expr: Expr,
+    now: i64,
+}
+
+impl Predicate {
+    pub fn parse(text: &str) -> Result<Self> {
+        Self::parse_all(&[text])
+    }
+
+    /// All of `exprs` ANDed together.
+    pub fn parse_all<S: AsRef<str>>(exprs: &[S]) -> Result<Self> {
+        let mut all = Vec::with_capacity(exprs.len());
+        for text in exprs {
+            let text = text.as_ref();
+            let mut p = Parser {
+                toks: lex(text)?,
+                pos: 0,
+            };
+            let expr = p.or()?;
+            if let Some(t) = p.toks.get(p.pos) {
+                bail!("unexpected {t} in {text:?}");
+            }
+            all.push(expr);
+        }
+        let expr = if all.len() == 1 {
+            all.remove(0)
+        } else {
+            Expr::And(all)
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        Ok(Self { expr, now })
+    }
+
+    /// Whether `entry` satisfies the expression. Metadata the backend did not
+    /// fetch (see [`FileEntry`]) fails owner/group tests and reads as 0 otherwise.
+    #[inline]
+    pub fn matches(&self, entry: &FileEntry<'_>) -> bool {
+        self.expr.eval(entry, self.now)
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Test(Test),
+}
+
+impl Expr {
+    fn eval(&self, e: &FileEntry<'_>, now: i64) -> bool {
+        match self {
+            Expr::And(v) => v.iter().all(|x| x.eval(e, now)),
+            Expr::Or(v) => v.iter().any(|x| x.eval(e, now)),
+            Expr::Not(x) => !x.eval(e, now),
+            Expr::Test(t) => t.eval(e, now),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    #[inline]
+    fn holds<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Cmp::Eq => a == b,
+            Cmp::Ne => a != b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Cmp(Cmp),
+    /// `~` (true) or `!~` (false)
+    Glob(bool),
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Exact(OsString),
+    Glob(GlobMatcher),
+}
+
+impl Pattern {
+    fn is_match(&self, s: &Path) -> bool {
+        match self {
+            Pattern::Exact(x) => s.as_os_str() == x,
+            Pattern::Glob(g) => g.is_match(s),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Test {
+    Size(Cmp, u64),
+    /// Age in seconds of the given timestamp
+    Age(TimeKind, Cmp, i64),
+    /// Only `Eq`/`Ne`
+    Type(Cmp, EntryKind),
+    Owner(Cmp, u32),
+    Group(Cmp, u32),
+    /// Whether the pattern should match
+    Name(bool, Pattern),
+    Path(bool, Pattern),
+}
+
+impl Test {
+    fn parse(text: &str) -> Result<Self> {
+        const OPS: [(&str, Op); 8] = [
+            ("<=", Op::Cmp(Cmp::Le)),
+            (">=", Op::Cmp(Cmp::Ge)),
+            ("!=", Op::Cmp(Cmp::Ne)),
+            ("!~", Op::Glob(false)),
+            ("=", Op::Cmp(Cmp::Eq)),
+            ("<", Op::Cmp(Cmp::Lt)),
+            (">", Op::Cmp(Cmp::Gt)),
+            ("~", Op::Glob(true)),
+        ];
+        let split = text
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(text.len());
+        let (field, rest) = text.split_at(split);
+        let Some((op, value)) = OPS
+            .iter()
+            .find_map(|(s, op)| rest.strip_prefix(s).map(|v| (*op, v)))
+        else {
+            bail!("{text:?}: expected FIELD OP VALUE, e.g. size>100M");
+        };
+        if value.is_empty() {
+            bail!("{text:?}: missing value");
+        }
+        let bad_value = || anyhow!("{text:?}: invalid value {value:?}");
+        let cmp = |ordered: bool| match op {
+            Op::Cmp(c) if ordered || matches!(c, Cmp::Eq | Cmp::Ne) => Ok(c),
+            _ => Err(anyhow!("{text:?}: operator not supported for {field}")),
+        };
+        Ok(match field {
+            "size" => Test::Size(cmp(true)?, parse_size(value).ok_or_else(bad_value)?),
+            "mtime" | "atime" | "ctime" => {
+                let kind = match field {
+                    "mtime" => TimeKind::Mtime,
+                    "atime" => TimeKind::Atime,
+                    _ => TimeKind::Ctime,
+                };
+                Test::Age(kind, cmp(true)?, parse_age(value).ok_or_else(bad_value)?)
+            }
+            "type" => {
+                let kind = match value {
+                    "f" => EntryKind::File,
+                    "l" => EntryKind::Symlink,
+                    "d" => bail!("{text:?}: filters only see files, not directories"),
+                    "o" => EntryKind::Other,
+                    _ => return Err(bad_value()),
+                };
+                Test::Type(cmp(false)?, kind)
+            }
+            "owner" => Test::Owner(
+                cmp(false)?,
+                crate::owner::user_id(value).ok_or_else(|| anyhow!("{text:?}: unknown user"))?,
+            ),
+            "group" => Test::Group(
+                cmp(false)?,
+                crate::owner::group_id(value).ok_or_else(|| anyhow!("{text:?}: unknown group"))?,
+            ),
+            "name" | "path" => {
+                let (want, pat) = match op {
+                    Op::Glob(want) => {
+                        let g = Glob::new(value).map_err(|e| anyhow!("{text:?}: {e}"))?;
+                        (want, Pattern::Glob(g.compile_matcher()))
+                    }
+                    Op::Cmp(_) => (cmp(false)? == Cmp::Eq, Pattern::Exact(value.into())),
+                };
+                if field == "name" {
+                    Test::Name(want, pat)
+                } else {
+                    Test::Path(want, pat)
+                }
+            }
+            _ => bail!(
+                "{text:?}: unknown field {field:?} (size, mtime, atime, ctime, type, owner, group, name, path)"
+            ),
+        })
+    }
+
+    #[inline]
+    fn eval(&self, e: &FileEntry<'_>, now: i64) -> bool {
+        match self {
+            Test::Size(c, n) => c.holds(e.logical, *n),
+            Test::Age(kind, c, secs) => c.holds(now - kind.of(e), *secs),
+            Test::Type(c, kind) => (e.kind == *kind) == (*c == Cmp::Eq),
+            Test::Owner(c, id) => e.uid.is_some_and(|u| c.holds(u, *id)),
+            Test::Group(c, id) => e.gid.is_some_and(|g| c.holds(g, *id)),
+            Test::Name(want, p) => p.is_match(Path::new(e.name)) == *want,
+            Test::Path(want, p) => p.is_match(&e.path()) == *want,
+        }
+    }
+}
+
+/// Leading decimal number and the unit after it.
+fn split_number(v: &str) -> Option<(f64, &str)> {
+    let end = v
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(v.len());
+    let (num, unit) = v.split_at(end);
+    Some((num.parse().ok()?, unit))
+}
+
+/// `1.5G`, `100MiB`, `10KB`, `512`.
+fn parse_size(v: &str) -> Option<u64> {
+    let (num, unit) = split_number(v)?;
+    let unit = unit.to_ascii_uppercase();
+    let mult = match unit.as_str() {
+        "" | "B" => 1.0,
+        u => {
+            let prefix = u.chars().next()?;
+            let exp = "KMGTP".find(prefix)? as i32 + 1;
+            let base: f64 = match &u[prefix.len_utf8()..] {
+                "" | "I" | "IB" => 1024.0,
+                "B" => 1000.0,
+                _ => return None,
+            };
+            base.powi(exp)
+        }
+    };
+    Some((num * mult) as u64)
+}
+
+/// `30d`, `12h`, `2w`, `1y`, `45` (days); in seconds.
+fn parse_age(v: &str) -> Option<i64> {
+    let (num, unit) = split_number(v)?;
+    let secs: i64 = match unit {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 3600,
+        "" | "d" => 86_400,
+        "w" => 7 * 86_400,
+        "y" => 365 * 86_400,
+        _ => return None,
+    };
+    Some((num * secs as f64) as i64)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Tok {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Test(String),
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::Open => f.write_str("'('"),
+            Tok::Close => f.write_str("')'"),
+            Tok::And => f.write_str("'and'"),
+            Tok::Or => f.write_str("'or'"),
+            Tok::Not => f.write_str("'not'"),
+            Tok::Test(t) => write!(f, "{t:?}"),
+        }
+    }
+}
+
+fn lex(text: &str) -> Result<Vec<Tok>> {
+    let mut toks = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '!' => {
+                chars.next();
+                toks.push(match c {
+                    '(' => Tok::Open,
+                    ')' => Tok::Close,
+                    _ => Tok::Not,
+                });
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    bail!("expected {c}{c} in {text:?}");
+                }
+                toks.push(if c == '&' { Tok::And } else { Tok::Or });
+            }
+            _ => {
+                let mut word = String::new();
+                let mut quote = None;
+                let mut quoted = false;
+                while let Some(&c) = chars.peek() {
+                    match quote {
+                        Some(q) if c == q => quote = None,
+                        Some(_) => word.push(c),
+                        None if c == '"' || c == '\'' => {
+                            quote = Some(c);
+                            quoted = true;
+                        }
+                        None if c.is_whitespace() || c == '(' || c == ')' => break,
+                        None => word.push(c),
+                    }
+                    chars.next();
+                }
+                if quote.is_some() {
+                    bail!("unterminated quote in {text:?}");
+                }
+                toks.push(match word.as_str() {
+                    "and" if !quoted => Tok::And,
+                    "or" if !quoted => Tok::Or,
+                    "not" if !quoted => Tok::Not,
+                    _ => Tok::Test(word),
+                });
+            }
+        }
+    }
+    Ok(toks)
+}
+
+/// Recursive descent: `or := and ('or' and)*`, `and := unary ('and'? unary)*`,
+/// `unary := 'not' unary | '(' or ')' | test`.
+struct Parser {
+    toks: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut v = vec![self.and()?];
+        while self.peek() == Some(&Tok::Or) {
+            self.pos += 1;
+            v.push(self.and()?);
+        }
+        Ok(if v.len() == 1 {
+            v.remove(0)
+        } else {
+            Expr::Or(v)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut v = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Tok::And) => self.pos += 1,
+                // Implicit and between adjacent terms
+                Some(Tok::Not | Tok::Open | Tok::Test(_)) => {}
+                _ => break,
+            }
+            v.push(self.unary()?);
+        }
+        Ok(if v.len() == 1 {
+            v.remove(0)
+        } else {
+            Expr::And(v)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let tok = self.peek().cloned();
+        self.pos += 1;
+        match tok {
+            Some(Tok::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Tok::Open) => {
+                let e = self.or()?;
+                if self.peek() != Some(&Tok::Close) {
+                    bail!("missing ')'");
+                }
+                self.pos += 1;
+                Ok(e)
+            }
+            Some(Tok::Test(t)) => Ok(Expr::Test(Test::parse(&t)?)),
+            Some(t) => bail!("unexpected {t}"),
+            None => bail!("expression ends where a test was expected"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_000_000_000;
+    const DAY: i64 = 86_400;
+
+    fn file(name: &'static str, size: u64, age_days: i64) -> FileEntry<'static> {
+        FileEntry {
+            logical: size,
+            mtime: NOW - age_days * DAY,
+            uid: Some(1000),
+            ..FileEntry::new(Path::new("/r/d"), name.as_ref(), EntryKind::File)
+        }
+    }
+
+    fn eval(expr: &str, e: &FileEntry<'_>) -> bool {
+        let mut p = Predicate::parse(expr).unwrap();
+        p.now = NOW;
+        p.matches(e)
+    }
+
+    fn error(expr: &str) -> String {
+        Predicate::parse(expr).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let x = file("x", 0, 0);
+        assert!(eval("name=x or name=y and size>10", &x));
+        assert!(!eval("(name=x or name=y) and size>10", &x));
+        assert!(!eval("name=x size>10", &x));
+        assert!(eval("name=y || name=x && size<10", &x));
+        assert!(!eval("name=y or name=z", &x));
+    }
+
+    #[test]
+    fn negation_and_parentheses() {
+        let x = file("x", 0, 0);
+        assert!(!eval("!name=x", &x));
+        assert!(eval("not not name=x", &x));
+        assert!(!eval("!(name=x or name=y)", &x));
+        assert!(eval("!name=y and (size=0)", &x));
+        assert!(eval("((name=x))", &x));
+        assert!(eval("name!=y", &x));
+    }
+
+    #[test]
+    fn size_units() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("10b"), Some(10));
+        assert_eq!(parse_size("1K"), Some(1024));
+        assert_eq!(parse_size("1KiB"), Some(1024));
+        assert_eq!(parse_size("1KB"), Some(1000));
+        assert_eq!(parse_size("1.5M"), Some(3 << 19));
+        assert_eq!(parse_size("2g"), Some(2 << 30));
+        assert_eq!(parse_size("1P"), Some(1 << 50));
+        assert_eq!(parse_size("1X"), None);
+        assert_eq!(parse_size("1KX"), None);
+        assert_eq!(parse_size("M"), None);
+
+        let f = file("f", 100 << 20, 0);
+        assert!(eval("size>=100M", &f));
+        assert!(!eval("size>100M", &f));
+        assert!(eval("size>100MB", &f));
+    }
+
+    #[test]
+    fn time_units() {
+        assert_eq!(parse_age("45"), Some(45 * DAY));
+        assert_eq!(parse_age("30s"), Some(30));
+        assert_eq!(parse_age("5m"), Some(300));
+        assert_eq!(parse_age("5min"), Some(300));
+        assert_eq!(parse_age("12h"), Some(12 * 3600));
+        assert_eq!(parse_age("2w"), Some(14 * DAY));
+        assert_eq!(parse_age("1y"), Some(365 * DAY));
+        assert_eq!(parse_age("3q"), None);
+
+        let old = file("old", 0, 100);
+        assert!(eval("mtime>90d", &old));
+        assert!(!eval("mtime<13w", &old));
+        assert!(eval("mtime<1y and atime>1y", &old));
+    }
+
+    #[test]
+    fn types_names_and_owners() {
+        let f = file("a b.iso", 0, 0);
+        let l = FileEntry {
+            kind: EntryKind::Symlink,
+            ..file("link", 0, 0)
+        };
+        assert!(eval("type=f", &f) && !eval("type=f", &l));
+        assert!(eval("type!=f", &l) && eval("type=l", &l));
+        assert!(eval("name~*.iso", &f) && !eval("name!~*.iso", &f));
+        assert!(eval("name='a b.iso'", &f));
+        assert!(eval("path~/r/**", &f) && eval("path=\"/r/d/a b.iso\"", &f));
+        assert!(eval("owner=1000", &f) && !eval("group=1000", &f));
+        // Quoted keywords are values, not operators
+        assert!(eval("name='and' or name~*.iso", &f));
+    }
+
+    #[test]
+    fn all_expressions_must_hold() {
+        let mut p = Predicate::parse_all(&["size>10", "name=x"]).unwrap();
+        p.now = NOW;
+        assert!(p.matches(&file("x", 11, 0)));
+        assert!(!p.matches(&file("x", 10, 0)));
+        assert!(!p.matches(&file("y", 11, 0)));
+    }
+
+    #[test]
+    fn malformed_expressions_are_rejected() {
+        assert!(error("").contains("expression ends"));
+        assert!(error("size").contains("expected FIELD OP VALUE"));
+        assert!(error("size>").contains("missing value"));
+        assert!(error("size>abc").contains("invalid value"));
+        assert!(error("mtime>3q").contains("invalid value"));
+        assert!(error("size~1").contains("operator not supported"));
+        assert!(error("type<f").contains("operator not supported"));
+        assert!(error("type=x").contains("invalid value"));
+        assert!(error("type=d").contains("not directories"));
+        assert!(error("color=red").contains("unknown field"));
+        assert!(error("(size>1").contains("missing ')'"));
+        assert!(error("size>1)").contains("unexpected ')'"));
+        assert!(error("size>1 or").contains("expression ends"));
+        assert!(error("size>1 & name=x").contains("expected &&"));
+        assert!(error("name='x").contains("unterminated quote"));
+        assert!(error("and").contains("unexpected 'and'"));
+        assert!(error("name~[").contains("name~["));
+    }
+}
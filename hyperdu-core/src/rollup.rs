@@ -2,36 +2,34 @@ use std::path::{Path, PathBuf};
 
 use ahash::AHashMap as HashMap;
 
-use crate::StatMap;
-
-#[inline(always)]
-fn depth_of(p: &Path) -> usize {
-    p.components().count()
-}
-
-pub fn rollup_child_to_parent(mut merged: StatMap) -> StatMap {
-    let mut by_depth: HashMap<usize, Vec<PathBuf>> = HashMap::default();
-    let mut maxd = 0usize;
-    for p in merged.keys() {
-        let d = depth_of(p);
-        by_depth.entry(d).or_default().push(p.clone());
-        if d > maxd {
-            maxd = d;
+/// Fold per-directory values into their ancestors up to `root`, so every entry
+/// covers its whole subtree. Missing intermediate directories are created.
+pub(crate) fn rollup_to_root<T: Default>(
+    map: &mut HashMap<PathBuf, T>,
+    root: &Path,
+    merge: impl Fn(&mut T, &T),
+) {
+    let keys: Vec<PathBuf> = map.keys().cloned().collect();
+    for k in keys {
+        for anc in k.ancestors().skip(1) {
+            if !anc.starts_with(root) || map.contains_key(anc) {
+                break;
+            }
+            map.insert(anc.to_path_buf(), T::default());
         }
     }
-    for d in (1..=maxd).rev() {
-        if let Some(paths) = by_depth.get(&d) {
-            for p in paths {
-                if let Some(parent) = p.parent() {
-                    if let Some(stat) = merged.get(p).copied() {
-                        let e = merged.entry(parent.to_path_buf()).or_default();
-                        e.logical += stat.logical;
-                        e.physical += stat.physical;
-                        e.files += stat.files;
-                    }
-                }
-            }
+    let mut paths: Vec<PathBuf> = map.keys().cloned().collect();
+    paths.sort_unstable_by_key(|p| std::cmp::Reverse(p.components().count()));
+    for p in paths {
+        if p.as_path() == root {
+            continue;
+        }
+        let Some(parent) = p.parent().filter(|pp| pp.starts_with(root)) else {
+            continue;
+        };
+        if let Some(child) = map.remove(&p) {
+            merge(map.entry(parent.to_path_buf()).or_default(), &child);
+            map.insert(p, child);
         }
     }
-    merged
 }
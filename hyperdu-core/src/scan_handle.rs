@@ -0,0 +1,254 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use ahash::AHashMap as HashMap;
+use anyhow::{anyhow, Result};
+
+use crate::{merge_stat_maps, Job, ScanTree, StatMap};
+
+/// How often a worker publishes its buffered results to the shared live state.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Shared state behind a [`ScanHandle`]. Workers publish into it periodically.
+#[derive(Default)]
+pub(crate) struct LiveState {
+    root: PathBuf,
+    inner: Mutex<LiveInner>,
+}
+
+#[derive(Default)]
+struct LiveInner {
+    stats: StatMap,
+    // Outstanding jobs per directory. Workers flush their deltas independently,
+    // so a count can dip below zero briefly; only positive counts are pending.
+    pending: HashMap<PathBuf, PendingJobs>,
+    // Final tree, set by the scan thread just before it exits.
+    finished: Option<ScanTree>,
+}
+
+/// Outstanding jobs of one directory: a count per resume offset (`None` = from
+/// the start) and the directory's depth.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PendingJobs {
+    depth: u32,
+    counts: Vec<(Option<u64>, i64)>,
+}
+
+impl PendingJobs {
+    fn add(&mut self, resume: Option<u64>, delta: i64) {
+        match self.counts.iter_mut().find(|(r, _)| *r == resume) {
+            Some((_, n)) => *n += delta,
+            None => self.counts.push((resume, delta)),
+        }
+        self.counts.retain(|(_, n)| *n != 0);
+    }
+
+    fn merge(&mut self, other: &PendingJobs) {
+        self.depth = other.depth;
+        for &(resume, n) in &other.counts {
+            self.add(resume, n);
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        self.counts.iter().any(|(_, n)| *n > 0)
+    }
+}
+
+impl LiveState {
+    pub(crate) fn new(root: &Path) -> Self {
+        Self::resumed(root, StatMap::default(), &[Job::root(root)])
+    }
+
+    /// Live state of a scan continuing from a checkpoint: `stats` already
+    /// counted and `jobs` still to run.
+    pub(crate) fn resumed(root: &Path, stats: StatMap, jobs: &[Job]) -> Self {
+        let mut inner = LiveInner {
+            stats,
+            ..LiveInner::default()
+        };
+        for job in jobs {
+            let p = inner.pending.entry(job.dir.clone()).or_default();
+            p.depth = job.depth;
+            p.add(job.resume, 1);
+        }
+        Self {
+            root: root.to_path_buf(),
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Move a worker's finished stats and pending deltas into the shared state.
+    pub(crate) fn publish(&self, stats: &mut StatMap, buf: &mut LiveBuffer) {
+        let pending = std::mem::take(buf.pending.get_mut());
+        buf.last_flush = Instant::now();
+        if stats.is_empty() && pending.is_empty() {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        merge_stat_maps(&mut inner.stats, std::mem::take(stats));
+        for (dir, delta) in pending {
+            inner.pending.entry(dir).or_default().merge(&delta);
+        }
+        inner.pending.retain(|_, p| !p.counts.is_empty());
+    }
+
+    /// Per-directory stats and outstanding jobs. Only consistent while no
+    /// worker holds unpublished results (all parked or exited).
+    pub(crate) fn checkpoint_state(&self) -> (StatMap, Vec<Job>) {
+        let inner = self.inner.lock().unwrap();
+        let mut jobs = Vec::new();
+        for (dir, p) in &inner.pending {
+            for &(resume, n) in &p.counts {
+                for _ in 0..n.max(0) {
+                    jobs.push(Job {
+                        dir: dir.clone(),
+                        depth: p.depth,
+                        resume,
+                    });
+                }
+            }
+        }
+        (inner.stats.clone(), jobs)
+    }
+
+    /// Copy of the merged stats once all workers have published. They stay in
+    /// place so snapshots keep showing them until [`LiveState::finish`].
+    pub(crate) fn final_stats(&self) -> StatMap {
+        self.inner.lock().unwrap().stats.clone()
+    }
+
+    /// Store the final tree for snapshots and drop the live map.
+    pub(crate) fn finish(&self, tree: &ScanTree) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.finished = Some(tree.clone());
+        inner.stats = StatMap::default();
+        inner.pending.clear();
+    }
+
+    fn snapshot(&self) -> (ScanTree, Vec<PathBuf>) {
+        let inner = self.inner.lock().unwrap();
+        if let Some(tree) = &inner.finished {
+            return (tree.clone(), Vec::new());
+        }
+        let tree = ScanTree::from_dir_stats(&self.root, &inner.stats);
+        let pending = inner
+            .pending
+            .iter()
+            .filter(|(_, p)| p.is_pending())
+            .map(|(p, _)| p.clone())
+            .collect();
+        (tree, pending)
+    }
+}
+
+/// Per-worker buffer of pending-count deltas not yet published. Lives on the
+/// worker's stack and is reached through `ScanContext` (hence the `RefCell`).
+pub(crate) struct LiveBuffer {
+    pending: RefCell<HashMap<PathBuf, PendingJobs>>,
+    last_flush: Instant,
+}
+
+impl Default for LiveBuffer {
+    fn default() -> Self {
+        Self {
+            pending: RefCell::default(),
+            last_flush: Instant::now(),
+        }
+    }
+}
+
+impl LiveBuffer {
+    #[inline]
+    pub(crate) fn adjust(&self, dir: &Path, depth: u32, resume: Option<u64>, delta: i64) {
+        let mut p = self.pending.borrow_mut();
+        let jobs = match p.get_mut(dir) {
+            Some(jobs) => jobs,
+            None => p.entry(dir.to_path_buf()).or_default(),
+        };
+        jobs.depth = depth;
+        jobs.add(resume, delta);
+    }
+
+    #[inline]
+    pub(crate) fn due(&self) -> bool {
+        self.last_flush.elapsed() >= FLUSH_INTERVAL
+    }
+}
+
+/// Point-in-time view of a running scan.
+#[derive(Clone, Debug, Default)]
+pub struct ScanSnapshot {
+    /// Rolled-up tree of everything counted so far.
+    pub tree: ScanTree,
+    /// Directories queued or partially read but not finished yet.
+    pub pending: Vec<PathBuf>,
+    /// True once the scan thread has exited; `tree` is then the final result.
+    pub finished: bool,
+}
+
+/// Handle to a scan running on a background thread.
+///
+/// Use [`ScanHandle::snapshot`] to peek at partial results and
+/// [`ScanHandle::join`] (or [`ScanHandle::join_tree`]) to wait for the result.
+pub struct ScanHandle {
+    live: Arc<LiveState>,
+    cancel: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<ScanTree>>>,
+}
+
+impl ScanHandle {
+    pub(crate) fn new(
+        live: Arc<LiveState>,
+        cancel: Arc<AtomicBool>,
+        thread: JoinHandle<Result<ScanTree>>,
+    ) -> Self {
+        Self {
+            live,
+            cancel,
+            thread: Some(thread),
+        }
+    }
+
+    /// Rolled-up stats of the directories finished so far plus the pending set.
+    /// Builds a tree from the live map, so call it at UI refresh rate, not in a tight loop.
+    pub fn snapshot(&self) -> ScanSnapshot {
+        let finished = self.is_finished();
+        let (tree, pending) = self.live.snapshot();
+        ScanSnapshot {
+            tree,
+            pending: if finished { Vec::new() } else { pending },
+            finished,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().map_or(true, |t| t.is_finished())
+    }
+
+    /// Request cooperative cancellation; `join` then returns the partial map.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Wait for the scan to finish and return the final rolled-up map.
+    pub fn join(self) -> Result<StatMap> {
+        self.join_tree().map(|t| t.to_stat_map())
+    }
+
+    /// Wait for the scan to finish and return the final tree.
+    pub fn join_tree(mut self) -> Result<ScanTree> {
+        match self.thread.take() {
+            Some(t) => t.join().map_err(|_| anyhow!("scan thread panicked"))?,
+            None => Err(anyhow!("scan already joined")),
+        }
+    }
+}
@@ -60,12 +60,7 @@ pub fn parallel_scan(
         .try_reduce(
             || ahash::AHashMap::default(),
             |mut acc, map| {
-                for (k, v) in map {
-                    let e = acc.entry(k).or_default();
-                    e.logical += v.logical;
-                    e.physical += v.physical;
-                    e.files += v.files;
-                }
+                crate::merge_stat_maps(&mut acc, map);
                 Ok(acc)
             },
         )
@@ -179,7 +174,7 @@ mod tests {
     use ahash::AHashMap as HashMap;
 
     use super::*;
-    use crate::Options;
+    use crate::{EntryKind, FileEntry, Options};
 
     #[derive(Clone, Debug, PartialEq, Eq)]
     #[allow(dead_code)]
@@ -188,10 +183,14 @@ mod tests {
         File(u64),
         SymlinkDir(PathBuf),
         SymlinkFile(u64),
+        // Hardlinked file: (ino, nlink, size)
+        Link(u64, u64, u64),
     }
 
     struct MockFileSystem {
         entries: HashMap<PathBuf, Vec<(String, MockKind)>>,
+        // Directories whose open fails with the given errno
+        unreadable: HashMap<PathBuf, i32>,
         visited: std::sync::Mutex<HashSet<PathBuf>>,
     }
 
@@ -200,12 +199,18 @@ mod tests {
             self.entries.insert(dir.to_path_buf(), items);
             self
         }
+
+        fn with_unreadable(mut self, dir: &Path, errno: i32) -> Self {
+            self.unreadable.insert(dir.to_path_buf(), errno);
+            self
+        }
     }
 
     impl Default for MockFileSystem {
         fn default() -> Self {
             Self {
                 entries: HashMap::default(),
+                unreadable: HashMap::default(),
                 visited: std::sync::Mutex::new(HashSet::new()),
             }
         }
@@ -216,6 +221,17 @@ mod tests {
             let opt = ctx.options;
             let dir = dctx.dir;
             let depth = dctx.depth;
+            // One directory read per call, like the real backends
+            ctx.throttle_io(1);
+            if let Some(&errno) = self.unreadable.get(dir) {
+                let err = crate::error_handling::ScanError::SystemCall {
+                    path: dir.to_path_buf(),
+                    call: "open",
+                    errno,
+                };
+                ctx.record_dir_error(&err);
+                return;
+            }
             let stat_cur = map.entry(dir.to_path_buf()).or_default();
             let Some(items) = self.entries.get(dir) else {
                 return;
@@ -223,6 +239,16 @@ mod tests {
             for (name, kind) in items {
                 // Build child path
                 let child = dir.join(name);
+                // Owner and mtime (days after the epoch) derived from the size so
+                // tests can split usage by uid and age
+                let entry = |kind, size: u64| FileEntry {
+                    logical: size,
+                    physical: size,
+                    mtime: size as i64 * 86_400,
+                    uid: Some((size % 2) as u32),
+                    gid: Some(100),
+                    ..FileEntry::new(dir, name.as_ref(), kind)
+                };
                 if crate::path_excluded(&child, opt) {
                     continue;
                 }
@@ -232,20 +258,36 @@ mod tests {
                             // simple visited set to approximate loop detection
                             let mut v = self.visited.lock().unwrap();
                             if v.insert(child.clone()) {
+                                ctx.visit(&entry(EntryKind::Dir, 0));
                                 ctx.enqueue_dir(child, depth + 1);
                             }
                         }
                     }
                     MockKind::File(sz) => {
                         if *sz >= opt.min_file_size {
-                            stat_cur.logical += *sz;
-                            stat_cur.physical += *sz;
-                            stat_cur.files += 1;
+                            ctx.record_file(stat_cur, &entry(EntryKind::File, *sz));
                             ctx.report_progress(opt, Some(&child));
                         }
                     }
+                    MockKind::Link(ino, nlink, sz) => {
+                        if *sz < opt.min_file_size {
+                            continue;
+                        }
+                        let e = FileEntry {
+                            dev: 1,
+                            ino: *ino,
+                            nlink: *nlink,
+                            ..entry(EntryKind::File, *sz)
+                        };
+                        if crate::common_ops::check_hardlink_duplicate(opt, 1, *ino) {
+                            ctx.record_link(&e);
+                        } else {
+                            ctx.record_file(stat_cur, &e);
+                        }
+                    }
                     MockKind::SymlinkDir(target) => {
                         if !opt.follow_links {
+                            ctx.record_symlink(stat_cur, &entry(EntryKind::Symlink, 0));
                             continue;
                         }
                         if opt.max_depth == 0 || depth < opt.max_depth {
@@ -257,12 +299,11 @@ mod tests {
                     }
                     MockKind::SymlinkFile(sz) => {
                         if !opt.follow_links {
+                            ctx.record_symlink(stat_cur, &entry(EntryKind::Symlink, 0));
                             continue;
                         }
                         if *sz >= opt.min_file_size {
-                            stat_cur.logical += *sz;
-                            stat_cur.physical += *sz;
-                            stat_cur.files += 1;
+                            ctx.record_file(stat_cur, &entry(EntryKind::File, *sz));
                             ctx.report_progress(opt, Some(&child));
                         }
                     }
@@ -303,6 +344,1210 @@ mod tests {
         assert_eq!(s_root.physical, 30);
     }
 
+    #[test]
+    fn stat_counts_dirs_symlinks_and_times() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+
+        // Layout: r/{l -> (file), d/{e/{f:1}}}
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![
+                    ("l".into(), MockKind::SymlinkFile(5)),
+                    ("d".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(&root.join("d"), vec![("e".into(), MockKind::Dir)])
+            .with_dir(&root.join("d/e"), vec![("f".into(), MockKind::File(1))]);
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+
+        let map = crate::scan_directory_with(&root, &opt, Arc::new(mock)).unwrap();
+        let s_root = map.get(&root).copied().unwrap();
+        assert_eq!((s_root.files, s_root.dirs, s_root.symlinks), (1, 2, 1));
+        assert_eq!(s_root.inodes(), 4);
+        assert_eq!(map.get(&root.join("d/e")).unwrap().dirs, 0);
+
+        // Unknown (0) times never win the min/max
+        let mut a = crate::Stat::default();
+        a.note_times(100, 0);
+        a.note_times(50, 70);
+        let mut b = crate::Stat::default();
+        b.note_times(0, 40);
+        a.merge(&b);
+        assert_eq!((a.max_mtime, a.min_atime), (100, 40));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_dirs_are_returned_as_structured_errors() {
+        use std::sync::Arc;
+
+        use crate::{ScanErrorKind, ScanErrorRecord};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+
+        // Layout: r/{a:10, locked/ (EACCES), loop/ (ELOOP)}
+        let mock = || {
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("a".into(), MockKind::File(10)),
+                        ("locked".into(), MockKind::Dir),
+                        ("loop".into(), MockKind::Dir),
+                    ],
+                )
+                .with_unreadable(&root.join("locked"), libc::EACCES)
+                .with_unreadable(&root.join("loop"), libc::ELOOP)
+        };
+        let opt = Options::default();
+
+        let tree = crate::scan_tree_with(&root, &opt, Arc::new(mock())).unwrap();
+        let mut errors = tree.errors().to_vec();
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            errors,
+            vec![
+                ScanErrorRecord {
+                    path: root.join("locked"),
+                    kind: ScanErrorKind::PermissionDenied,
+                    errno: Some(libc::EACCES),
+                    syscall: Some("open"),
+                },
+                ScanErrorRecord {
+                    path: root.join("loop"),
+                    kind: ScanErrorKind::Loop,
+                    errno: Some(libc::ELOOP),
+                    syscall: Some("open"),
+                },
+            ]
+        );
+        // Unreadable directories still appear in the tree, empty and incomplete
+        let locked = tree.find(&root.join("locked")).unwrap();
+        assert_eq!(tree.stat(locked).files, 0);
+        assert_eq!(tree.stat(locked).incomplete, 1);
+        let s_root = tree.stat(crate::ScanTree::ROOT);
+        assert_eq!((s_root.dirs, s_root.incomplete), (2, 2));
+
+        // A rescan replaces the errors kept in the options; the count keeps going
+        let tree = crate::scan_tree_with(&root, &opt, Arc::new(mock())).unwrap();
+        assert_eq!(tree.errors().len(), 2);
+        assert_eq!(*opt.errors.lock().unwrap(), tree.errors());
+        assert_eq!(
+            opt.error_count.load(std::sync::atomic::Ordering::Relaxed),
+            4
+        );
+    }
+
+    #[test]
+    fn cancelled_scan_marks_unread_dirs_incomplete() {
+        use std::sync::{atomic::Ordering, Arc};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+
+        // Layout: r/{a:10, d/{b:20}, e/{f/{c:5}}}; cancel once the root is listed
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![
+                    ("a".into(), MockKind::File(10)),
+                    ("d".into(), MockKind::Dir),
+                    ("e".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(&root.join("d"), vec![("b".into(), MockKind::File(20))])
+            .with_dir(&root.join("e"), vec![("f".into(), MockKind::Dir)])
+            .with_dir(&root.join("e/f"), vec![("c".into(), MockKind::File(5))]);
+        let mut opt = Options::default();
+        opt.threads = 1; // no worker may pick up a child before the cancel
+        let cancel = opt.cancel.clone();
+        let visitor = move |e: &FileEntry<'_>| {
+            if e.kind == EntryKind::Dir {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        };
+
+        let map =
+            crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock), Arc::new(visitor))
+                .unwrap();
+        let s_root = map.get(&root).copied().unwrap();
+        assert_eq!((s_root.logical, s_root.incomplete), (10, 2));
+        assert!(!s_root.is_complete());
+        for d in ["d", "e"] {
+            assert_eq!(map.get(&root.join(d)).unwrap().incomplete, 1, "{d}");
+        }
+        assert!(!map.contains_key(&root.join("e/f")));
+    }
+
+    #[test]
+    fn resumed_scan_matches_uninterrupted_scan() {
+        use std::sync::{atomic::Ordering, Arc};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        let ckpt = tmp.path().join("scan.ckpt");
+
+        // Layout: r/{a:10, d/{b:20}, e/{f/{c:5}}}
+        let mock = || {
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("a".into(), MockKind::File(10)),
+                        ("d".into(), MockKind::Dir),
+                        ("e".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(&root.join("d"), vec![("b".into(), MockKind::File(20))])
+                .with_dir(&root.join("e"), vec![("f".into(), MockKind::Dir)])
+                .with_dir(&root.join("e/f"), vec![("c".into(), MockKind::File(5))])
+        };
+        let mut opt = Options::default();
+        opt.threads = 1;
+        let full = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap();
+
+        // Cancel once the root is listed; the cancel path saves a checkpoint
+        opt.checkpoint_path = Some(ckpt.clone());
+        let cancel = opt.cancel.clone();
+        let visitor = move |e: &FileEntry<'_>| {
+            if e.kind == EntryKind::Dir {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        };
+        crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock()), Arc::new(visitor))
+            .unwrap();
+        let cp = crate::checkpoint::Checkpoint::load(&ckpt).unwrap();
+        assert_eq!(cp.root(), root.as_path());
+        assert_eq!(cp.pending_dirs(), 2);
+
+        let mut opt = Options::default();
+        opt.threads = 1;
+        opt.checkpoint_path = Some(ckpt.clone());
+        opt.resume_from = Some(Arc::new(cp));
+        let resumed = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap();
+        for (dir, s) in &full {
+            let r = resumed.get(dir).unwrap();
+            assert_eq!((r.logical, r.files, r.dirs), (s.logical, s.files, s.dirs));
+            assert!(r.is_complete(), "{}", dir.display());
+        }
+        assert_eq!(resumed.get(&root).unwrap().logical, 35);
+        assert!(!ckpt.exists(), "a finished scan removes its checkpoint");
+    }
+
+    #[test]
+    fn file_budget_stops_scan_and_estimates_rest() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+
+        // Layout: r/{d0..d9/{f0,f1,f2: 100 each}}; stop after 10 files
+        let names: Vec<String> = (0..10).map(|i| format!("d{i}")).collect();
+        let mut mock = MockFileSystem::default().with_dir(
+            &root,
+            names.iter().map(|n| (n.clone(), MockKind::Dir)).collect(),
+        );
+        for n in &names {
+            let files = (0..3).map(|i| (format!("f{i}"), MockKind::File(100)));
+            mock = mock.with_dir(&root.join(n), files.collect());
+        }
+        let mut opt = Options::default();
+        opt.threads = 1;
+        opt.budget_files = 10;
+
+        let tree = crate::scan_tree_with(&root, &opt, Arc::new(mock)).unwrap();
+        let est = tree.estimate().unwrap();
+        assert_eq!((est.unexplored_dirs, est.sampled_dirs), (6, 5));
+        // Identical directories: the estimate is exact and the interval collapses
+        let files = est.files;
+        assert_eq!((files.counted, files.total), (12, 30));
+        assert_eq!((files.low, files.high), (30, 30));
+        assert_eq!(est.logical.total, 3000);
+        assert!(!tree.stat(crate::ScanTree::ROOT).is_complete());
+        assert_eq!(opt.last_estimate.lock().unwrap().as_ref(), Some(est));
+    }
+
+    #[test]
+    fn io_rate_limit_slows_scan_without_changing_totals() {
+        use std::{sync::Arc, time::Instant};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        let names: Vec<String> = (0..30).map(|i| format!("d{i}")).collect();
+        let mut mock = MockFileSystem::default().with_dir(
+            &root,
+            names.iter().map(|n| (n.clone(), MockKind::Dir)).collect(),
+        );
+        for n in &names {
+            mock = mock.with_dir(&root.join(n), vec![("f".into(), MockKind::File(10))]);
+        }
+        let mut opt = Options::default();
+        opt.threads = 1;
+        // 31 directory reads at 100/s: ~0.2s after the 0.1s burst allowance
+        opt.io_ops_per_sec = 100;
+
+        let started = Instant::now();
+        let m = crate::scan_directory_with(&root, &opt, Arc::new(mock)).unwrap();
+        assert!(started.elapsed().as_millis() >= 150);
+        let s = m.get(&root).unwrap();
+        assert_eq!((s.files, s.logical), (30, 300));
+    }
+
+    #[test]
+    fn size_hints_visit_largest_subtrees_first() {
+        use std::sync::{Arc, Mutex};
+
+        struct Recording {
+            inner: MockFileSystem,
+            order: Mutex<Vec<PathBuf>>,
+        }
+        impl FileSystemScanner for Recording {
+            fn process_dir(&self, ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
+                self.order.lock().unwrap().push(dctx.dir.to_path_buf());
+                self.inner.process_dir(ctx, dctx, map);
+            }
+        }
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        // Discovery order small, big, mid; big holds its files one level down
+        let mock = || {
+            let files = |n: usize| {
+                (0..n)
+                    .map(|i| (format!("f{i}"), MockKind::File(1)))
+                    .collect()
+            };
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    ["small", "big", "mid"]
+                        .map(|n| (n.to_string(), MockKind::Dir))
+                        .to_vec(),
+                )
+                .with_dir(&root.join("small"), files(1))
+                .with_dir(&root.join("big"), vec![("inner".into(), MockKind::Dir)])
+                .with_dir(&root.join("big/inner"), files(9))
+                .with_dir(&root.join("mid"), files(4))
+        };
+        let mut opt = Options::default();
+        opt.threads = 1;
+        let scan = |opt: &Options| {
+            let rec = Arc::new(Recording {
+                inner: mock(),
+                order: Mutex::default(),
+            });
+            let map = crate::scan_directory_with(&root, opt, rec.clone()).unwrap();
+            let order = std::mem::take(&mut *rec.order.lock().unwrap());
+            let names: Vec<String> = order
+                .iter()
+                .map(|p| p.strip_prefix(&root).unwrap().display().to_string())
+                .collect();
+            (map, names)
+        };
+
+        let (prev, fifo) = scan(&opt);
+        assert_eq!(fifo, ["", "small", "big", "mid", "big/inner"]);
+        opt.size_hints = Some(Arc::new(crate::hints::SizeHints::from_stats(&prev)));
+        let (map, hinted) = scan(&opt);
+        assert_eq!(hinted, ["", "big", "big/inner", "mid", "small"]);
+        assert_eq!(map.get(&root).unwrap().files, 14);
+    }
+
+    #[test]
+    fn scan_roots_shares_dedupe_and_totals_roots() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+        std::fs::create_dir_all(a.join("sub")).unwrap();
+        std::fs::create_dir_all(&b).unwrap();
+        // One file hardlinked from both roots; a/sub is also given as a root
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &a,
+                vec![
+                    ("f".into(), MockKind::File(100)),
+                    ("link".into(), MockKind::Link(7, 2, 50)),
+                    ("sub".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(&a.join("sub"), vec![("g".into(), MockKind::File(10))])
+            .with_dir(
+                &b,
+                vec![
+                    ("h".into(), MockKind::File(20)),
+                    ("link".into(), MockKind::Link(7, 2, 50)),
+                ],
+            );
+        let mut opt = Options::default();
+        opt.threads = 1;
+
+        let roots = [a.clone(), b.clone(), a.join("sub")];
+        let scan = crate::scan_roots_with(&roots, &opt, Arc::new(mock)).unwrap();
+        let totals: Vec<(u64, u64)> = scan
+            .trees()
+            .iter()
+            .map(|t| {
+                let s = t.stat(crate::ScanTree::ROOT);
+                (s.files, s.logical)
+            })
+            .collect();
+        assert_eq!(totals, [(3, 160), (1, 20), (1, 10)]);
+        let total = scan.total();
+        assert_eq!((total.files, total.logical, total.dirs), (4, 180, 3));
+        // Freeing both roots frees the shared link too
+        assert_eq!(total.exclusive, 180);
+    }
+
+    #[test]
+    fn scan_roots_charges_shared_links_to_the_first_root() {
+        use std::{sync::Arc, time::Duration};
+
+        // Reads the first root late, so the second one sees the link first
+        struct LateFirst(MockFileSystem, PathBuf);
+        impl FileSystemScanner for LateFirst {
+            fn process_dir(&self, ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
+                if dctx.dir == self.1 {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                self.0.process_dir(ctx, dctx, map);
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let a = tmp.path().join("a");
+        let b = tmp.path().join("b");
+        std::fs::create_dir_all(&a).unwrap();
+        std::fs::create_dir_all(&b).unwrap();
+        let mock = MockFileSystem::default()
+            .with_dir(&a, vec![("link".into(), MockKind::Link(7, 2, 50))])
+            .with_dir(
+                &b,
+                vec![
+                    ("h".into(), MockKind::File(20)),
+                    ("link".into(), MockKind::Link(7, 2, 50)),
+                ],
+            );
+        let mut opt = Options::default();
+        opt.threads = 2;
+        opt.active_threads = Arc::new(std::sync::atomic::AtomicUsize::new(2));
+
+        let roots = [a.clone(), b.clone()];
+        let scan = crate::scan_roots_with(&roots, &opt, Arc::new(LateFirst(mock, a))).unwrap();
+        let totals: Vec<(u64, u64)> = scan
+            .trees()
+            .iter()
+            .map(|t| {
+                let s = t.stat(crate::ScanTree::ROOT);
+                (s.files, s.logical)
+            })
+            .collect();
+        assert_eq!(totals, [(1, 50), (1, 20)]);
+        assert_eq!(scan.total().logical, 70);
+    }
+
+    #[test]
+    fn checkpoint_writer_saves_while_running() {
+        use std::{
+            sync::Arc,
+            time::{Duration, Instant},
+        };
+
+        struct Slow(MockFileSystem);
+        impl FileSystemScanner for Slow {
+            fn process_dir(&self, ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
+                std::thread::sleep(Duration::from_millis(100));
+                self.0.process_dir(ctx, dctx, map);
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        let ckpt = tmp.path().join("scan.ckpt");
+        let names: Vec<String> = (0..30).map(|i| format!("d{i}")).collect();
+        let mut mock = MockFileSystem::default().with_dir(
+            &root,
+            names.iter().map(|n| (n.clone(), MockKind::Dir)).collect(),
+        );
+        for n in &names {
+            mock = mock.with_dir(&root.join(n), vec![("f".into(), MockKind::File(7))]);
+        }
+        let mut opt = Options::default();
+        opt.threads = 1;
+        opt.checkpoint_path = Some(ckpt.clone());
+        opt.checkpoint_every_secs = 1;
+
+        let handle = crate::start_scan_with(&root, &opt, Arc::new(Slow(mock))).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !ckpt.exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let cp = crate::checkpoint::Checkpoint::load(&ckpt).unwrap();
+        assert!(cp.pending_dirs() > 0 && cp.pending_dirs() < 30);
+        let map = handle.join().unwrap();
+        assert_eq!(map.get(&root).unwrap().logical, 7 * 30);
+        assert!(!ckpt.exists());
+    }
+
+    #[test]
+    fn scan_tree_rolls_up_and_converts() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+
+        // Layout: r/{a:10, d/{b:20, e/{c:5}}, f/}
+        let mock = || {
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("a".into(), MockKind::File(10)),
+                        ("d".into(), MockKind::Dir),
+                        ("f".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(
+                    &root.join("d"),
+                    vec![
+                        ("b".into(), MockKind::File(20)),
+                        ("e".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(&root.join("d/e"), vec![("c".into(), MockKind::File(5))])
+        };
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+
+        let mut tree = crate::scan_tree_with(&root, &opt, Arc::new(mock())).unwrap();
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree.stat(crate::ScanTree::ROOT).logical, 35);
+        let d = tree.find(&root.join("d")).unwrap();
+        assert_eq!(tree.name(d), "d");
+        assert_eq!(tree.stat(d).files, 2);
+        let e = tree.find(&root.join("d/e")).unwrap();
+        assert_eq!(tree.parent(e), Some(d));
+        assert_eq!(tree.path(e), root.join("d/e"));
+        tree.sort_children_by_physical();
+        let kids: Vec<_> = tree.children(crate::ScanTree::ROOT).to_vec();
+        assert_eq!(kids, vec![d, tree.find(&root.join("f")).unwrap()]);
+
+        // Compatibility map matches the classic result and stays under the root
+        let map = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap();
+        let conv = tree.to_stat_map();
+        assert_eq!(conv.len(), map.len());
+        for (p, s) in &map {
+            assert_eq!(
+                conv.get(p).map(|c| (c.files, c.logical)),
+                Some((s.files, s.logical))
+            );
+        }
+        assert!(conv.keys().all(|p| p.starts_with(&root)));
+        assert!(!conv.contains_key(tmp.path()));
+    }
+
+    #[test]
+    fn worker_arenas_merge_into_one_tree() {
+        use crate::{tree::DirArena, ScanTree, Stat};
+
+        let files = |n| Stat {
+            files: n,
+            ..Stat::default()
+        };
+        // Two workers that both saw r/d, interning the paths in different orders
+        let mut a = DirArena::default();
+        a.add_map([("/r/d/x".into(), files(1)), ("/r".into(), files(2))]);
+        let mut b = DirArena::default();
+        b.add_map([("/r/d".into(), files(4)), ("/r/d/y".into(), files(8))]);
+        b.add_map([("/other".into(), files(16))]);
+        a.absorb(b);
+
+        let mut tree = a.subtree(Path::new("/r"));
+        tree.roll_up();
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree.stat(ScanTree::ROOT).files, 15);
+        assert_eq!(tree.stat(ScanTree::ROOT).dirs, 3);
+        assert_eq!(tree.get(Path::new("/r/d")).unwrap().files, 13);
+        assert_eq!(
+            tree.path(tree.find(Path::new("/r/d/y")).unwrap()),
+            Path::new("/r/d/y")
+        );
+        assert_eq!(a.subtree(Path::new("/missing")).len(), 1);
+    }
+
+    #[test]
+    fn scan_handle_snapshots_partial_results() {
+        use std::{
+            sync::{Arc, Condvar, Mutex},
+            time::{Duration, Instant},
+        };
+
+        // Holds `slow` until the test opens the gate; sleeps on other dirs so the
+        // worker's periodic flush publishes them first.
+        struct Gated {
+            inner: MockFileSystem,
+            slow: PathBuf,
+            gate: Arc<(Mutex<bool>, Condvar)>,
+        }
+        impl FileSystemScanner for Gated {
+            fn process_dir(&self, ctx: &ScanContext, dctx: &DirContext, map: &mut StatMap) {
+                if dctx.dir == self.slow {
+                    let (open, cv) = &*self.gate;
+                    let mut g = open.lock().unwrap();
+                    while !*g {
+                        g = cv.wait(g).unwrap();
+                    }
+                } else {
+                    std::thread::sleep(Duration::from_millis(250));
+                }
+                self.inner.process_dir(ctx, dctx, map);
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        let slow = root.join("slow");
+        std::fs::create_dir_all(&slow).unwrap();
+        let gate = Arc::new((Mutex::new(false), Condvar::new()));
+        let scanner = Gated {
+            inner: MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("a".into(), MockKind::File(10)),
+                        ("slow".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(&slow, vec![("b".into(), MockKind::File(20))]),
+            slow: slow.clone(),
+            gate: gate.clone(),
+        };
+        let mut opt = Options::default();
+        opt.threads = 1;
+
+        let handle = crate::start_scan_with(&root, &opt, Arc::new(scanner)).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let snap = loop {
+            let snap = handle.snapshot();
+            if snap.tree.get(&root).map_or(0, |s| s.files) > 0 || Instant::now() > deadline {
+                break snap;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+        assert!(!snap.finished);
+        assert_eq!(snap.tree.get(&root).unwrap().logical, 10);
+        assert_eq!(snap.pending, vec![slow.clone()]);
+
+        *gate.0.lock().unwrap() = true;
+        gate.1.notify_all();
+        while !handle.is_finished() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        // Between the workers exiting and join, snapshots show the final tree
+        let snap = handle.snapshot();
+        assert!(snap.finished && snap.pending.is_empty());
+        assert_eq!(snap.tree.get(&root).unwrap().logical, 30);
+        let map = handle.join().unwrap();
+        assert_eq!(map.get(&root).unwrap().logical, 30);
+        assert_eq!(map.get(&slow).unwrap().files, 1);
+    }
+
+    #[test]
+    fn visitor_sees_counted_entries() {
+        use std::sync::{Arc, Mutex};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![
+                    ("a".into(), MockKind::File(10)),
+                    ("small".into(), MockKind::File(1)),
+                    ("d".into(), MockKind::Dir),
+                    ("l".into(), MockKind::SymlinkFile(7)),
+                ],
+            )
+            .with_dir(&root.join("d"), vec![("b".into(), MockKind::File(20))]);
+        let mut opt = Options::default();
+        opt.min_file_size = 5;
+
+        let seen: Arc<Mutex<Vec<(PathBuf, EntryKind, u64)>>> = Arc::default();
+        let sink = seen.clone();
+        let visitor = move |e: &FileEntry<'_>| {
+            sink.lock().unwrap().push((e.path(), e.kind, e.logical));
+        };
+        let map =
+            crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock), Arc::new(visitor))
+                .unwrap();
+        assert_eq!(map.get(&root).copied().unwrap_or_default().files, 2);
+
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            seen,
+            vec![
+                (root.join("a"), EntryKind::File, 10),
+                (root.join("d"), EntryKind::Dir, 0),
+                (root.join("d").join("b"), EntryKind::File, 20),
+                (root.join("l"), EntryKind::Symlink, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn owner_visitor_rolls_up_per_dir() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+
+        // Layout: r/{a:10, d/{e/{b:21, c:31}}}; odd sizes belong to uid 1
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![
+                    ("a".into(), MockKind::File(10)),
+                    ("d".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(&root.join("d"), vec![("e".into(), MockKind::Dir)])
+            .with_dir(
+                &root.join("d/e"),
+                vec![
+                    ("b".into(), MockKind::File(21)),
+                    ("c".into(), MockKind::File(31)),
+                ],
+            );
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+
+        let owners = Arc::new(crate::owner::OwnerVisitor::new());
+        crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock), owners.clone()).unwrap();
+        let report = owners.report(&root);
+        let users = report.total.users_by_size();
+        assert_eq!(
+            users
+                .iter()
+                .map(|(u, s)| (*u, s.logical, s.files))
+                .collect::<Vec<_>>(),
+            vec![(1, 52, 2), (0, 10, 1)]
+        );
+        assert_eq!(report.total.groups[&100].logical, 62);
+        // Intermediate directory without files of its own still gets the subtree
+        let d = &report.per_dir[&root.join("d")];
+        assert_eq!(d.users.len(), 1);
+        assert_eq!(d.users[&1].logical, 52);
+    }
+
+    #[test]
+    fn mount_visitor_splits_usage_by_filesystem() {
+        use std::sync::Arc;
+
+        use crate::mounts::{MountTable, MountVisitor};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        let abs = std::fs::canonicalize(&root).unwrap();
+
+        // r/{a:10, usb/{b:20, d/{c:30}}, "sp ace"/{e:40}}; r/net is mounted but
+        // not listed (as if skipped by one_file_system)
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![
+                    ("a".into(), MockKind::File(10)),
+                    ("usb".into(), MockKind::Dir),
+                    ("sp ace".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(
+                &root.join("usb"),
+                vec![
+                    ("b".into(), MockKind::File(20)),
+                    ("d".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(&root.join("usb/d"), vec![("c".into(), MockKind::File(30))])
+            .with_dir(&root.join("sp ace"), vec![("e".into(), MockKind::File(40))]);
+        let r = abs.display();
+        let table = MountTable::parse_mountinfo(&format!(
+            "1 0 8:1 / / rw - ext4 /dev/sda1 rw\n\
+             2 1 8:17 / {r}/usb rw - vfat /dev/sdb1 rw\n\
+             3 1 0:50 / {r}/net rw shared:5 - nfs4 srv:/x rw\n\
+             4 1 0:51 / {r}/sp\\040ace rw - tmpfs tmp rw\n\
+             5 1 0:52 / {r}/sp\\040ace rw - tmpfs hidden rw\n"
+        ));
+        assert_eq!(table.mounts()[3].mount_point, abs.join("sp ace"));
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+
+        let mounts = Arc::new(MountVisitor::new());
+        crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock), mounts.clone()).unwrap();
+        let report = mounts.report(&root, &table);
+        let usage: Vec<(&str, u64, u64, u64)> = report
+            .mounts
+            .iter()
+            .map(|u| {
+                (
+                    u.mount.dev.as_str(),
+                    u.stat.logical,
+                    u.stat.files,
+                    u.stat.dirs,
+                )
+            })
+            .collect();
+        assert_eq!(
+            usage,
+            vec![("8:17", 50, 2, 2), ("0:52", 40, 1, 1), ("8:1", 10, 1, 1)]
+        );
+        let skipped: Vec<&str> = report.skipped.iter().map(|m| m.fs_type.as_str()).collect();
+        assert_eq!(skipped, vec!["nfs4"]);
+        // Directories carry their own bytes and device
+        let (i, s) = report.per_dir[&root.join("usb/d")];
+        assert_eq!(
+            (report.mounts[i].mount.dev.as_str(), s.logical),
+            ("8:17", 30)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn mount_visitor_skips_excluded_dirs_on_the_platform_backend() {
+        use std::sync::Arc;
+
+        use crate::mounts::{MountTable, MountVisitor};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(root.join("skip/sub")).unwrap();
+        std::fs::create_dir_all(root.join("keep")).unwrap();
+        std::fs::write(root.join("skip/sub/b"), b"bb").unwrap();
+        std::fs::write(root.join("keep/a"), b"a").unwrap();
+        let table = MountTable::parse_mountinfo("1 0 8:1 / / rw - ext4 /dev/sda1 rw\n");
+        let mut opt = Options::default();
+        opt.exclude_contains = vec!["skip".into()];
+
+        let mounts = Arc::new(MountVisitor::new());
+        crate::scan_directory_with_visitor(
+            &root,
+            &opt,
+            Arc::new(crate::platform_scanner()),
+            mounts.clone(),
+        )
+        .unwrap();
+        let report = mounts.report(&root, &table);
+        let s = report.mounts[0].stat;
+        assert_eq!((s.files, s.logical, s.dirs), (1, 1, 2));
+        assert!(report
+            .per_dir
+            .keys()
+            .all(|p| !p.starts_with(root.join("skip"))));
+    }
+
+    #[test]
+    fn histogram_visitor_buckets_and_rolls_up() {
+        use std::sync::Arc;
+
+        use crate::histogram::{HistogramVisitor, SizeHistogram, HIST_BUCKETS};
+
+        assert_eq!(SizeHistogram::bucket_of(0), 0);
+        assert_eq!(SizeHistogram::bucket_of(1), 1);
+        assert_eq!(SizeHistogram::bucket_of(4095), 12);
+        assert_eq!(SizeHistogram::bucket_of(4096), 13);
+        assert_eq!(SizeHistogram::bucket_of(u64::MAX), HIST_BUCKETS - 1);
+        assert_eq!(SizeHistogram::bucket_range(13), (4096, Some(8192)));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        // Layout: r/{a:0, d/{b:5000, c:6000}}
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![("a".into(), MockKind::File(0)), ("d".into(), MockKind::Dir)],
+            )
+            .with_dir(
+                &root.join("d"),
+                vec![
+                    ("b".into(), MockKind::File(5000)),
+                    ("c".into(), MockKind::File(6000)),
+                ],
+            );
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+
+        // Fan out to two visitors at once
+        let hist = Arc::new(HistogramVisitor::new());
+        let owners = Arc::new(crate::owner::OwnerVisitor::new());
+        let set = crate::VisitorSet(vec![hist.clone(), owners.clone()]);
+        crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock), Arc::new(set)).unwrap();
+        let report = hist.report(&root);
+        let h_root = report[&root];
+        assert_eq!(h_root.total_files(), 3);
+        assert_eq!(h_root.files[0], 1);
+        assert_eq!((h_root.files[13], h_root.bytes[13]), (2, 11000));
+        assert_eq!(report[&root.join("d")].total_files(), 2);
+        assert_eq!(owners.report(&root).total.groups[&100].files, 3);
+    }
+
+    #[test]
+    fn classify_visitor_runs_inside_scan() {
+        use std::sync::Arc;
+
+        use crate::classify::{ClassifyMode, ClassifyVisitor};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![
+                    ("a.rs".into(), MockKind::File(10)),
+                    ("b.png".into(), MockKind::File(20)),
+                    ("d".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(&root.join("d"), vec![("c.RS".into(), MockKind::File(5))]);
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+        opt.min_file_size = 6;
+
+        let v = Arc::new(ClassifyVisitor::with_threads(ClassifyMode::Basic, 3));
+        crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock), v.clone()).unwrap();
+        let stats = v.finish();
+        // c.RS is below the scan's min size, so it is not classified either
+        assert_eq!(stats.by_category["source"].files, 1);
+        assert_eq!(stats.by_category["image"].bytes, 20);
+        assert_eq!(
+            stats.top_consumers.keys().copied().collect::<Vec<_>>(),
+            [10, 20]
+        );
+
+        // Deep mode sniffs contents on the worker pool: PNG magic behind a .dat name
+        let dir = tmp.path().join("deep");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("x.dat"), b"\x89PNG\r\n\x1a\n0000").unwrap();
+        let stats =
+            crate::classify::classify_directory(&dir, &Options::default(), ClassifyMode::Deep);
+        assert_eq!(stats.by_category["image"].files, 1);
+        assert_eq!(stats.by_extension["dat"].files, 1);
+    }
+
+    #[cfg(all(target_os = "linux", not(target_env = "musl")))]
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn special_files_count_only_when_requested() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a"), b"aa").unwrap();
+        let fifo = std::ffi::CString::new(root.join("p").as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        let mut opt = Options::default();
+        let scan = |opt: &Options| {
+            let map = crate::scan_directory_with(
+                &root,
+                opt,
+                std::sync::Arc::new(crate::platform_scanner()),
+            )
+            .unwrap();
+            map.get(&root).copied().unwrap_or_default()
+        };
+        let s = scan(&opt);
+        assert_eq!((s.files, s.logical), (1, 2));
+        opt.count_special_files = true;
+        assert_eq!(scan(&opt).files, 2);
+    }
+
+    #[test]
+    fn statx_entry_leaves_unfetched_fields_unset() {
+        let mut stx: libc::statx = unsafe { std::mem::zeroed() };
+        stx.stx_mask = libc::STATX_SIZE | libc::STATX_MODE;
+        stx.stx_uid = 1000;
+        let e = crate::common_ops::statx_entry(Path::new("/r"), b"a", EntryKind::File, &stx, 1, 1);
+        assert_eq!((e.uid, e.gid, e.allocated), (None, None, None));
+
+        stx.stx_mask |= libc::STATX_UID | libc::STATX_GID | libc::STATX_BLOCKS;
+        let e = crate::common_ops::statx_entry(Path::new("/r"), b"a", EntryKind::File, &stx, 1, 1);
+        assert_eq!((e.uid, e.gid, e.allocated), (Some(1000), Some(0), Some(0)));
+    }
+
+    #[test]
+    fn sparse_visitor_flags_sparse_and_slack() {
+        use crate::{sparse::SparseVisitor, FileVisitor};
+
+        let root = PathBuf::from("/r");
+        let d = root.join("d");
+        let v = SparseVisitor::new(1);
+        let file = |parent, name: &'static str, logical, physical| FileEntry {
+            logical,
+            physical,
+            ..FileEntry::new(parent, name.as_ref(), EntryKind::File)
+        };
+        // Two sparse images (only the bigger hole is kept), one tiny file on a 4K block
+        v.visit(&file(&root, "vm.img", 1 << 30, 1 << 20));
+        v.visit(&file(&d, "small.img", 1 << 20, 4096));
+        v.visit(&file(&d, "tiny", 10, 4096));
+        v.visit(&file(&d, "dense", 8192, 8192));
+        let report = v.report(&root);
+        assert_eq!(report.top_sparse.len(), 1);
+        assert_eq!(report.top_sparse[0].path, root.join("vm.img"));
+        assert_eq!(report.top_sparse[0].unallocated, (1 << 30) - (1 << 20));
+        let r = report.per_dir[&root];
+        assert_eq!(
+            (r.sparse_files, r.wasteful_files, r.slack_bytes),
+            (2, 1, 4086)
+        );
+        assert_eq!(report.per_dir[&d].sparse_bytes, (1 << 20) - 4096);
+
+        // No blocks at all: physical falls back to logical, the allocation does not
+        let v = SparseVisitor::new(1);
+        v.visit(&FileEntry {
+            allocated: Some(0),
+            ..file(&root, "hole", 1 << 20, 1 << 20)
+        });
+        assert_eq!(v.report(&root).per_dir[&root].sparse_bytes, 1 << 20);
+
+        // Same for a real file that was only truncated to its size
+        #[cfg(unix)]
+        {
+            use std::{os::unix::fs::MetadataExt, sync::Arc};
+
+            let tmp = tempfile::tempdir().unwrap();
+            let f = std::fs::File::create(tmp.path().join("hole")).unwrap();
+            f.set_len(1 << 20).unwrap();
+            if f.metadata().unwrap().blocks() == 0 {
+                let mut opt = Options::default();
+                opt.compute_physical = true;
+                let v = Arc::new(SparseVisitor::new(1));
+                crate::scan_directory_with_visitor(
+                    tmp.path(),
+                    &opt,
+                    Arc::new(crate::platform_scanner()),
+                    v.clone(),
+                )
+                .unwrap();
+                let report = v.report(tmp.path());
+                assert_eq!(report.per_dir[tmp.path()].sparse_files, 1);
+                assert_eq!(report.top_sparse[0].physical, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn extent_visitor_counts_shared_extents_once() {
+        use crate::{extents::ExtentVisitor, FileVisitor};
+
+        let root = PathBuf::from("/r");
+        let (a, b) = (root.join("a"), root.join("b"));
+        let v = ExtentVisitor::new(8);
+        let file =
+            |parent, name: &'static str| FileEntry::new(parent, name.as_ref(), EntryKind::File);
+        // Extent 100 is reflinked across a/ and b/, extent 200 twice inside a/
+        v.add_extents(&file(&a, "f1"), [(100, 4096, true), (1, 512, false)]);
+        v.add_extents(&file(&b, "f2"), [(100, 4096, true)]);
+        v.add_extents(&file(&a, "f3"), [(200, 8192, true)]);
+        v.add_extents(&file(&a, "f4"), [(200, 8192, true)]);
+        let report = v.report(&root);
+        let r = report.per_dir[&root];
+        assert_eq!(
+            (r.exclusive, r.shared, r.shared_unique),
+            (512, 24576, 12288)
+        );
+        assert_eq!(report.per_dir[&a].shared_unique, 8192);
+        assert_eq!(report.per_dir[&b].shared_unique, 0);
+        assert_eq!(report.top_shared.len(), 4);
+        assert_eq!(report.top_shared[0].shared, 8192);
+
+        // A freshly written file has no shared extents, with or without FIEMAP
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("data"), vec![7u8; 8192]).unwrap();
+        let v = ExtentVisitor::new(8);
+        v.visit(&FileEntry {
+            logical: 8192,
+            physical: 8192,
+            ..FileEntry::new(tmp.path(), "data".as_ref(), EntryKind::File)
+        });
+        let t = v.report(tmp.path()).per_dir[tmp.path()];
+        assert_eq!((t.exclusive, t.shared), (8192, 0));
+    }
+
+    #[test]
+    fn hardlink_groups_and_split_attribution() {
+        use std::sync::Arc;
+
+        use crate::hardlink::HardlinkVisitor;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        // Layout: r/{a:ino7, x:4, d/{b:ino7}, e/{c:ino7}}; ino 7 has one link outside
+        let mock = || {
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("a".into(), MockKind::Link(7, 4, 300)),
+                        ("x".into(), MockKind::File(4)),
+                        ("d".into(), MockKind::Dir),
+                        ("e".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(
+                    &root.join("d"),
+                    vec![("b".into(), MockKind::Link(7, 4, 300))],
+                )
+                .with_dir(
+                    &root.join("e"),
+                    vec![("c".into(), MockKind::Link(7, 4, 300))],
+                )
+        };
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+        opt.inode_cache = Some(Arc::new(dashmap::DashMap::new()));
+
+        let links = Arc::new(HardlinkVisitor::new());
+        let mut map =
+            crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock()), links.clone())
+                .unwrap();
+        let report = links.report();
+        assert_eq!(report.groups.len(), 1);
+        let g = &report.groups[0];
+        assert_eq!((g.ino, g.nlink, g.physical), (7, 4, 300));
+        assert_eq!(
+            g.paths,
+            vec![root.join("a"), root.join("d/b"), root.join("e/c")]
+        );
+        assert_eq!(report.shared_bytes(), 600);
+        assert_eq!(map[&root].physical, 304);
+
+        // Dedupe gave all bytes to one directory; split gives each 100
+        links.apply_split(&root, &mut map);
+        assert_eq!(map[&root].physical, 304);
+        assert_eq!(map[&root.join("d")].physical, 100);
+        assert_eq!(map[&root.join("e")].physical, 100);
+        assert!(!map.contains_key(tmp.path()));
+
+        // Without dedupe every link is counted; split brings it back to one copy
+        opt.inode_cache = None;
+        let links = Arc::new(HardlinkVisitor::new());
+        let mut map =
+            crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock()), links.clone())
+                .unwrap();
+        assert_eq!(map[&root].physical, 904);
+        links.apply_split(&root, &mut map);
+        assert_eq!(map[&root].physical, 304);
+        assert_eq!(map[&root.join("e")].physical, 100);
+    }
+
+    #[test]
+    fn exclusive_size_skips_shared_links() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        // Ino 7 is linked from d and e; ino 8 has one more link outside the tree
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![
+                    ("d".into(), MockKind::Dir),
+                    ("e".into(), MockKind::Dir),
+                    ("f".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(
+                &root.join("d"),
+                vec![
+                    ("a".into(), MockKind::Link(7, 2, 300)),
+                    ("x".into(), MockKind::File(10)),
+                ],
+            )
+            .with_dir(
+                &root.join("e"),
+                vec![("b".into(), MockKind::Link(7, 2, 300))],
+            )
+            .with_dir(
+                &root.join("f"),
+                vec![("c".into(), MockKind::Link(8, 2, 50))],
+            );
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+        opt.inode_cache = Some(std::sync::Arc::new(dashmap::DashMap::new()));
+
+        let map = crate::scan_directory_with(&root, &opt, std::sync::Arc::new(mock)).unwrap();
+        assert_eq!(map[&root].physical, 360);
+        assert_eq!(map[&root].exclusive, 310);
+        assert_eq!(map[&root.join("d")].exclusive, 10);
+        assert_eq!(map[&root.join("e")].exclusive, 0);
+        assert_eq!(map[&root.join("f")].exclusive, 0);
+    }
+
+    #[test]
+    fn age_visitor_buckets_by_mtime() {
+        use std::sync::Arc;
+
+        use crate::age::{AgeVisitor, TimeKind};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        // Mock mtime is `size` days; now is day 400, so ages are a:10, b:100, c:380
+        let mock = MockFileSystem::default()
+            .with_dir(
+                &root,
+                vec![
+                    ("a".into(), MockKind::File(390)),
+                    ("d".into(), MockKind::Dir),
+                ],
+            )
+            .with_dir(
+                &root.join("d"),
+                vec![
+                    ("b".into(), MockKind::File(300)),
+                    ("c".into(), MockKind::File(20)),
+                ],
+            );
+        let mut opt = Options::default();
+        opt.compute_physical = false;
+
+        let ages = Arc::new(AgeVisitor::with_now(
+            TimeKind::Mtime,
+            &[365, 30, 90],
+            400 * 86_400,
+        ));
+        assert_eq!(ages.days(), &[30, 90, 365]);
+        crate::scan_directory_with_visitor(&root, &opt, Arc::new(mock), ages.clone()).unwrap();
+        let report = ages.report(&root);
+        let r = &report[&root];
+        assert_eq!(r.files, vec![1, 0, 1, 1]);
+        assert_eq!(r.older_than(0), (2, 320));
+        assert_eq!(r.older_than(2), (1, 20));
+        assert_eq!(r.total_bytes(), 710);
+        assert_eq!(report[&root.join("d")].files, vec![0, 0, 1, 1]);
+    }
+
     #[test]
     fn filter_exclude_contains() {
         let tmp = tempfile::tempdir().unwrap();
@@ -411,6 +1656,223 @@ mod tests {
         assert_eq!(s_root.logical, 100);
     }
 
+    #[test]
+    fn include_filters_count_only_matching_files() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        let mock = || {
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("a.log".into(), MockKind::File(10)),
+                        ("b.txt".into(), MockKind::File(20)),
+                        ("sub".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(
+                    &root.join("sub"),
+                    vec![
+                        ("c.log".into(), MockKind::File(30)),
+                        ("d.bin".into(), MockKind::File(40)),
+                    ],
+                )
+        };
+        let base = || crate::OptionsBuilder::new().threads(1);
+
+        // Directories are still walked, so sub/c.log counts
+        let opt = base().with_include_glob(["*.log".to_string()]).build();
+        let map = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap();
+        assert_eq!((map[&root].files, map[&root].logical), (2, 40));
+        assert_eq!(map[&root.join("sub")].logical, 30);
+
+        // Globs and regexes combine as "either matches"
+        let opt = base()
+            .with_include_glob(["*.log".to_string()])
+            .with_include_regex([r"/sub/.*\.bin$".to_string()])
+            .build();
+        let map = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap();
+        assert_eq!((map[&root].files, map[&root].logical), (3, 80));
+
+        // A bad pattern fails the scan instead of counting everything
+        let opt = base().with_include_regex(["(".to_string()]).build();
+        let err = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap_err();
+        assert!(err.to_string().contains("invalid include regex"));
+    }
+
+    #[test]
+    fn exclude_stats_charge_skipped_bytes_per_pattern() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        let nm = root.join("node_modules");
+        let mock = || {
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("keep.txt".into(), MockKind::File(1000)),
+                        ("a.tmp".into(), MockKind::File(3)),
+                        ("node_modules".into(), MockKind::Dir),
+                        ("target".into(), MockKind::Dir),
+                        ("cache".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(
+                    &nm,
+                    vec![
+                        ("x.js".into(), MockKind::File(100)),
+                        ("deep".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(&nm.join("deep"), vec![("y.js".into(), MockKind::File(50))])
+                .with_dir(
+                    &root.join("target"),
+                    vec![("bin".into(), MockKind::File(7))],
+                )
+                .with_dir(&root.join("cache"), vec![("c".into(), MockKind::File(9))])
+        };
+        let base = || {
+            crate::OptionsBuilder::new()
+                .threads(1)
+                .with_exclude_contains(["node_modules".to_string(), "target".to_string()])
+                .with_exclude_glob(["**/*.tmp".to_string()])
+                .with_exclude_regex(["/cache$".to_string()])
+                .build()
+        };
+
+        let map = crate::scan_directory_with(&root, &base(), Arc::new(mock())).unwrap();
+        assert_eq!((map[&root].files, map[&root].logical), (1, 1000));
+
+        // Same totals, but excluded paths are walked and charged to their pattern
+        let mut opt = base();
+        let stats = Arc::new(crate::exclusions::ExcludeStats::new());
+        opt.exclude_stats = Some(stats.clone());
+        let map = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap();
+        assert_eq!((map[&root].files, map[&root].logical), (1, 1000));
+        let usage: Vec<_> = stats
+            .report()
+            .into_iter()
+            .map(|u| (u.pattern, u.files, u.dirs, u.logical))
+            .collect();
+        assert_eq!(
+            usage,
+            [
+                ("**/*.tmp".to_string(), 1, 0, 3),
+                ("/cache$".to_string(), 1, 1, 9),
+                ("node_modules".to_string(), 2, 1, 150),
+                ("target".to_string(), 1, 1, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_expressions_select_counted_files() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        std::fs::create_dir_all(&root).unwrap();
+        const BIG: u64 = 200 << 20;
+        // The mock derives mtime (days after the epoch) and uid from the size:
+        // old.log is decades old, new.log about two years, big.iso in the future
+        let mock = || {
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("big.iso".into(), MockKind::File(BIG)),
+                        ("old.log".into(), MockKind::File(11)),
+                        ("new.log".into(), MockKind::File(20_001)),
+                        ("sub".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(&root.join("sub"), vec![("x.iso".into(), MockKind::File(5))])
+        };
+        let scan = |expr: &str| {
+            let opt = crate::OptionsBuilder::new()
+                .threads(1)
+                .with_filter_expr([expr.to_string()])
+                .build();
+            crate::scan_directory_with(&root, &opt, Arc::new(mock()))
+        };
+        let count = |expr: &str| {
+            let s = scan(expr).unwrap()[&root];
+            (s.files, s.logical)
+        };
+
+        assert_eq!(count("size>100M"), (1, BIG));
+        assert_eq!(count("name~*.log and mtime<10000d"), (1, 20_001));
+        assert_eq!(
+            count("name~*.iso || (owner=1 && !size<100)"),
+            (3, BIG + 20_001 + 5)
+        );
+        // Adjacent tests are ANDed; directories are still walked
+        assert_eq!(count("type=f name~'*.iso'"), (2, BIG + 5));
+        assert_eq!(count("not type=f"), (0, 0));
+
+        for bad in ["size~1", "size>", "colour=red", "(size>1", "name~a and"] {
+            let err = scan(bad).unwrap_err().to_string();
+            assert!(err.contains("invalid filter expression"), "{bad}: {err}");
+        }
+    }
+
+    #[test]
+    fn ignore_files_drop_or_report_matched_paths() {
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("r");
+        let sub = root.join("sub");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.tmp\nbuild/\n").unwrap();
+        // Deeper files override shallower ones; .duignore adds to .ignore
+        std::fs::write(sub.join(".ignore"), "!keep.tmp\n").unwrap();
+        std::fs::write(sub.join(".duignore"), "c.bin\n").unwrap();
+        let mock = || {
+            MockFileSystem::default()
+                .with_dir(
+                    &root,
+                    vec![
+                        ("a.tmp".into(), MockKind::File(1)),
+                        ("b.txt".into(), MockKind::File(2)),
+                        ("build".into(), MockKind::Dir),
+                        ("sub".into(), MockKind::Dir),
+                    ],
+                )
+                .with_dir(&root.join("build"), vec![("x".into(), MockKind::File(8))])
+                .with_dir(
+                    &sub,
+                    vec![
+                        ("keep.tmp".into(), MockKind::File(16)),
+                        ("c.bin".into(), MockKind::File(32)),
+                        // `build/` only matches directories
+                        ("build".into(), MockKind::File(64)),
+                        ("other.tmp".into(), MockKind::File(128)),
+                    ],
+                )
+        };
+
+        let mut opt = crate::OptionsBuilder::new().threads(1).build();
+        opt.ignore_files = true;
+        let map = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap();
+        assert_eq!((map[&root].files, map[&root].logical), (3, 82));
+        assert_eq!(map[&root].ignored, 0);
+        assert!(!map.contains_key(&root.join("build")));
+
+        // Report mode walks ignored paths and keeps their bytes apart
+        opt.ignore_report = true;
+        let map = crate::scan_directory_with(&root, &opt, Arc::new(mock())).unwrap();
+        assert_eq!((map[&root].files, map[&root].logical), (3, 82));
+        assert_eq!(map[&root].ignored, 1 + 8 + 32 + 128);
+        assert_eq!(map[&root.join("build")].ignored, 8);
+    }
+
     #[test]
     fn symlink_follow_false_skips() {
         let tmp = tempfile::tempdir().unwrap();
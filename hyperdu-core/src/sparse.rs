@@ -0,0 +1,147 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use ahash::AHashMap as HashMap;
+use dashmap::DashMap;
+use serde::Serialize;
+
+use crate::{EntryKind, FileEntry, FileVisitor};
+
+/// Allocation waste and sparseness of the files in a directory.
+///
+/// A file is *sparse* when less than half of its logical size is allocated and
+/// *wasteful* when block rounding takes more than half of its allocation (tiny
+/// files on large blocks). Compressed filesystems also show up as sparse.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SlackStats {
+    /// Allocated bytes beyond the logical size, summed over all files.
+    pub slack_bytes: u64,
+    pub wasteful_files: u64,
+    pub sparse_files: u64,
+    /// Logical bytes not backed by allocation, summed over sparse files.
+    pub sparse_bytes: u64,
+}
+
+impl SlackStats {
+    pub fn merge(&mut self, other: &SlackStats) {
+        self.slack_bytes += other.slack_bytes;
+        self.wasteful_files += other.wasteful_files;
+        self.sparse_files += other.sparse_files;
+        self.sparse_bytes += other.sparse_bytes;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct SparseFile {
+    /// Logical size minus allocated size.
+    pub unallocated: u64,
+    pub path: PathBuf,
+    pub logical: u64,
+    pub physical: u64,
+}
+
+/// Sparse/slack breakdown of a scan.
+#[derive(Clone, Debug, Default)]
+pub struct SparseReport {
+    /// Sparse files with the most unallocated bytes, largest first.
+    pub top_sparse: Vec<SparseFile>,
+    /// Rolled-up slack per directory.
+    pub per_dir: HashMap<PathBuf, SlackStats>,
+}
+
+/// Visitor that flags sparse files and sums allocation slack per directory.
+///
+/// Compares the logical size with the raw allocation (`FileEntry::allocated`),
+/// so files with no blocks at all count as sparse too. Entries without an
+/// allocation fall back to `physical`, which equals the logical size unless
+/// `compute_physical` is set; such files never look sparse.
+pub struct SparseVisitor {
+    top: usize,
+    dirs: DashMap<PathBuf, SlackStats>,
+    // Min-heap holding the `top` largest sparse files seen so far
+    sparse: Mutex<BinaryHeap<Reverse<SparseFile>>>,
+}
+
+impl SparseVisitor {
+    /// Keep the `top` sparse files with the most unallocated bytes.
+    pub fn new(top: usize) -> Self {
+        Self {
+            top,
+            dirs: DashMap::new(),
+            sparse: Mutex::new(BinaryHeap::new()),
+        }
+    }
+
+    pub fn report(&self, root: &Path) -> SparseReport {
+        let mut per_dir: HashMap<PathBuf, SlackStats> = self
+            .dirs
+            .iter()
+            .map(|e| (e.key().clone(), *e.value()))
+            .collect();
+        crate::rollup::rollup_to_root(&mut per_dir, root, SlackStats::merge);
+        let mut top_sparse: Vec<SparseFile> = self
+            .sparse
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.0.clone())
+            .collect();
+        top_sparse.sort_unstable_by(|a, b| b.cmp(a));
+        SparseReport {
+            top_sparse,
+            per_dir,
+        }
+    }
+
+    fn note_sparse(&self, entry: &FileEntry<'_>, physical: u64, unallocated: u64) {
+        if self.top == 0 {
+            return;
+        }
+        let mut heap = self.sparse.lock().unwrap();
+        if heap.len() >= self.top && heap.peek().is_some_and(|m| unallocated <= m.0.unallocated) {
+            return;
+        }
+        heap.push(Reverse(SparseFile {
+            unallocated,
+            path: entry.path(),
+            logical: entry.logical,
+            physical,
+        }));
+        if heap.len() > self.top {
+            heap.pop();
+        }
+    }
+}
+
+impl FileVisitor for SparseVisitor {
+    fn visit(&self, entry: &FileEntry<'_>) {
+        if entry.kind != EntryKind::File {
+            return;
+        }
+        let (logical, physical) = (entry.logical, entry.allocated.unwrap_or(entry.physical));
+        let mut s = SlackStats {
+            slack_bytes: physical.saturating_sub(logical),
+            ..SlackStats::default()
+        };
+        if logical < physical / 2 {
+            s.wasteful_files = 1;
+        }
+        if physical < logical / 2 {
+            s.sparse_files = 1;
+            s.sparse_bytes = logical - physical;
+            self.note_sparse(entry, physical, s.sparse_bytes);
+        }
+        if let Some(mut d) = self.dirs.get_mut(entry.parent) {
+            d.merge(&s);
+            return;
+        }
+        self.dirs
+            .entry(entry.parent.to_path_buf())
+            .or_default()
+            .merge(&s);
+    }
+}
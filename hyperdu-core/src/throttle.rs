@@ -0,0 +1,135 @@
+//! I/O throttling for scans on busy machines: a shared rate limit on
+//! directory-read and stat calls, a cap on in-flight io_uring SQEs and the
+//! idle I/O priority class.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::Options;
+
+/// Unused rate a limiter may save up and spend at once.
+const MAX_BURST: Duration = Duration::from_millis(100);
+
+/// Rate limit shared by all workers of a scan. Each operation reserves the
+/// next free time slot (1/rate apart) and sleeps until it comes up.
+pub(crate) struct IoThrottle {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl IoThrottle {
+    /// Limiter for `opt.io_ops_per_sec`, if set.
+    pub(crate) fn from_options(opt: &Options) -> Option<Self> {
+        (opt.io_ops_per_sec > 0).then(|| Self {
+            interval: Duration::from_secs(1) / opt.io_ops_per_sec.min(u32::MAX as u64) as u32,
+            next: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Block until `n` more operations fit into the rate.
+    pub(crate) fn acquire(&self, n: u32) {
+        let now = Instant::now();
+        let at = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let at = (*next).max(now.checked_sub(MAX_BURST).unwrap_or(now));
+            *next = at + self.interval * n;
+            at
+        };
+        if at > now {
+            std::thread::sleep(at - now);
+        }
+    }
+}
+
+/// Clamp an io_uring batch or queue depth to `max_inflight`
+/// (`Options::uring_max_inflight`, 0 = no cap). Tuners go through this so
+/// they never push past the cap.
+pub fn cap_inflight(max_inflight: usize, n: usize) -> usize {
+    match max_inflight {
+        0 => n,
+        cap => n.min(cap).max(1),
+    }
+}
+
+/// Move the calling thread to the idle I/O scheduling class, so its disk
+/// requests are only served when nothing else is waiting (Linux only; the
+/// idle class needs BFQ or CFQ to have an effect).
+pub(crate) fn set_idle_io_priority() {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+        const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+        // who = 0 with IOPRIO_WHO_PROCESS means the calling thread
+        let _ = libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0 as libc::c_long,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::field_reassign_with_default)]
+    use super::*;
+
+    fn limiter(ops_per_sec: u64) -> Option<IoThrottle> {
+        let mut opt = Options::default();
+        opt.io_ops_per_sec = ops_per_sec;
+        IoThrottle::from_options(&opt)
+    }
+
+    #[test]
+    fn rate_spaces_operations() {
+        assert!(limiter(0).is_none());
+        let t = limiter(100).unwrap();
+        assert_eq!(t.interval, Duration::from_millis(10));
+        let start = Instant::now();
+        t.acquire(5);
+        t.acquire(1);
+        assert!(start.elapsed() >= Duration::from_millis(45));
+    }
+
+    #[test]
+    fn idle_time_saves_up_at_most_one_burst() {
+        let t = limiter(100).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        // 200ms worth of operations, of which only MAX_BURST were saved up
+        let start = Instant::now();
+        t.acquire(20);
+        t.acquire(1);
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    fn inflight_cap() {
+        assert_eq!(cap_inflight(0, 64), 64);
+        assert_eq!(cap_inflight(8, 64), 8);
+        assert_eq!(cap_inflight(8, 4), 4);
+        assert_eq!(cap_inflight(8, 0), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn idle_priority_applies_to_the_calling_thread() {
+        const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+        let class = || unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) } >> 13;
+        let before = class();
+        if before < 0 {
+            eprintln!("skip: ioprio_get not permitted");
+            return;
+        }
+        let idle = std::thread::spawn(move || {
+            set_idle_io_priority();
+            class()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(idle, 3);
+        assert_eq!(class(), before);
+    }
+}
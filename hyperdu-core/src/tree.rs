@@ -0,0 +1,422 @@
+use std::{
+    collections::hash_map::Entry,
+    ffi::OsStr,
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+use ahash::AHashMap as HashMap;
+
+use crate::{estimate::SizeEstimate, ScanErrorRecord, Stat, StatMap};
+
+/// Index of a directory in a [`ScanTree`]. The root is always [`ScanTree::ROOT`].
+pub type NodeId = u32;
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Node {
+    parent: NodeId,
+    name: (usize, u32),   // offset/len into `names`
+    children: (u32, u32), // offset/len into `child_ids`
+    stat: Stat,
+}
+
+/// Compact directory tree of a scan result.
+///
+/// Directories are stored in an arena indexed by [`NodeId`] with a parent index,
+/// interned name bytes and a contiguous children range per node, so a result
+/// costs a few dozen bytes per directory instead of a `PathBuf` each. Parents
+/// always have a smaller id than their children. Stats are rolled up (each node
+/// includes its whole subtree), like the maps returned by `scan_directory`.
+#[derive(Clone, Debug, Default)]
+pub struct ScanTree {
+    root: PathBuf,
+    nodes: Vec<Node>,
+    names: Vec<u8>,
+    child_ids: Vec<NodeId>,
+    pub(crate) errors: Vec<ScanErrorRecord>,
+    pub(crate) estimate: Option<SizeEstimate>,
+}
+
+impl ScanTree {
+    pub const ROOT: NodeId = 0;
+
+    /// Build from per-directory stats as collected by the workers (files counted
+    /// in their own directory only) and roll them up by node id.
+    pub(crate) fn from_dir_stats(root: &Path, map: &StatMap) -> Self {
+        let mut t = Self::build(root, map);
+        t.roll_up();
+        t
+    }
+
+    /// Turn per-directory stats into subtree totals, deepest node first.
+    pub(crate) fn roll_up(&mut self) {
+        for id in (1..self.nodes.len()).rev() {
+            let Node { parent, stat, .. } = self.nodes[id];
+            let p = &mut self.nodes[parent as usize].stat;
+            p.merge(&stat);
+            p.dirs += 1;
+        }
+    }
+
+    /// Build from an already rolled-up map such as `scan_directory` returns.
+    /// Entries outside `root` are ignored.
+    pub fn from_stat_map(root: &Path, map: &StatMap) -> Self {
+        Self::build(root, map)
+    }
+
+    fn build(root: &Path, map: &StatMap) -> Self {
+        let mut t = ScanTree {
+            root: root.to_path_buf(),
+            nodes: vec![Node::default()],
+            ..Default::default()
+        };
+        // Child lookup only needed while building; borrows names from the map keys.
+        let mut index: HashMap<(NodeId, &OsStr), NodeId> = HashMap::default();
+        for (path, stat) in map {
+            let Ok(rel) = path.strip_prefix(root) else {
+                continue;
+            };
+            let mut cur = Self::ROOT;
+            for comp in rel.components() {
+                let Component::Normal(name) = comp else {
+                    continue;
+                };
+                cur = match index.get(&(cur, name)) {
+                    Some(&id) => id,
+                    None => {
+                        let id = t.push(cur, name.as_encoded_bytes());
+                        index.insert((cur, name), id);
+                        id
+                    }
+                };
+            }
+            t.nodes[cur as usize].stat.merge(stat);
+        }
+        drop(index);
+        t.link_children();
+        t
+    }
+
+    fn push(&mut self, parent: NodeId, bytes: &[u8]) -> NodeId {
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            parent,
+            name: (self.names.len(), bytes.len() as u32),
+            ..Node::default()
+        });
+        self.names.extend_from_slice(bytes);
+        id
+    }
+
+    /// Fill `child_ids` so each node's children are one contiguous range.
+    fn link_children(&mut self) {
+        let mut counts = vec![0u32; self.nodes.len()];
+        for n in &self.nodes[1..] {
+            counts[n.parent as usize] += 1;
+        }
+        let mut off = 0u32;
+        for (n, &c) in self.nodes.iter_mut().zip(&counts) {
+            n.children = (off, 0);
+            off += c;
+        }
+        self.child_ids = vec![0; off as usize];
+        for id in 1..self.nodes.len() {
+            let p = self.nodes[id].parent as usize;
+            let (start, len) = self.nodes[p].children;
+            self.child_ids[(start + len) as usize] = id as NodeId;
+            self.nodes[p].children.1 += 1;
+        }
+    }
+
+    /// Path the tree was scanned from.
+    pub fn root_path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Paths the scan could not read (at most [`crate::MAX_ERROR_RECORDS`]).
+    /// Empty for live snapshots.
+    pub fn errors(&self) -> &[ScanErrorRecord] {
+        &self.errors
+    }
+
+    /// Totals estimated for the unexplored part when the scan ran out of its
+    /// time or file budget (`Options::budget_ms` / `budget_files`).
+    pub fn estimate(&self) -> Option<&SizeEstimate> {
+        self.estimate.as_ref()
+    }
+
+    /// Number of directories, including the root.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// All node ids; parents come before their children.
+    pub fn ids(&self) -> Range<NodeId> {
+        0..self.nodes.len() as NodeId
+    }
+
+    pub fn stat(&self, id: NodeId) -> Stat {
+        self.nodes[id as usize].stat
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        (id != Self::ROOT).then(|| self.nodes[id as usize].parent)
+    }
+
+    /// Last path component of the node (empty for the root).
+    pub fn name(&self, id: NodeId) -> &OsStr {
+        let (off, len) = self.nodes[id as usize].name;
+        let bytes = &self.names[off..off + len as usize];
+        // SAFETY: the bytes were produced by `OsStr::as_encoded_bytes` (see `push`).
+        unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        let (start, len) = self.nodes[id as usize].children;
+        &self.child_ids[start as usize..(start + len) as usize]
+    }
+
+    /// Full path of a node (allocates).
+    pub fn path(&self, id: NodeId) -> PathBuf {
+        let mut chain = Vec::new();
+        let mut cur = id;
+        while cur != Self::ROOT {
+            chain.push(cur);
+            cur = self.nodes[cur as usize].parent;
+        }
+        let mut p = self.root.clone();
+        for id in chain.into_iter().rev() {
+            p.push(self.name(id));
+        }
+        p
+    }
+
+    /// Look up a directory by path.
+    pub fn find(&self, path: &Path) -> Option<NodeId> {
+        let rel = path.strip_prefix(&self.root).ok()?;
+        let mut cur = Self::ROOT;
+        for comp in rel.components() {
+            let Component::Normal(name) = comp else {
+                continue;
+            };
+            cur = *self.children(cur).iter().find(|&&c| self.name(c) == name)?;
+        }
+        Some(cur)
+    }
+
+    /// Rolled-up stat of a directory by path.
+    pub fn get(&self, path: &Path) -> Option<Stat> {
+        self.find(path).map(|id| self.stat(id))
+    }
+
+    /// Reorder every children range by physical size, largest first.
+    pub fn sort_children_by_physical(&mut self) {
+        let nodes = &self.nodes;
+        for n in nodes {
+            let (start, len) = n.children;
+            self.child_ids[start as usize..(start + len) as usize]
+                .sort_unstable_by_key(|&c| std::cmp::Reverse(nodes[c as usize].stat.physical));
+        }
+    }
+
+    /// Convert to the path-keyed map returned by `scan_directory`: one entry
+    /// per scanned directory, all of them at or below the root.
+    pub fn to_stat_map(&self) -> StatMap {
+        let mut map = StatMap::default();
+        if self.nodes.is_empty() {
+            return map;
+        }
+        map.reserve(self.nodes.len());
+        // Parents precede children, so each path extends an already built one.
+        let mut paths: Vec<PathBuf> = Vec::with_capacity(self.nodes.len());
+        paths.push(self.root.clone());
+        for id in 1..self.nodes.len() {
+            let p = paths[self.nodes[id].parent as usize].join(self.name(id as NodeId));
+            paths.push(p);
+        }
+        for (p, n) in paths.into_iter().zip(&self.nodes) {
+            map.insert(p, n.stat);
+        }
+        map
+    }
+}
+
+/// Directory arena the scan fills: interned names and parent ids with
+/// per-directory (not rolled up) stats. Node 0 is the empty path and paths are
+/// interned component by component below it, so all roots of a scan share one
+/// arena. Workers keep one each; [`DirArena::absorb`] merges them and
+/// [`DirArena::subtree`] cuts out the [`ScanTree`] of a root.
+pub(crate) struct DirArena {
+    nodes: Vec<Node>,
+    names: Vec<u8>,
+    // (parent, name hash) -> child, so names live in `names` only
+    index: HashMap<(NodeId, u64), NodeId>,
+    // Further children whose (parent, name hash) was already taken
+    collisions: HashMap<(NodeId, u64), Vec<NodeId>>,
+    hasher: ahash::RandomState,
+}
+
+impl Default for DirArena {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+            names: Vec::new(),
+            index: HashMap::default(),
+            collisions: HashMap::default(),
+            hasher: ahash::RandomState::new(),
+        }
+    }
+}
+
+impl DirArena {
+    fn name(&self, id: NodeId) -> &[u8] {
+        let (off, len) = self.nodes[id as usize].name;
+        &self.names[off..off + len as usize]
+    }
+
+    fn lookup(&self, parent: NodeId, name: &[u8]) -> Option<NodeId> {
+        let key = (parent, self.hasher.hash_one(name));
+        let first = *self.index.get(&key)?;
+        if self.name(first) == name {
+            return Some(first);
+        }
+        let more = self.collisions.get(&key)?;
+        more.iter().copied().find(|&id| self.name(id) == name)
+    }
+
+    fn child(&mut self, parent: NodeId, name: &[u8]) -> NodeId {
+        if let Some(id) = self.lookup(parent, name) {
+            return id;
+        }
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            parent,
+            name: (self.names.len(), name.len() as u32),
+            ..Node::default()
+        });
+        self.names.extend_from_slice(name);
+        let key = (parent, self.hasher.hash_one(name));
+        match self.index.entry(key) {
+            Entry::Vacant(e) => {
+                e.insert(id);
+            }
+            Entry::Occupied(_) => self.collisions.entry(key).or_default().push(id),
+        }
+        id
+    }
+
+    fn find(&self, path: &Path) -> Option<NodeId> {
+        path.components().try_fold(0, |cur, c| {
+            self.lookup(cur, c.as_os_str().as_encoded_bytes())
+        })
+    }
+
+    /// Stats of the directory at `path`, created (with its ancestors) if new.
+    pub(crate) fn stat_mut(&mut self, path: &Path) -> &mut Stat {
+        let id = path.components().fold(0, |cur, c| {
+            self.child(cur, c.as_os_str().as_encoded_bytes())
+        });
+        &mut self.nodes[id as usize].stat
+    }
+
+    /// Fold in per-directory stats keyed by path (a worker's or the live map).
+    pub(crate) fn add_map(&mut self, map: impl IntoIterator<Item = (PathBuf, Stat)>) {
+        for (path, stat) in map {
+            self.stat_mut(&path).merge(&stat);
+        }
+    }
+
+    /// Merge another worker's arena into this one.
+    pub(crate) fn absorb(&mut self, other: DirArena) {
+        if self.nodes.len() == 1 {
+            *self = other;
+            return;
+        }
+        // Parents come before children in both arenas
+        let mut ids: Vec<NodeId> = vec![0; other.nodes.len()];
+        for id in 1..other.nodes.len() {
+            let n = &other.nodes[id];
+            ids[id] = self.child(ids[n.parent as usize], other.name(id as NodeId));
+            self.nodes[ids[id] as usize].stat.merge(&n.stat);
+        }
+    }
+
+    /// The directories at or below `root` as a tree that still holds
+    /// per-directory stats (see [`ScanTree::roll_up`]).
+    pub(crate) fn subtree(&self, root: &Path) -> ScanTree {
+        let mut t = ScanTree {
+            root: root.to_path_buf(),
+            nodes: vec![Node::default()],
+            ..Default::default()
+        };
+        let Some(top) = self.find(root) else {
+            return t;
+        };
+        t.nodes[0].stat = self.nodes[top as usize].stat;
+        const OUTSIDE: NodeId = NodeId::MAX;
+        let mut ids: Vec<NodeId> = vec![OUTSIDE; self.nodes.len()];
+        ids[top as usize] = ScanTree::ROOT;
+        for id in top as usize + 1..self.nodes.len() {
+            let n = &self.nodes[id];
+            let parent = ids[n.parent as usize];
+            if parent == OUTSIDE {
+                continue;
+            }
+            let new = t.push(parent, self.name(id as NodeId));
+            t.nodes[new as usize].stat = n.stat;
+            ids[id] = new;
+        }
+        t.link_children();
+        t
+    }
+
+    /// Exclusive bytes placed above all of `roots` (hardlinks whose links
+    /// span several roots end up in a common ancestor).
+    pub(crate) fn exclusive_outside(&self, roots: &[&Path]) -> u64 {
+        let mut inside = vec![false; self.nodes.len()];
+        for r in roots {
+            if let Some(id) = self.find(r) {
+                inside[id as usize] = true;
+            }
+        }
+        let mut sum = 0;
+        for id in 1..self.nodes.len() {
+            let n = &self.nodes[id];
+            inside[id] |= inside[n.parent as usize];
+            if !inside[id] {
+                sum += n.stat.exclusive;
+            }
+        }
+        sum
+    }
+}
+
+/// Result of [`crate::scan_roots`]: one tree per root and their grand total.
+#[derive(Clone, Debug, Default)]
+pub struct MultiScan {
+    pub(crate) trees: Vec<ScanTree>,
+    pub(crate) total: Stat,
+}
+
+impl MultiScan {
+    /// One tree per root, in the order the roots were given. A root inside
+    /// another one gets the matching subtree of the enclosing scan.
+    pub fn trees(&self) -> &[ScanTree] {
+        &self.trees
+    }
+
+    pub fn into_trees(self) -> Vec<ScanTree> {
+        self.trees
+    }
+
+    /// Grand total over all roots (like `du -c`). Nested roots and files
+    /// hardlinked between roots count once; `dirs` includes the roots
+    /// themselves and `exclusive` the hardlinks spread over several roots.
+    pub fn total(&self) -> Stat {
+        self.total
+    }
+}
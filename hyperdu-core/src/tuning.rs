@@ -140,9 +140,9 @@ pub fn start_if_enabled(
                     0.0
                 };
                 let mut batch = opt.uring_batch.load(Ordering::Relaxed);
-                // adjust batch within [64, 4096]
-                let min_b = 64usize;
-                let max_b = 4096usize;
+                // adjust batch within [64, 4096], never above the in-flight cap
+                let max_b = crate::throttle::cap_inflight(opt.uring_max_inflight, 4096);
+                let min_b = 64usize.min(max_b);
                 if dfail > 0 {
                     // queue was often full: be conservative
                     batch = batch.saturating_sub(64).max(min_b);
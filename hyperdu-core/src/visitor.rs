@@ -0,0 +1,115 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Entry type as reported by the platform backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// Per-entry metadata handed to a [`FileVisitor`] by the scan backends.
+///
+/// Fields the backend did not fetch for this entry are left at 0 (e.g. sizes and
+/// timestamps of directories discovered via `d_type`, or approximate-size files).
+/// Timestamps are seconds since the Unix epoch. `uid`/`gid` are `None` when not
+/// fetched and on Windows; `nlink` is 0 when unknown. `allocated` is the raw
+/// allocation reported by the filesystem, which unlike `physical` is not
+/// replaced by the logical size for files with no blocks; `None` when the
+/// backend did not fetch it.
+#[derive(Clone, Copy, Debug)]
+pub struct FileEntry<'a> {
+    pub parent: &'a Path,
+    pub name: &'a OsStr,
+    pub kind: EntryKind,
+    pub logical: u64,
+    pub physical: u64,
+    pub allocated: Option<u64>,
+    pub dev: u64,
+    pub ino: u64,
+    pub mtime: i64,
+    pub atime: i64,
+    pub ctime: i64,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub nlink: u64,
+}
+
+impl<'a> FileEntry<'a> {
+    /// Entry with only identity and type filled in.
+    #[inline]
+    pub fn new(parent: &'a Path, name: &'a OsStr, kind: EntryKind) -> Self {
+        Self {
+            parent,
+            name,
+            kind,
+            logical: 0,
+            physical: 0,
+            allocated: None,
+            dev: 0,
+            ino: 0,
+            mtime: 0,
+            atime: 0,
+            ctime: 0,
+            uid: None,
+            gid: None,
+            nlink: 0,
+        }
+    }
+
+    /// Full path of the entry (allocates).
+    pub fn path(&self) -> PathBuf {
+        self.parent.join(self.name)
+    }
+}
+
+/// Per-entry callback invoked from the scan workers.
+///
+/// Called concurrently from every worker thread, so implementations should keep
+/// the hot path cheap and use their own synchronization (atomics, sharded maps).
+/// Files are reported only when they are counted (after exclude, min-size and
+/// hardlink checks); directories when they are queued for traversal; symlinks
+/// that are not followed when they are skipped.
+pub trait FileVisitor: Send + Sync {
+    fn visit(&self, entry: &FileEntry<'_>);
+
+    /// Called instead of `visit` for a file skipped by hardlink dedupe, i.e. a
+    /// further link to an inode that was already counted elsewhere.
+    #[inline]
+    fn visit_link(&self, _entry: &FileEntry<'_>) {}
+}
+
+impl<F> FileVisitor for F
+where
+    F: Fn(&FileEntry<'_>) + Send + Sync,
+{
+    #[inline]
+    fn visit(&self, entry: &FileEntry<'_>) {
+        self(entry)
+    }
+}
+
+/// Fans every entry out to several visitors, in order.
+#[derive(Clone, Default)]
+pub struct VisitorSet(pub Vec<Arc<dyn FileVisitor>>);
+
+impl FileVisitor for VisitorSet {
+    #[inline]
+    fn visit(&self, entry: &FileEntry<'_>) {
+        for v in &self.0 {
+            v.visit(entry);
+        }
+    }
+
+    #[inline]
+    fn visit_link(&self, entry: &FileEntry<'_>) {
+        for v in &self.0 {
+            v.visit_link(entry);
+        }
+    }
+}
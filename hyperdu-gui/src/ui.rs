@@ -3,16 +3,19 @@ use std::{
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicU64, AtomicUsize, Ordering},
-        mpsc, Arc,
+        Arc,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use egui::{self, Align, FontData, FontDefinitions, FontFamily, Layout, RichText};
 use egui_extras::TableBuilder;
 use humansize::{format_size, BINARY};
 use hyperdu_core as core;
-use hyperdu_core::{Stat, StatMap};
+use hyperdu_core::{
+    age::{AgeHistogram, AgeVisitor, TimeKind, DEFAULT_AGE_DAYS},
+    histogram::{HistogramVisitor, SizeHistogram, HIST_BUCKETS},
+};
 
 fn puffin_frame() {}
 
@@ -25,8 +28,22 @@ pub struct App {
     follow: bool,
     scanning: bool,
     selected: Option<PathBuf>,
-    tree: Option<Node>,
-    rx: Option<mpsc::Receiver<Vec<(PathBuf, Stat)>>>,
+    tree: Option<core::ScanTree>,
+    scan: Option<core::ScanHandle>,
+    last_snapshot: Option<Instant>,
+    pending_dirs: usize,
+    // Optional per-directory size histograms
+    histogram: bool,
+    hist_visitor: Option<Arc<HistogramVisitor>>,
+    hists: HashMap<PathBuf, SizeHistogram>,
+    // Optional per-directory age buckets
+    age: bool,
+    age_kind: TimeKind,
+    age_visitor: Option<Arc<AgeVisitor>>,
+    ages: HashMap<PathBuf, AgeHistogram>,
+    // Paths the last finished scan could not read
+    errors: Vec<core::ScanErrorRecord>,
+    show_errors: bool,
     // Live metrics
     files_processed: Option<Arc<AtomicU64>>,
     start_at: Option<Instant>,
@@ -44,34 +61,28 @@ pub struct App {
 
 // Default is derived above
 
-#[derive(Clone)]
-struct Node {
-    path: PathBuf,
-    name: String,
-    stat: Stat,
-    children: Vec<Node>,
-}
-
-impl Default for Node {
-    fn default() -> Self {
-        Self {
-            path: PathBuf::new(),
-            name: String::new(),
-            stat: Stat::default(),
-            children: vec![],
-        }
-    }
-}
-
 impl App {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         configure_fonts(&cc.egui_ctx);
         Self::default()
     }
     pub fn start_scan(&mut self, root: PathBuf) {
+        if let Some(prev) = self.scan.take() {
+            prev.cancel();
+        }
         self.scanning = true;
-        let (tx, rx) = mpsc::channel();
-        self.rx = Some(rx);
+        self.tree = None;
+        self.selected = None;
+        self.last_snapshot = Some(Instant::now());
+        self.pending_dirs = 0;
+        self.errors.clear();
+        self.show_errors = false;
+        self.hists.clear();
+        self.hist_visitor = self.histogram.then(|| Arc::new(HistogramVisitor::new()));
+        self.ages.clear();
+        self.age_visitor = self
+            .age
+            .then(|| Arc::new(AgeVisitor::new(self.age_kind, &DEFAULT_AGE_DAYS)));
         let exclude = self.exclude.clone();
         let min_file = self.min_file;
         let max_depth = self.max_depth;
@@ -97,78 +108,137 @@ impl App {
         self.uring_enq = Some(uring_enq.clone());
         self.uring_cqe = Some(uring_cqe.clone());
         self.uring_err = Some(uring_err.clone());
-        std::thread::spawn(move || {
-            let mut opt = core::Options {
-                exclude_contains: exclude
-                    .split(',')
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect(),
-                max_depth,
-                min_file_size: min_file,
-                follow_links: follow,
-                threads: std::thread::available_parallelism()
-                    .map(|n| n.get())
-                    .unwrap_or(4),
-                progress_every: 8192,
-                progress_callback: None,
-                progress_path_callback: None,
-                compute_physical: true,
-                approximate_sizes: false,
-                dir_yield_every: dir_yield.clone(),
-                uring_batch,
-                uring_sq_depth: uring_depth,
-                uring_sqe_fail: uring_fail,
-                uring_submit_wait_ns: uring_wait_ns,
-                uring_sqe_enq: uring_enq,
-                uring_cqe_comp: uring_cqe,
-                uring_cqe_err: uring_err,
-                ..core::Options::default()
-            };
-            // Install progress callback with live tuning (quiet)
-            let t0 = Instant::now();
-            let last = Arc::new(std::sync::Mutex::new((0u64, t0)));
-            let lc = last.clone();
-            let y_atomic = opt.dir_yield_every.clone();
-            let yield_candidates: [usize; 5] = [8192, 16384, 32768, 65536, 131072];
-            let tuner = Arc::new(std::sync::Mutex::new((2usize, 1isize, 0.0f64))); // idx, dir, last_rate
-            let tuner_cl = tuner.clone();
-            opt.progress_callback = Some(Arc::new(move |n| {
-                files_counter.store(n, Ordering::Relaxed);
-                let now = Instant::now();
-                let (prev_n, prev_t) = *lc.lock().unwrap();
-                let dt = now.duration_since(prev_t).as_secs_f64().max(1e-6);
-                let dn = n.saturating_sub(prev_n) as f64;
-                let recent = dn / dt;
-                *lc.lock().unwrap() = (n, now);
-                // tune with 5% threshold
-                let mut st = tuner_cl.lock().unwrap();
-                let (ref mut idx, ref mut dir, ref mut last_rate) = *st;
-                if *last_rate == 0.0 {
-                    *last_rate = recent;
-                }
-                let degrade = recent < *last_rate * 0.95;
-                let improve = recent > *last_rate * 1.05;
-                if degrade {
-                    *dir = -*dir;
-                }
-                if degrade || improve {
-                    let new_idx = (*idx as isize + *dir)
-                        .clamp(0, (yield_candidates.len() - 1) as isize)
-                        as usize;
-                    if new_idx != *idx {
-                        *idx = new_idx;
-                        let new_y = yield_candidates[*idx];
-                        y_atomic.store(new_y, Ordering::Relaxed);
-                    }
-                }
+        let mut opt = core::Options {
+            exclude_contains: exclude
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            max_depth,
+            min_file_size: min_file,
+            follow_links: follow,
+            threads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            progress_every: 8192,
+            progress_callback: None,
+            progress_path_callback: None,
+            compute_physical: true,
+            approximate_sizes: false,
+            dir_yield_every: dir_yield.clone(),
+            uring_batch,
+            uring_sq_depth: uring_depth,
+            uring_sqe_fail: uring_fail,
+            uring_submit_wait_ns: uring_wait_ns,
+            uring_sqe_enq: uring_enq,
+            uring_cqe_comp: uring_cqe,
+            uring_cqe_err: uring_err,
+            ..core::Options::default()
+        };
+        // Install progress callback with live tuning (quiet)
+        let t0 = Instant::now();
+        let last = Arc::new(std::sync::Mutex::new((0u64, t0)));
+        let lc = last.clone();
+        let y_atomic = opt.dir_yield_every.clone();
+        let yield_candidates: [usize; 5] = [8192, 16384, 32768, 65536, 131072];
+        let tuner = Arc::new(std::sync::Mutex::new((2usize, 1isize, 0.0f64))); // idx, dir, last_rate
+        let tuner_cl = tuner.clone();
+        opt.progress_callback = Some(Arc::new(move |n| {
+            files_counter.store(n, Ordering::Relaxed);
+            let now = Instant::now();
+            let (prev_n, prev_t) = *lc.lock().unwrap();
+            let dt = now.duration_since(prev_t).as_secs_f64().max(1e-6);
+            let dn = n.saturating_sub(prev_n) as f64;
+            let recent = dn / dt;
+            *lc.lock().unwrap() = (n, now);
+            // tune with 5% threshold
+            let mut st = tuner_cl.lock().unwrap();
+            let (ref mut idx, ref mut dir, ref mut last_rate) = *st;
+            if *last_rate == 0.0 {
                 *last_rate = recent;
-            }));
-            let res = core::scan_directory(&root, &opt).unwrap_or_default();
-            let mut v: Vec<_> = res.into_iter().collect();
-            v.sort_unstable_by_key(|(_, s)| std::cmp::Reverse(s.physical));
-            let _ = tx.send(v);
-        });
+            }
+            let degrade = recent < *last_rate * 0.95;
+            let improve = recent > *last_rate * 1.05;
+            if degrade {
+                *dir = -*dir;
+            }
+            if degrade || improve {
+                let new_idx =
+                    (*idx as isize + *dir).clamp(0, (yield_candidates.len() - 1) as isize) as usize;
+                if new_idx != *idx {
+                    *idx = new_idx;
+                    let new_y = yield_candidates[*idx];
+                    y_atomic.store(new_y, Ordering::Relaxed);
+                }
+            }
+            *last_rate = recent;
+        }));
+        let mut visitors: Vec<Arc<dyn core::FileVisitor>> = Vec::new();
+        if let Some(h) = &self.hist_visitor {
+            visitors.push(h.clone());
+        }
+        if let Some(a) = &self.age_visitor {
+            visitors.push(a.clone());
+        }
+        let started = if visitors.is_empty() {
+            core::start_scan(&root, &opt)
+        } else {
+            core::start_scan_with_visitor(
+                &root,
+                &opt,
+                Arc::new(core::platform_scanner()),
+                Arc::new(core::VisitorSet(visitors)),
+            )
+        };
+        match started {
+            Ok(handle) => self.scan = Some(handle),
+            Err(e) => {
+                eprintln!("scan error: {e}");
+                self.scanning = false;
+            }
+        }
+    }
+
+    fn apply_tree(&mut self, mut tree: core::ScanTree) {
+        tree.sort_children_by_physical();
+        if let Some(h) = &self.hist_visitor {
+            self.hists = h.report(tree.root_path()).into_iter().collect();
+        }
+        if let Some(a) = &self.age_visitor {
+            self.ages = a.report(tree.root_path()).into_iter().collect();
+        }
+        if self.selected.is_none() {
+            self.selected = Some(tree.root_path().to_path_buf());
+        }
+        self.tree = Some(tree);
+    }
+
+    /// Refresh the tree from a live snapshot while scanning; swap in the final
+    /// tree once the scan thread is done.
+    fn poll_scan(&mut self, ctx: &egui::Context) {
+        let Some(handle) = &self.scan else {
+            return;
+        };
+        if handle.is_finished() {
+            let tree = self.scan.take().and_then(|h| h.join_tree().ok());
+            self.scanning = false;
+            self.pending_dirs = 0;
+            if let Some(tree) = tree {
+                self.errors = tree.errors().to_vec();
+                self.apply_tree(tree);
+            }
+            return;
+        }
+        let due = self
+            .last_snapshot
+            .map_or(true, |t| t.elapsed() >= Duration::from_millis(500));
+        if due {
+            let snap = handle.snapshot();
+            self.pending_dirs = snap.pending.len();
+            self.last_snapshot = Some(Instant::now());
+            self.apply_tree(snap.tree);
+        }
+        ctx.request_repaint_after(Duration::from_millis(50));
     }
 }
 
@@ -195,6 +265,15 @@ impl eframe::App for App {
                 ui.label("深さ上限(0=無制限)");
                 ui.add(egui::DragValue::new(&mut self.max_depth).range(0..=u32::MAX));
                 ui.checkbox(&mut self.follow, "リンク追従");
+                ui.checkbox(&mut self.histogram, "サイズ分布");
+                ui.checkbox(&mut self.age, "経過日数");
+                egui::ComboBox::from_id_salt("age_kind")
+                    .selected_text(format!("{:?}", self.age_kind))
+                    .show_ui(ui, |ui| {
+                        for k in [TimeKind::Mtime, TimeKind::Atime, TimeKind::Ctime] {
+                            ui.selectable_value(&mut self.age_kind, k, format!("{k:?}"));
+                        }
+                    });
                 if let Some(start) = self.start_at {
                     if let Some(counter) = &self.files_processed {
                         let n = counter.load(Ordering::Relaxed);
@@ -218,6 +297,9 @@ impl eframe::App for App {
                         ui.monospace(format!(
                             "files/s: {total_rate:.0} (recent {recent:.0})  yield: {y}"
                         ));
+                        if self.scanning {
+                            ui.monospace(format!("pending dirs: {}", self.pending_dirs));
+                        }
                         if let (Some(b), Some(d)) = (&self.uring_batch, &self.uring_depth) {
                             let depth = d.load(Ordering::Relaxed);
                             let batch = b.load(Ordering::Relaxed);
@@ -241,6 +323,16 @@ impl eframe::App for App {
                         }
                     }
                 }
+                if !self.errors.is_empty() {
+                    ui.separator();
+                    let text = format!(
+                        "{} 個のディレクトリを読み取れませんでした（一部のみ読み取れたものを含む）",
+                        self.errors.len()
+                    );
+                    if ui.link(RichText::new(text).color(ui.visuals().warn_fg_color)).clicked() {
+                        self.show_errors = true;
+                    }
+                }
                 if let Some(root) = &self.root {
                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                         ui.label(RichText::new(root.display().to_string()).monospace());
@@ -249,27 +341,21 @@ impl eframe::App for App {
             });
         });
 
-        // Receive scan result
-        if let Some(rx) = &self.rx {
-            if let Ok(v) = rx.try_recv() {
-                self.scanning = false;
-                let map: StatMap = v.into_iter().collect();
-                if let Some(root) = &self.root {
-                    self.tree = Some(build_tree(root, &map));
-                    self.selected = Some(root.clone());
-                }
-                self.rx = None;
-            } else if self.scanning {
-                ctx.request_repaint_after(std::time::Duration::from_millis(50));
-            }
-        }
+        self.poll_scan(ctx);
+
+        let errors = &self.errors;
+        egui::Window::new("読み取りエラー")
+            .open(&mut self.show_errors)
+            .default_size([640.0, 320.0])
+            .show(ctx, |ui| show_errors_table(ui, errors));
 
         egui::SidePanel::left("left")
             .resizable(true)
             .show(ctx, |ui| {
                 ui.heading("ディレクトリツリー");
-                if let Some(tree) = &mut self.tree {
-                    show_tree(ui, tree, &mut self.selected);
+                if let Some(tree) = &self.tree {
+                    let root = tree.root_path().to_path_buf();
+                    show_tree(ui, tree, core::ScanTree::ROOT, &root, &mut self.selected);
                 } else {
                     ui.label("スキャン結果なし");
                 }
@@ -278,8 +364,17 @@ impl eframe::App for App {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("内容");
             if let (Some(sel), Some(tree)) = (&self.selected, &self.tree) {
-                if let Some(node) = find_node(tree, sel) {
-                    show_children_table(ui, node);
+                if let Some(id) = tree.find(sel) {
+                    let age_days = self.age_visitor.as_ref().map(|a| a.days());
+                    show_children_table(
+                        ui,
+                        tree,
+                        id,
+                        sel,
+                        &self.hists,
+                        &self.ages,
+                        age_days.unwrap_or_default(),
+                    );
                 } else {
                     ui.label("選択ノードが見つかりません");
                 }
@@ -290,75 +385,82 @@ impl eframe::App for App {
     }
 }
 
-fn build_tree(root: &Path, map: &StatMap) -> Node {
-    // Build parent -> children index
-    let mut idx: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
-    for p in map.keys() {
-        if let Some(parent) = p.parent() {
-            idx.entry(parent.to_path_buf()).or_default().push(p.clone());
-        }
-    }
-    // Sort children by physical desc
-    for v in idx.values_mut() {
-        v.sort_by_key(|p| std::cmp::Reverse(map.get(p).map(|s| s.physical).unwrap_or(0)));
-    }
-    fn make_node(p: &Path, idx: &HashMap<PathBuf, Vec<PathBuf>>, map: &StatMap) -> Node {
-        let name = p
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or(p.as_os_str().to_string_lossy().as_ref())
-            .to_string();
-        let mut node = Node {
-            path: p.to_path_buf(),
-            name,
-            stat: *map.get(p).unwrap_or(&Stat::default()),
-            children: vec![],
-        };
-        if let Some(children) = idx.get(p) {
-            for c in children {
-                node.children.push(make_node(c, idx, map));
-            }
-        }
-        node
-    }
-    make_node(root, &idx, map)
-}
-
-fn show_tree(ui: &mut egui::Ui, node: &mut Node, selected: &mut Option<PathBuf>) {
+fn show_tree(
+    ui: &mut egui::Ui,
+    tree: &core::ScanTree,
+    id: core::NodeId,
+    path: &Path,
+    selected: &mut Option<PathBuf>,
+) {
+    let stat = tree.stat(id);
     let label = format!(
-        "{}  ({} / {})",
-        node.name,
-        format_size(node.stat.physical, BINARY),
-        format_size(node.stat.logical, BINARY)
+        "{}  ({}{} / {})",
+        node_name(tree, id),
+        partial_mark(&stat),
+        format_size(stat.physical, BINARY),
+        format_size(stat.logical, BINARY)
     );
-    let resp = egui::CollapsingHeader::new(label).show(ui, |ui| {
-        for child in &mut node.children {
-            show_tree(ui, child, selected);
-        }
-    });
+    // Stable id so expansion state survives live tree rebuilds (label changes)
+    let resp = egui::CollapsingHeader::new(label)
+        .id_salt(path)
+        .show(ui, |ui| {
+            for &child in tree.children(id) {
+                show_tree(ui, tree, child, &path.join(tree.name(child)), selected);
+            }
+        });
     if resp.header_response.clicked() {
-        *selected = Some(node.path.clone());
+        *selected = Some(path.to_path_buf());
     }
 }
 
-fn find_node<'a>(node: &'a Node, p: &Path) -> Option<&'a Node> {
-    if node.path == p {
-        return Some(node);
+/// `~` in front of sizes that only cover part of the subtree (unread
+/// directories or a cancelled scan).
+fn partial_mark(stat: &core::Stat) -> &'static str {
+    if stat.is_complete() {
+        ""
+    } else {
+        "~"
     }
-    for c in &node.children {
-        if let Some(n) = find_node(c, p) {
-            return Some(n);
-        }
+}
+
+fn node_name(tree: &core::ScanTree, id: core::NodeId) -> String {
+    if id == core::ScanTree::ROOT {
+        let root = tree.root_path();
+        return root
+            .file_name()
+            .unwrap_or(root.as_os_str())
+            .to_string_lossy()
+            .into_owned();
     }
-    None
+    tree.name(id).to_string_lossy().into_owned()
 }
 
-fn show_children_table(ui: &mut egui::Ui, parent: &Node) {
-    let total = parent.stat.physical.max(1);
-    let rows = parent.children.len();
-    let table = TableBuilder::new(ui)
+fn show_children_table(
+    ui: &mut egui::Ui,
+    tree: &core::ScanTree,
+    parent: core::NodeId,
+    parent_path: &Path,
+    hists: &HashMap<PathBuf, SizeHistogram>,
+    ages: &HashMap<PathBuf, AgeHistogram>,
+    age_days: &[u32],
+) {
+    let total = tree.stat(parent).physical.max(1);
+    let children = tree.children(parent);
+    let rows = children.len();
+    let show_hist = !hists.is_empty();
+    let mut table = TableBuilder::new(ui)
         .striped(true)
         .cell_layout(Layout::left_to_right(Align::Center))
+        .column(egui_extras::Column::auto());
+    let show_age = !ages.is_empty();
+    if show_hist {
+        table = table.column(egui_extras::Column::auto());
+    }
+    if show_age {
+        table = table.column(egui_extras::Column::auto());
+    }
+    // Exclusive ("freed if deleted") size, then the physical/logical bar
+    table = table
         .column(egui_extras::Column::auto())
         .column(egui_extras::Column::remainder());
     table
@@ -366,29 +468,162 @@ fn show_children_table(ui: &mut egui::Ui, parent: &Node) {
             header.col(|ui| {
                 ui.label(egui::RichText::new("名前").strong());
             });
+            if show_hist {
+                header.col(|ui| {
+                    ui.label(egui::RichText::new("サイズ分布").strong());
+                });
+            }
+            if show_age {
+                header.col(|ui| {
+                    ui.label(egui::RichText::new("経過日数").strong());
+                });
+            }
+            header.col(|ui| {
+                ui.label(egui::RichText::new("解放可能").strong())
+                    .on_hover_text("削除すると戻る容量（ツリー外と共有するハードリンクを除く）");
+            });
             header.col(|ui| {
                 ui.label(egui::RichText::new("サイズ(物理/論理)").strong());
             });
         })
         .body(|body| {
             body.rows(22.0, rows, |mut row| {
-                let i = row.index();
-                let child = &parent.children[i];
+                let child = children[row.index()];
+                let stat = tree.stat(child);
                 row.col(|ui| {
-                    ui.label(&child.name);
+                    ui.label(node_name(tree, child));
+                });
+                if show_hist {
+                    row.col(|ui| match hists.get(&parent_path.join(tree.name(child))) {
+                        Some(h) => histogram_bars(ui, h),
+                        None => {
+                            ui.label("-");
+                        }
+                    });
+                }
+                if show_age {
+                    row.col(|ui| match ages.get(&parent_path.join(tree.name(child))) {
+                        Some(h) => age_label(ui, h, age_days),
+                        None => {
+                            ui.label("-");
+                        }
+                    });
+                }
+                row.col(|ui| {
+                    ui.label(format_size(stat.exclusive, BINARY));
                 });
                 row.col(|ui| {
-                    let frac = (child.stat.physical as f64 / total as f64) as f32;
-                    ui.add(egui::ProgressBar::new(frac).show_percentage().text(format!(
-                        "{} / {}",
-                        format_size(child.stat.physical, BINARY),
-                        format_size(child.stat.logical, BINARY)
+                    let frac = (stat.physical as f64 / total as f64) as f32;
+                    let bar = ui.add(egui::ProgressBar::new(frac).show_percentage().text(format!(
+                        "{}{} / {}",
+                        partial_mark(&stat),
+                        format_size(stat.physical, BINARY),
+                        format_size(stat.logical, BINARY)
                     )));
+                    if !stat.is_complete() {
+                        bar.on_hover_text(format!(
+                            "{} 個のディレクトリが未読のため下限値です",
+                            stat.incomplete
+                        ));
+                    }
                 });
             });
         });
 }
 
+/// Paths that could not be read, with error kind, errno and system call.
+fn show_errors_table(ui: &mut egui::Ui, errors: &[core::ScanErrorRecord]) {
+    TableBuilder::new(ui)
+        .striped(true)
+        .cell_layout(Layout::left_to_right(Align::Center))
+        .column(egui_extras::Column::remainder())
+        .column(egui_extras::Column::auto())
+        .column(egui_extras::Column::auto())
+        .column(egui_extras::Column::auto())
+        .header(20.0, |mut header| {
+            for title in ["パス", "種類", "errno", "システムコール"] {
+                header.col(|ui| {
+                    ui.label(egui::RichText::new(title).strong());
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(20.0, errors.len(), |mut row| {
+                let e = &errors[row.index()];
+                row.col(|ui| {
+                    ui.label(RichText::new(e.path.display().to_string()).monospace());
+                });
+                row.col(|ui| {
+                    ui.label(e.kind.to_string());
+                });
+                row.col(|ui| {
+                    ui.label(e.errno.map_or_else(|| "-".into(), |n| n.to_string()));
+                });
+                row.col(|ui| {
+                    ui.label(e.syscall.unwrap_or("-"));
+                });
+            });
+        });
+}
+
+/// Small bar chart of file counts per log2 size bucket, with a hover breakdown.
+fn histogram_bars(ui: &mut egui::Ui, h: &SizeHistogram) {
+    const BAR_W: f32 = 3.0;
+    let size = egui::vec2(BAR_W * HIST_BUCKETS as f32, 18.0);
+    let (rect, resp) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let max = h.files.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let painter = ui.painter_at(rect);
+    let color = ui.visuals().selection.bg_fill;
+    for (i, files) in h.files.iter().enumerate() {
+        if *files == 0 {
+            continue;
+        }
+        let height = (rect.height() * (*files as f32 / max)).max(1.0);
+        let x = rect.left() + BAR_W * i as f32;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(x, rect.bottom() - height),
+            egui::pos2(x + BAR_W - 1.0, rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, color);
+    }
+    resp.on_hover_ui(|ui| {
+        for (i, files, bytes) in h.nonzero() {
+            let (lo, hi) = SizeHistogram::bucket_range(i);
+            let range = match hi {
+                Some(hi) => format!("{}–{}", format_size(lo, BINARY), format_size(hi, BINARY)),
+                None => format!("≥{}", format_size(lo, BINARY)),
+            };
+            ui.monospace(format!(
+                "{range:<20} {files:>9} files  {}",
+                format_size(bytes, BINARY)
+            ));
+        }
+    });
+}
+
+/// Share of bytes at least N days old per threshold, with a hover breakdown.
+fn age_label(ui: &mut egui::Ui, h: &AgeHistogram, days: &[u32]) {
+    let total = h.total_bytes().max(1) as f64;
+    let text = days
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let pct = h.older_than(i).1 as f64 * 100.0 / total;
+            format!("{d}日+ {pct:.0}%")
+        })
+        .collect::<Vec<_>>()
+        .join(" · ");
+    ui.label(text).on_hover_ui(|ui| {
+        for (i, d) in days.iter().enumerate() {
+            let (files, bytes) = h.older_than(i);
+            ui.monospace(format!(
+                "≥{d:<5}日 {files:>9} files  {}",
+                format_size(bytes, BINARY)
+            ));
+        }
+    });
+}
+
 fn configure_fonts(ctx: &egui::Context) {
     // Start from egui defaults and add UTF-8 capable system fallbacks (CJK, Emoji).
     let mut fonts = FontDefinitions::default();